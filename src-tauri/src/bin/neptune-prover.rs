@@ -0,0 +1,39 @@
+//! Reference remote prover, see [wallet::prover::remote].
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+use tracing_subscriber::EnvFilter;
+use wallet::prover::remote;
+
+#[derive(Parser)]
+struct Args {
+    #[clap(long, default_value = "127.0.0.1:9810")]
+    listen: SocketAddr,
+    /// hex encoded P-256 secret key, created if missing
+    #[clap(long, default_value = "prover.key")]
+    key_file: PathBuf,
+    /// hex encoded prover client key of a wallet allowed to use the prover,
+    /// see `get_remote_prover_client_key`, may be repeated, any wallet is
+    /// allowed if unset
+    #[clap(long)]
+    allow: Vec<String>,
+    /// proof collections produced at the same time
+    #[clap(long, default_value = "1")]
+    max_jobs: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .init();
+
+    let args = Args::parse();
+    let secret_key = remote::load_or_create_server_key(&args.key_file)?;
+    println!("server key: {}", remote::server_public_key(&secret_key));
+
+    remote::run_server(args.listen, secret_key, args.allow, args.max_jobs).await
+}
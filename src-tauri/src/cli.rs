@@ -1,21 +1,33 @@
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
+use anyhow::bail;
 use anyhow::Result;
 use clap::Parser;
 use neptune_cash::config_models::data_directory::DataDirectory;
 use neptune_cash::config_models::network::Network;
 
 use crate::rpc::client::RestRpcClient;
+use crate::rpc::consolidate::ConsolidateParams;
+use crate::rpc::sweep::SweepParams;
+use crate::rpc::transaction_status::BumpFeeParams;
 use crate::rpc::Output;
 use crate::rpc::SendToAddressParams;
 use crate::wallet::fake_archival_state::generate_snapshot;
+use crate::wallet::job::JobStatus;
+use crate::wallet::offline::UnsignedTransaction;
+use crate::wallet::sweep::SweepFilter;
 #[derive(Parser)]
 enum WalletCli {
     RUN(RunArgs),
     SEND(SendArgs),
     HISTORY(HistoryArgs),
     SNAPSHOT(SnapshotArgs),
+    TX(TxArgs),
+    CONSOLIDATE(ConsolidateArgs),
+    SWEEP(SweepArgs),
+    JOB(JobArgs),
 }
 
 #[derive(clap::Args)]
@@ -30,6 +42,9 @@ struct GlobalArgs {
 struct RunArgs {
     #[clap(flatten)]
     global: GlobalArgs,
+    /// create a watch-only wallet from an exported viewing key file
+    #[clap(long)]
+    watch_only: Option<PathBuf>,
 }
 
 #[derive(clap::Args)]
@@ -40,12 +55,125 @@ struct SendArgs {
     amount: String,
     #[clap(long)]
     address: String,
+    /// fee amount, or `auto` to use the medium estimate from recent blocks
     #[clap(long, default_value = "0.01")]
     fee: String,
     #[clap(long)]
     priority_fee: Option<String>,
     #[clap(long, default_value = FEE_ADDRESS)]
     fee_address: String,
+    /// the recipient pays the fee and priority fee out of `amount`
+    #[clap(long)]
+    subtract_fee: bool,
+    /// coin selection strategy, e.g. `oldest`, `branch_and_bound`, `min_inputs` or `privacy`
+    #[clap(long)]
+    input_rule: Option<String>,
+    /// print the selected inputs and change without sending
+    #[clap(long)]
+    preview: bool,
+    /// `proofcollection` or `singleproof`, defaults to the wallet setting
+    #[clap(long)]
+    proving: Option<String>,
+    /// print the job id and return instead of waiting for the broadcast
+    #[clap(long)]
+    detach: bool,
+}
+
+#[derive(clap::Args)]
+struct JobArgs {
+    #[clap(subcommand)]
+    command: JobCommand,
+}
+
+/// sends queued in the background, see `send --detach`
+#[derive(clap::Subcommand)]
+enum JobCommand {
+    LIST(JobListArgs),
+    /// wait for a job and print its progress
+    WAIT(JobIdArgs),
+    /// stop a job that has not been broadcast yet
+    CANCEL(JobIdArgs),
+}
+
+#[derive(clap::Args)]
+struct JobListArgs {
+    #[clap(flatten)]
+    global: GlobalArgs,
+}
+
+#[derive(clap::Args)]
+struct JobIdArgs {
+    #[clap(flatten)]
+    global: GlobalArgs,
+    #[clap(long)]
+    id: i64,
+}
+
+#[derive(clap::Args)]
+struct TxArgs {
+    #[clap(subcommand)]
+    command: TxCommand,
+}
+
+/// offline signing: `create` on the online wallet, `sign` on the wallet holding
+/// the mnemonic, `broadcast` on the online wallet again
+#[derive(clap::Subcommand)]
+enum TxCommand {
+    CREATE(TxCreateArgs),
+    SIGN(TxSignArgs),
+    BROADCAST(TxBroadcastArgs),
+    /// replace a pending transaction with one paying a higher fee
+    BUMP(TxBumpArgs),
+}
+
+#[derive(clap::Args)]
+struct TxCreateArgs {
+    #[clap(flatten)]
+    global: GlobalArgs,
+    #[clap(long)]
+    amount: String,
+    #[clap(long)]
+    address: String,
+    #[clap(long, default_value = "0.01")]
+    fee: String,
+    /// file the unsigned transaction is written to
+    #[clap(long, short)]
+    output: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct TxSignArgs {
+    #[clap(flatten)]
+    global: GlobalArgs,
+    /// unsigned transaction file
+    #[clap(long, short)]
+    input: PathBuf,
+    /// file the signed transaction is written to
+    #[clap(long, short)]
+    output: PathBuf,
+    /// sign without asking for confirmation
+    #[clap(long, short)]
+    yes: bool,
+}
+
+#[derive(clap::Args)]
+struct TxBroadcastArgs {
+    #[clap(flatten)]
+    global: GlobalArgs,
+    /// signed transaction file
+    #[clap(long, short)]
+    input: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct TxBumpArgs {
+    #[clap(flatten)]
+    global: GlobalArgs,
+    #[clap(long)]
+    txid: String,
+    /// new total fee, the increase is taken from the change
+    #[clap(long)]
+    fee: String,
 }
 
 #[derive(clap::Args)]
@@ -62,6 +190,37 @@ struct SnapshotArgs {
 
 const FEE_ADDRESS:&'static str = "nolgam1nurfm22evhpscn5ddwgwa96z0048454c84hwapmvqq6rqqwqx4w34kudq6q5adjvgch8f8v9dsfz3h0vk60npzya04248umqq2xs9n9cznxzl92nh65k6pg60jesff6wu77l8e3c2h8yyjtwwd9kz00m6z7nl5vxk5929q34837shxn4x5t6p9wgheljlfs3kp7lnrl2z0an80y50lwzm704svvpw3ze5k9fkccttuhunjn96cr3jcgt80qggj5x9ltta5z3qmyxhxxmz9ns7kddcrtun0mfd5fz2d05xnkhjzp3pphc83jytrecc437gf7e9czqh9qfhw5000f43ghyc2dfa5vcl38rwzax27kuv0e0gtkj7q2ar3dt0q6y32fdp9nhtm9l4crg7ud7w6vlg28ncns5q4f86teneuu8ezs2zur30gscw5qk9dgmter2nzryph5k2r68k5xf5pf7lkjas9km6eu6jjl2ujfjv5572xqrdrymm3mne6gptpvg54qxfwp3kkm45fvc5knjecsv7w5dfx82u9kcl5mrdd39k8dgc6gddty49f4yy32nfczhxq0k5dx5qmyet273mz6ggthrtvsxtteg3ceg366pnhmgaplejmjgq7qyyc0vz43ecvry8k7p7ddysqutxgpm6w950mzcxcppe5rm6pkjv9tv5uxyx3kz8lpd744udfc8h0575lfkxuwfp4y3uf9nu3fzj8x2r4gt8y3wtwdlf3flldp0m289jc3lh0dv9372dxk7fddx3ns9acfz7cdxsluucxnrn7e8p7lx5h3ngztft68ae5fcnplekay90kvnqjnxr3e80q4xl0nufucchr66p6swa2gkptf85304wwjktllz7f2sswpx3qkpld8mku900jz0g6e2q9y806enem49qud89uqu6z8d98v9sux5anr2v88hr80jqz7t7g4dcj5spgnc0l996lrq0hfswzfwldx7klsxk82zlpfzwpfgkmu3gkdyqnh9salfwrckn95tk0k0kyhrkchhaplehldfj5wf6dnkhapaxhzwfzu8gglp2rf3jtpx7ew3hlq6yqtxtrfxu0ctwsycj9eqccnlpg77mjs292t39kz4n99vjd2yejuxztk4828yk2wk5urejc3fd00gwqmcxl4k2pw85vmxrvv8n9dv6amcgkmuhgfzfcy3wm0p5yhtvdhs4l0447au6x7kwdhmuxjgk7x80gtdmgd74zswdw0jkngwef2zctxnuktxp4e5fqftgw0yplq0d3lcrcqg6q3rw5ljc654adhee53xmmeaazg0avtzkt2q0ngsq8xuxxcax8u2x9zhcxjltcsewhe7ffzqrkznv3z3vuhar4whazsergmymz4jx2d3l8qwrlhcducztkkeygm8luwnrmh2fcrpkg79gj34u88e72ljt94aapkn5uunu457h2kc3czpgekjl2wjyuz9wcpyfk3z22xx7lx7etchn5mfqxpvjf63wcy0sd9qap8mwnmfzs5j4zh9jv8n8jdwvjyk5d3x0j42cdvh5zhq00g429j0vrvm8097vfq2fg2axhrzfuy6qv97swl39dm3q859guyk4pqv9a82kz5wgnvs84l9g3g5wjf9z888spenf97ddaprkxvxluhg268hst8jgfa78t4nrqklgvw6f630nt4yrsddwahmfcfux9gmt0zjyg9vkfrfct8qtg9lehrvgmwq4e7h6ys6r34l2xn82fy2ey5wwq0jn6vk52vugmzlpgc0aywltxqzn7dvz6dlec98en9f482vdmhf33th0k5nrpwq3qj6xg7ve09nna3kp3ff4nhknt4etqhzauc8v2047yl72yefh4zddc6g9s4ye4hvukulhhu37gqrll7qyg0sx6gtgalwgwcc50gd00m90vzca8mxykdqjhfesxre99ahmfcpa2xtqftzlvu8ag55wqm84rqapa06774v876lms39y5mx0r67mus4n45crh4j99f6wptmcmy9q8hqlnl8qgvxetx3ce3kla74uwuleh7jkzdpafgcvl7amv0s8usgg6z2nr3utc4xg5qgzaf5zw3tjnak72e0ptl86k5d2667pkzauq35c7x83tms2ysev6x20h5am89qu6mm77f8f7cemtd4hhxh4qp6ae55krpst59656mqzpzc8uup42mxrarc298n7y86ekgrgft3nkasfa30u9w50dxt6gx3rpyvpgsyv8nz3d0dhzgdtkt7gxd6nj02awyesdmncj0pwzdp59gh2c09rqfm7x8t7le70ej2dd7ncq2z2qwl0cphu8ds5hxzegur3mlrrqx0zdvmje79s86ads9v6srn2skztz7mlr47f2xs43tt2eejx0j66ukqusg2ltjjxe79efggq022u9j8dqd6qcuedrfhhm8rqg6na9rcuq35aqn40q4llseyrdz68x5enuyt7yhk3d3kqxwjfullcrqhtc82vzraw0pdgjxpjtxgjvrqeqfdn7j9ck57w2u5dppfuvkk52cc3mn28nnshn87j84vfd3tdkqu9wl037yn49l829gftaky623476hw4wc7x26al8q7mfsg56pmzlyzdmgqsa33r37k0thurnjasahp3c9z5mwk3zgtgtfvj2qydgz5su6wvewhh7yeqft8z2ze4j99qha32wagywmjuqhtff3v7wpdmrcu84zmlxd5zhf5lngp4t070uup93w7lv95uk6ckhrqq4fx8epcuynh6qwh86a03nvnjf7vxvmkae2l2qzu24pjz8wdtwqs87pfdhzcwj29ruzh9ag54zqe8qzw46azds62ug7qxgf3z00rgu5q28newruew6pcvv7w7uvs9fzchha5awsfk2xfjtyu3ml5y98m2fs7peusgwv9r78uy8w6stzgc9prtsa57l03l7sfhakkt40va06uwva5qc6vy8mztwkdw2z69xpzuf4qaz9rk83wtjqjj5xvxp4xjpeple9dxgxp0tqhqzt2f8t8r03dn0vx9tl6tnh7mn6k2tnatwqkjx0csz5fj7a3g4fs07rv2p2hxag0hc8p29hx4skh0xp6x2y6afwrs5jx8hagl8pm320wwwfeh2zsernkgul5jhpy2ea5tjf934z6qgwsxezex94w935z2txr8gw3fcsrpp4m94nmwmap3pe6xyw5qlz7yyjg9merzckv6lxe5k8rtysn7fgzy3f5ug99hzq29gpllklmja7sdjg2wwgxee6m5nqercjx48cta7qp4q6hyerdts4fc5ly0hemn9rnygwng4hckqc7le3u7jpemgjxjc4rudzdekqllkg88k9p3m0gadjm4s2ha5r42p0cv5ss44n7kfyzw4scpyjw0alt2rmuwckvezejusxsxdqu6c8ad0ja7fqh2e4";
 
+/// merge the smallest utxos into a single one
+#[derive(clap::Args)]
+struct ConsolidateArgs {
+    #[clap(flatten)]
+    global: GlobalArgs,
+    /// number of utxos merged by the transaction
+    #[clap(long)]
+    max_inputs: Option<usize>,
+    #[clap(long, default_value = "0.01")]
+    fee: String,
+}
+
+/// send all spendable utxos to one address, the fee is taken from the amount
+#[derive(clap::Args)]
+struct SweepArgs {
+    #[clap(flatten)]
+    global: GlobalArgs,
+    #[clap(long)]
+    address: String,
+    #[clap(long, default_value = "0.01")]
+    fee: String,
+    /// only sweep keys from this derivation index on
+    #[clap(long)]
+    index_start: Option<u64>,
+    /// only sweep keys below this derivation index
+    #[clap(long)]
+    index_end: Option<u64>,
+    #[clap(long)]
+    min_confirmations: Option<u64>,
+}
+
 #[derive(clap::Args)]
 struct HistoryArgs {
     #[clap(flatten)]
@@ -84,6 +243,18 @@ pub async fn run() {
         WalletCli::SNAPSHOT(args) => {
             snapshot(args).await.unwrap();
         }
+        WalletCli::TX(args) => {
+            tx(args).await.unwrap();
+        }
+        WalletCli::CONSOLIDATE(args) => {
+            consolidate(args).await.unwrap();
+        }
+        WalletCli::SWEEP(args) => {
+            sweep(args).await.unwrap();
+        }
+        WalletCli::JOB(args) => {
+            job(args).await.unwrap();
+        }
     }
 }
 
@@ -111,7 +282,22 @@ async fn run_server(args: RunArgs) -> Result<()> {
     crate::rpc::commands::try_password().await.unwrap();
 
     let wallets = crate::rpc::commands::get_wallets().await.unwrap();
-    if wallets.is_empty() {
+    if wallets.is_empty() && args.watch_only.is_some() {
+        let viewing_key = tokio::fs::read_to_string(args.watch_only.unwrap()).await?;
+
+        let start_height = dialoguer::Input::new()
+            .with_prompt("Enter the start height:")
+            .default(0)
+            .interact_text()?;
+
+        crate::command::commands::add_watch_only_wallet(
+            "watch-only".to_string(),
+            viewing_key,
+            start_height,
+        )
+        .await
+        .expect("Failed to add watch-only wallet");
+    } else if wallets.is_empty() {
         // ask user to input mnemonic
         let mnemonic: String = dialoguer::Input::new()
             .with_prompt("Enter your mnemonic(divide by space):")
@@ -162,23 +348,129 @@ async fn send(args: SendArgs) -> Result<()> {
     let mut outputs = vec![Output {
         address: args.address,
         amount: args.amount,
+        subtract_fee: args.subtract_fee,
+        priority_fee: false,
     }];
 
     if let Some(fee) = args.priority_fee {
         outputs.push(Output {
             address: args.fee_address,
             amount: fee,
+            subtract_fee: false,
+            priority_fee: true,
         });
     }
 
+    let fee = if args.fee == "auto" {
+        let estimate = rest_client.estimate_fee().await?;
+        println!(
+            "estimated fee: {} (low {}, high {}, {} blocks)",
+            estimate.medium, estimate.low, estimate.high, estimate.samples
+        );
+        estimate.medium
+    } else {
+        args.fee
+    };
+
     let params = SendToAddressParams {
         outputs,
-        fee: args.fee,
-        input_rule: None,
+        fee,
+        input_rule: args.input_rule,
         inputs: vec![],
+        preview: args.preview,
+        proving: args.proving,
     };
-    let txid = rest_client.send(&params).await?;
-    println!("txid: {}", txid);
+
+    if args.preview {
+        let preview = rest_client.preview_send(&params).await?;
+        println!("{:#?}", preview);
+        return Ok(());
+    }
+
+    let id = rest_client.submit_send_job(&params).await?;
+    println!("job id: {}", id);
+    if args.detach {
+        return Ok(());
+    }
+
+    wait_job(&rest_client, id).await
+}
+
+/// Polls a job until it finishes, ctrl-c cancels it.
+async fn wait_job(rest_client: &RestRpcClient, id: i64) -> Result<()> {
+    let mut stage = None;
+    let mut progress = None;
+    loop {
+        let job = tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                match rest_client.cancel_job(id).await {
+                    Ok(_) => println!("cancelling job {}", id),
+                    Err(e) => println!("cannot cancel job {}: {}", id, e),
+                }
+                continue;
+            }
+            job = async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                rest_client.get_job(id).await
+            } => job?,
+        };
+
+        if job.stage != stage {
+            if let Some(stage) = &job.stage {
+                println!("{:?}", stage);
+            }
+            stage = job.stage;
+        }
+        if job.progress != progress {
+            if let Some(progress) = &job.progress {
+                println!(
+                    "  {:?} ({}/{})",
+                    progress.stage, progress.done, progress.total
+                );
+            }
+            progress = job.progress;
+        }
+
+        match job.status {
+            JobStatus::Succeeded => {
+                println!("txid: {}", job.txid.unwrap_or_default());
+                return Ok(());
+            }
+            JobStatus::Failed(e) => bail!("job {} failed: {}", id, e),
+            JobStatus::Unknown(e) => bail!("job {} was interrupted: {}", id, e),
+            JobStatus::Cancelled => {
+                println!("job {} cancelled", id);
+                return Ok(());
+            }
+            JobStatus::Queued | JobStatus::Running => {}
+        }
+    }
+}
+
+async fn job(args: JobArgs) -> Result<()> {
+    match args.command {
+        JobCommand::LIST(args) => {
+            let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+            let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+            for job in rest_client.list_jobs().await? {
+                println!("{:?}", job);
+            }
+        }
+        JobCommand::WAIT(args) => {
+            let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+            let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+            wait_job(&rest_client, args.id).await?;
+        }
+        JobCommand::CANCEL(args) => {
+            let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+            let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+            rest_client.cancel_job(args.id).await?;
+            println!("cancelling job {}", args.id);
+        }
+    }
 
     Ok(())
 }
@@ -198,6 +490,138 @@ async fn history(args: HistoryArgs) -> Result<()> {
     Ok(())
 }
 
+async fn consolidate(args: ConsolidateArgs) -> Result<()> {
+    let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+    let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+    let resp = rest_client
+        .consolidate(&ConsolidateParams {
+            max_inputs: args.max_inputs,
+            fee: args.fee,
+        })
+        .await?;
+
+    match resp.txid {
+        Some(txid) => println!("consolidated {} utxos, txid: {}", resp.inputs, txid),
+        None => println!("nothing to consolidate"),
+    }
+
+    Ok(())
+}
+
+async fn sweep(args: SweepArgs) -> Result<()> {
+    let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+    let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+    let resp = rest_client
+        .sweep(&SweepParams {
+            address: args.address,
+            fee: args.fee,
+            filter: SweepFilter {
+                index_start: args.index_start,
+                index_end: args.index_end,
+                min_confirmations: args.min_confirmations,
+            },
+        })
+        .await?;
+
+    println!(
+        "swept {} utxos, {} sent, txid: {}",
+        resp.inputs, resp.amount, resp.txid
+    );
+
+    Ok(())
+}
+
+async fn tx(args: TxArgs) -> Result<()> {
+    match args.command {
+        TxCommand::CREATE(args) => {
+            let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+            let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+            let params = SendToAddressParams {
+                outputs: vec![Output {
+                    address: args.address,
+                    amount: args.amount,
+                    subtract_fee: false,
+                    priority_fee: false,
+                }],
+                fee: args.fee,
+                input_rule: None,
+                inputs: vec![],
+                preview: false,
+                proving: None,
+            };
+            let unsigned = rest_client.create_unsigned_transaction(&params).await?;
+            tokio::fs::write(&args.output, &unsigned.unsigned_tx).await?;
+
+            println!(
+                "inputs: {:?}, input amount: {}, fee: {}",
+                unsigned.inputs, unsigned.input_amount, unsigned.fee
+            );
+            println!("unsigned transaction written to {}", args.output.display());
+        }
+        TxCommand::SIGN(args) => {
+            let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+            let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+            let unsigned_tx = tokio::fs::read_to_string(&args.input).await?;
+            let unsigned = UnsignedTransaction::decode(&unsigned_tx)?;
+            let Some(change) = unsigned.change_amount() else {
+                bail!("inputs do not cover the outputs and fee");
+            };
+
+            println!("network: {}", unsigned.network);
+            for (address, amount) in &unsigned.outputs {
+                println!(
+                    "output: {} to {}",
+                    amount,
+                    address.to_bech32m(unsigned.network)?
+                );
+            }
+            println!("change: {}", change);
+            println!("fee: {}", unsigned.fee);
+
+            if !args.yes
+                && !dialoguer::Confirm::new()
+                    .with_prompt("Sign this transaction?")
+                    .default(false)
+                    .interact()?
+            {
+                bail!("signing cancelled");
+            }
+
+            let signed = rest_client.sign_transaction(unsigned_tx).await?;
+            tokio::fs::write(&args.output, &signed.signed_tx).await?;
+
+            println!("txid: {}", signed.txid);
+            println!("signed transaction written to {}", args.output.display());
+        }
+        TxCommand::BROADCAST(args) => {
+            let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+            let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+            let signed_tx = tokio::fs::read_to_string(&args.input).await?;
+            let txid = rest_client.broadcast_signed_transaction(signed_tx).await?;
+            println!("txid: {}", txid);
+        }
+        TxCommand::BUMP(args) => {
+            let data_dir = DataDirectory::get(args.global.data_dir, Network::Main)?.root_dir_path();
+            let rest_client = RestRpcClient::new(read_token(&data_dir).await?);
+
+            let resp = rest_client
+                .bump_fee(&BumpFeeParams {
+                    txid: args.txid,
+                    fee: args.fee,
+                })
+                .await?;
+            println!("replaced {} with txid: {}", resp.replaced, resp.txid);
+        }
+    }
+
+    Ok(())
+}
+
 async fn snapshot(args: SnapshotArgs) -> Result<()> {
     crate::rpc_client::node_rpc_client().set_rest_server(
         args.global
@@ -4,33 +4,26 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use neptune_cash::api::export::Network;
+use neptune_cash::api::export::TxProvingCapability;
 
+use super::Result;
+use super::TauriCommandResultExt;
 use crate::config::wallet::ScanConfig;
 use crate::config::wallet::WalletData;
 use crate::config::Config;
+use crate::prover::remote::RemoteProverConfig;
+use crate::rpc::error::ErrorCode;
+use crate::rpc::error::RestError;
 use crate::rpc_client;
 use crate::wallet::block_cache::BlockCacheFile;
 use crate::wallet::block_cache::PersistBlockCache;
+use crate::wallet::consolidate::ConsolidateConfig;
 use crate::wallet::fake_archival_state::generate_snapshot;
+use crate::wallet::registry::WalletRegistry;
 use crate::wallet::sync::SyncState;
+use crate::wallet::upgrader::UpgraderConfig;
 use crate::wallet::wallet_file;
-
-type Result<T> = std::result::Result<T, String>;
-
-pub trait TauriCommandResultExt {
-    type Output;
-
-    /// Converts any error into a string automatically for Tauri commands
-    fn into_tauri_result(self) -> std::result::Result<Self::Output, String>;
-}
-
-impl<T> TauriCommandResultExt for std::result::Result<T, anyhow::Error> {
-    type Output = T;
-
-    fn into_tauri_result(self) -> std::result::Result<T, String> {
-        self.map_err(|e| format!("{:#?}", e))
-    }
-}
+use crate::wallet::webhook::WebhookConfig;
 
 #[cfg_attr(feature = "gui", tauri::command)]
 #[cfg_attr(not(feature = "gui"), allow(unused))]
@@ -52,7 +45,7 @@ pub async fn get_remote_rest() -> Result<String> {
 #[cfg_attr(feature = "gui", tauri::command)]
 #[cfg_attr(not(feature = "gui"), allow(unused))]
 pub async fn set_network(network: String) -> Result<()> {
-    let network = Network::from_str(&network).map_err(|e| e.to_string())?;
+    let network = Network::from_str(&network).map_err(RestError::invalid_params)?;
     let config = crate::service::get_state::<Arc<Config>>();
     config.set_network(network).await.into_tauri_result()?;
     set_wallet_id(-1).await?;
@@ -84,6 +77,122 @@ pub async fn get_disk_cache() -> Result<bool> {
     Ok(config.get_disk_cache().await.into_tauri_result()?)
 }
 
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn set_remote_prover(prover: Option<RemoteProverConfig>) -> Result<()> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    config.set_remote_prover(prover).await.into_tauri_result()?;
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn get_remote_prover() -> Result<Option<RemoteProverConfig>> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    Ok(config.get_remote_prover().await.into_tauri_result()?)
+}
+
+/// key to allow on the remote prover for this wallet
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn get_remote_prover_client_key() -> Result<String> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    let secret = config.get_secret_key().await.into_tauri_result()?;
+    Ok(crate::prover::remote::client_public_key(&secret).into_tauri_result()?)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn set_proof_upgrader(upgrader: Option<UpgraderConfig>) -> Result<()> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    config
+        .set_proof_upgrader(upgrader)
+        .await
+        .into_tauri_result()?;
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn get_proof_upgrader() -> Result<Option<UpgraderConfig>> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    Ok(config.get_proof_upgrader().await.into_tauri_result()?)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn set_webhooks(webhooks: Option<WebhookConfig>) -> Result<()> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    config.set_webhooks(webhooks).await.into_tauri_result()?;
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn get_webhooks() -> Result<Option<WebhookConfig>> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    Ok(config.get_webhooks().await.into_tauri_result()?)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn set_synced_wallets(ids: Option<Vec<i64>>) -> Result<()> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    config.set_synced_wallets(ids).await.into_tauri_result()?;
+    if let Some(registry) = crate::service::try_get_state::<Arc<WalletRegistry>>() {
+        registry
+            .update_selection(&config)
+            .await
+            .into_tauri_result()?;
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn get_synced_wallets() -> Result<Option<Vec<i64>>> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    Ok(config.get_synced_wallets().await.into_tauri_result()?)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn set_tx_proving_capability(capability: TxProvingCapability) -> Result<()> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    config
+        .set_tx_proving_capability(capability)
+        .await
+        .into_tauri_result()?;
+    Ok(())
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn get_tx_proving_capability() -> Result<TxProvingCapability> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    Ok(config
+        .get_tx_proving_capability()
+        .await
+        .into_tauri_result()?)
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn set_auto_consolidate(consolidate: Option<ConsolidateConfig>) -> Result<()> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    config
+        .set_auto_consolidate(consolidate)
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn get_auto_consolidate() -> Result<Option<ConsolidateConfig>> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    config.get_auto_consolidate().await.into_tauri_result()
+}
+
 #[cfg_attr(feature = "gui", tauri::command)]
 pub async fn add_wallet(
     name: String,
@@ -118,10 +227,35 @@ pub async fn add_wallet(
     Ok(id)
 }
 
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn add_watch_only_wallet(
+    name: String,
+    viewing_key: String,
+    start_height: u64,
+) -> Result<i64> {
+    let config = crate::service::get_state::<Arc<Config>>();
+
+    let scan_config = ScanConfig {
+        start_height,
+        ..Default::default()
+    };
+
+    let id = config
+        .add_watch_only_wallet(&name, &viewing_key, scan_config)
+        .await
+        .into_tauri_result()?;
+
+    Ok(id)
+}
+
 #[cfg_attr(feature = "gui", tauri::command)]
 #[cfg_attr(not(feature = "gui"), allow(unused))]
 pub async fn remove_wallet(id: i64) -> Result<()> {
     let config = crate::service::get_state::<Arc<Config>>();
+    if let Some(registry) = crate::service::try_get_state::<Arc<WalletRegistry>>() {
+        registry.close(id).await;
+    }
     config.remove_wallet(id).await.into_tauri_result()?;
     wallet_file::delete_wallet(config.as_ref(), id)
         .await
@@ -135,10 +269,10 @@ pub async fn export_wallet(password: String, id: i64) -> Result<Vec<String>> {
     let config = crate::service::get_state::<Arc<Config>>();
     let config_password = config.password.lock().await.clone();
     if config_password.is_none() {
-        return Err("password is not set".to_string());
+        return Err(RestError::new(ErrorCode::Locked, "password is not set"));
     }
     if password != config_password.unwrap() {
-        return Err("wrong password".to_string());
+        return Err(RestError::new(ErrorCode::WrongPassword, "wrong password"));
     }
     let mnemonic: Vec<String> = config
         .get_wallet_mnemonic(id)
@@ -148,6 +282,25 @@ pub async fn export_wallet(password: String, id: i64) -> Result<Vec<String>> {
     Ok(mnemonic)
 }
 
+#[cfg_attr(feature = "gui", tauri::command)]
+#[cfg_attr(not(feature = "gui"), allow(unused))]
+pub async fn export_viewing_key(password: String, id: i64, num_keys: u64) -> Result<String> {
+    let config = crate::service::get_state::<Arc<Config>>();
+    let config_password = config.password.lock().await.clone();
+    if config_password.is_none() {
+        return Err(RestError::new(ErrorCode::Locked, "password is not set"));
+    }
+    if password != config_password.unwrap() {
+        return Err(RestError::new(ErrorCode::WrongPassword, "wrong password"));
+    }
+    let viewing_key = config
+        .get_wallet_viewing_key(id, num_keys)
+        .await
+        .context("failed to get wallet viewing key")
+        .into_tauri_result()?;
+    Ok(viewing_key)
+}
+
 #[cfg_attr(feature = "gui", tauri::command)]
 pub async fn get_wallets() -> Result<Vec<WalletData>> {
     let config = crate::service::get_state::<Arc<Config>>();
@@ -168,13 +321,14 @@ pub async fn set_wallet_id(id: i64) -> Result<()> {
         config.set_wallet_id(id).await.into_tauri_result()?;
     }
 
-    if let Some(sync_state) = crate::service::try_get_state::<Arc<SyncState>>() {
-        sync_state.cancel_sync().await;
-    };
-
-    let sync_state = Arc::new(SyncState::new(&config).await.into_tauri_result()?);
-    crate::service::manage_or_replace(sync_state.clone());
-    sync_state.sync().await;
+    // a negative id reloads after the network changed, wallet ids are per
+    // network so every wallet is restarted
+    match crate::service::try_get_state::<Arc<WalletRegistry>>() {
+        Some(registry) if id >= 0 => registry.switch_to(&config, id).await.into_tauri_result()?,
+        _ => {
+            WalletRegistry::restart(&config).await.into_tauri_result()?;
+        }
+    }
 
     Ok(())
 }
@@ -212,7 +366,7 @@ pub async fn set_password(old_password: String, password: String) -> Result<()>
 #[cfg_attr(feature = "gui", tauri::command)]
 pub async fn has_password() -> Result<bool> {
     let config = crate::service::get_state::<Arc<Config>>();
-    config.has_password().await.map_err(|e| e.to_string())
+    config.has_password().await.into_tauri_result()
 }
 
 #[cfg_attr(feature = "gui", tauri::command)]
@@ -267,10 +421,16 @@ pub async fn list_cache() -> Result<Vec<BlockCacheFile>> {
     let network = config.get_network().await.into_tauri_result()?;
     let data_dir = config.get_data_dir().await.into_tauri_result()?;
     let mut files = PersistBlockCache::list_cache_files(&data_dir).into_tauri_result()?;
-    let sync_state = crate::service::get_state::<Arc<SyncState>>().status().await;
+    // cached blocks are shared, keep those any synced wallet still needs
+    let mut height = i64::MAX;
+    if let Some(registry) = crate::service::try_get_state::<Arc<WalletRegistry>>() {
+        for state in registry.wallets() {
+            height = height.min(state.status().await.height as i64);
+        }
+    }
 
     files.retain(|file| {
-        if file.network == network.to_string() && file.range.1 > sync_state.height as i64 {
+        if file.network == network.to_string() && file.range.1 > height {
             return false;
         }
         return true;
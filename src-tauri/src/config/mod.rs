@@ -2,9 +2,11 @@ use std::path::PathBuf;
 use std::str::FromStr;
 
 use anyhow::anyhow;
+use anyhow::ensure;
 use anyhow::Context;
 use anyhow::Result;
 use neptune_cash::api::export::Network;
+use neptune_cash::api::export::TxProvingCapability;
 use neptune_cash::application::config::data_directory::DataDirectory;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -12,14 +14,30 @@ use sha2::Digest;
 use sha2::Sha256;
 use sqlx::Row;
 use sqlx::SqlitePool;
+use thiserror::Error;
 use tokio::sync::Mutex;
 
+use crate::prover::remote::RemoteProverConfig;
 use crate::rpc::tls;
+use crate::wallet::consolidate::ConsolidateConfig;
+use crate::wallet::upgrader::UpgraderConfig;
+use crate::wallet::webhook::WebhookConfig;
 
 mod config_migrate;
 pub mod consts;
 pub mod wallet;
 
+#[derive(Debug, Error)]
+pub enum PasswordError {
+    #[error("password is wrong")]
+    Wrong,
+    #[error("password not set")]
+    NotSet,
+    /// the password has not been entered since the app started
+    #[error("no password set!")]
+    Locked,
+}
+
 pub struct Config {
     db: SqlitePool,
     pub password: Mutex<Option<String>>,
@@ -106,6 +124,18 @@ impl Config {
         }
     }
 
+    /// wallets synced besides the current one, `None` syncs all wallets
+    pub async fn set_synced_wallets(&self, ids: Option<Vec<i64>>) -> Result<()> {
+        self.set_data("synced_wallets", &ids).await
+    }
+
+    pub async fn get_synced_wallets(&self) -> Result<Option<Vec<i64>>> {
+        Ok(self
+            .get_data::<Option<Vec<i64>>>("synced_wallets")
+            .await?
+            .flatten())
+    }
+
     pub async fn set_network(&self, network: Network) -> Result<()> {
         self.set_data("network", &network.to_string()).await
     }
@@ -128,6 +158,28 @@ impl Config {
         }
     }
 
+    /// proof produced by sends that do not choose one
+    pub async fn set_tx_proving_capability(&self, capability: TxProvingCapability) -> Result<()> {
+        ensure!(
+            matches!(
+                capability,
+                TxProvingCapability::ProofCollection | TxProvingCapability::SingleProof
+            ),
+            "transactions can only be proven with a proof collection or single proof"
+        );
+        self.set_data("tx_proving_capability", &capability).await
+    }
+
+    pub async fn get_tx_proving_capability(&self) -> Result<TxProvingCapability> {
+        match self
+            .get_data::<TxProvingCapability>("tx_proving_capability")
+            .await?
+        {
+            Some(v) => Ok(v),
+            None => Ok(TxProvingCapability::ProofCollection),
+        }
+    }
+
     async fn remote_rest_key(&self) -> Result<&str> {
         let network = self.get_network().await?;
         match network {
@@ -153,19 +205,20 @@ impl Config {
         let pass_test = self
             .get_data::<Vec<u8>>(PASSWORD_TEST_KEY)
             .await?
-            .context("password not set")?;
+            .ok_or(PasswordError::NotSet)?;
         match password {
             "" => {
                 if pass_test != PASSWORD_TEST.as_bytes().to_vec() {
-                    return Err(anyhow!("password is wrong"));
+                    return Err(PasswordError::Wrong.into());
                 }
             }
             _ => {
                 let encrypt_key = hash(password);
+                // decryption fails with any other key
                 let decrypted = crate::rpc::tls::aes::aes_decode(&encrypt_key, &pass_test)
-                    .context("cant decode db")?;
+                    .map_err(|_| PasswordError::Wrong)?;
                 if decrypted != PASSWORD_TEST.as_bytes() {
-                    return Err(anyhow!("password is wrong"));
+                    return Err(PasswordError::Wrong.into());
                 }
             }
         };
@@ -241,7 +294,7 @@ impl Config {
             }
 
             None => {
-                return Err(anyhow!("no password set!"));
+                return Err(PasswordError::Locked.into());
             }
         }
 
@@ -262,7 +315,7 @@ impl Config {
             .lock()
             .await
             .clone()
-            .ok_or(anyhow!("no password set!"))?
+            .ok_or(PasswordError::Locked)?
             .as_str()
         {
             "" => return Ok(value),
@@ -309,6 +362,54 @@ impl Config {
     pub async fn get_log_level(&self) -> Result<Option<String>> {
         self.get_data::<String>("log_level").await
     }
+
+    /// `None` disables the background consolidation
+    pub async fn set_auto_consolidate(&self, config: Option<ConsolidateConfig>) -> Result<()> {
+        self.set_data("auto_consolidate", &config).await
+    }
+
+    pub async fn get_auto_consolidate(&self) -> Result<Option<ConsolidateConfig>> {
+        Ok(self
+            .get_data::<Option<ConsolidateConfig>>("auto_consolidate")
+            .await?
+            .flatten())
+    }
+
+    /// `None` proves locally
+    pub async fn set_remote_prover(&self, config: Option<RemoteProverConfig>) -> Result<()> {
+        self.set_data("remote_prover", &config).await
+    }
+
+    pub async fn get_remote_prover(&self) -> Result<Option<RemoteProverConfig>> {
+        Ok(self
+            .get_data::<Option<RemoteProverConfig>>("remote_prover")
+            .await?
+            .flatten())
+    }
+
+    /// `None` disables the proof upgrader
+    pub async fn set_proof_upgrader(&self, config: Option<UpgraderConfig>) -> Result<()> {
+        self.set_data("proof_upgrader", &config).await
+    }
+
+    pub async fn get_proof_upgrader(&self) -> Result<Option<UpgraderConfig>> {
+        Ok(self
+            .get_data::<Option<UpgraderConfig>>("proof_upgrader")
+            .await?
+            .flatten())
+    }
+
+    /// `None` disables the webhooks, queued events are kept
+    pub async fn set_webhooks(&self, config: Option<WebhookConfig>) -> Result<()> {
+        self.set_data("webhooks", &config).await
+    }
+
+    pub async fn get_webhooks(&self) -> Result<Option<WebhookConfig>> {
+        Ok(self
+            .get_data::<Option<WebhookConfig>>("webhooks")
+            .await?
+            .flatten())
+    }
 }
 
 pub fn hash(str: &str) -> Vec<u8> {
@@ -1,3 +1,5 @@
+use anyhow::bail;
+use anyhow::ensure;
 use anyhow::Result;
 use neptune_cash::api::export::Network;
 use neptune_cash::api::export::SpendingKey;
@@ -8,22 +10,33 @@ use serde::Serialize;
 use sqlx::Row;
 
 use super::Config;
+use crate::wallet::watch_only::WalletKey;
+use crate::wallet::watch_only::WatchOnlyKeys;
 
 impl Config {
     pub async fn get_current_wallet(&self) -> Result<WalletConfig> {
-        let mut conn = self.db.acquire().await?;
-
         let id = self.get_wallet_id().await?;
+        self.get_wallet(id).await
+    }
 
-        let row = sqlx::query("select id,secret_key,scan_config from wallets where id = ?")
-            .bind(&id)
-            .fetch_one(&mut *conn)
-            .await?;
+    pub async fn get_wallet(&self, id: i64) -> Result<WalletConfig> {
+        let mut conn = self.db.acquire().await?;
+
+        let row =
+            sqlx::query("select id,secret_key,scan_config,watch_only from wallets where id = ?")
+                .bind(&id)
+                .fetch_one(&mut *conn)
+                .await?;
 
         let secret = row.get::<Vec<u8>, _>("secret_key");
-        let mnemonic = self.secret_to_mnemonic(secret).await?;
-        let secret = SecretKeyMaterial::from_phrase(&mnemonic)?;
-        let key = WalletEntropy::new(secret);
+        let key = if row.get::<bool, _>("watch_only") {
+            let viewing_key = self.secret_to_viewing_key(secret).await?;
+            WalletKey::WatchOnly(viewing_key)
+        } else {
+            let mnemonic = self.secret_to_mnemonic(secret).await?;
+            let secret = SecretKeyMaterial::from_phrase(&mnemonic)?;
+            WalletKey::Entropy(WalletEntropy::new(secret))
+        };
 
         let scan_config = row.get::<String, _>("scan_config");
         let scan_config = serde_json::from_str::<ScanConfig>(&scan_config)?;
@@ -43,15 +56,40 @@ impl Config {
     pub async fn get_wallet_mnemonic(&self, id: i64) -> Result<Vec<String>> {
         let mut conn = self.db.acquire().await?;
 
-        let row = sqlx::query("select secret_key from wallets where id = ?")
+        let row = sqlx::query("select secret_key,watch_only from wallets where id = ?")
             .bind(&id)
             .fetch_one(&mut *conn)
             .await?;
 
+        if row.get::<bool, _>("watch_only") {
+            bail!("watch-only wallet has no mnemonic");
+        }
+
         let secret = row.get::<Vec<u8>, _>("secret_key");
         self.secret_to_mnemonic(secret).await
     }
 
+    /// export the viewing material of a wallet, used to create watch-only wallets
+    pub async fn get_wallet_viewing_key(&self, id: i64, num_keys: u64) -> Result<String> {
+        let mut conn = self.db.acquire().await?;
+
+        let row = sqlx::query("select secret_key,watch_only from wallets where id = ?")
+            .bind(&id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        let secret = row.get::<Vec<u8>, _>("secret_key");
+        if row.get::<bool, _>("watch_only") {
+            return self.secret_to_viewing_key(secret).await?.encode();
+        }
+
+        let mnemonic = self.secret_to_mnemonic(secret).await?;
+        let key = WalletEntropy::new(SecretKeyMaterial::from_phrase(&mnemonic)?);
+        let network = self.get_network().await?;
+
+        WatchOnlyKeys::from_entropy(&key, num_keys, network)?.encode()
+    }
+
     pub async fn add_wallet(
         &self,
         name: &str,
@@ -82,6 +120,44 @@ impl Config {
         Ok(res.last_insert_rowid())
     }
 
+    pub async fn add_watch_only_wallet(
+        &self,
+        name: &str,
+        viewing_key: &str,
+        scan_config: ScanConfig,
+    ) -> Result<i64> {
+        let mut conn = self.db.acquire().await?;
+
+        let network = self.get_network().await?;
+
+        let keys = WatchOnlyKeys::decode(viewing_key)?;
+        ensure!(
+            keys.network == network,
+            "viewing key is for network {}, current network is {}",
+            keys.network,
+            network
+        );
+
+        let address = keys.address(0)?.to_bech32m(network)?;
+
+        let scan_config = serde_json::to_string(&scan_config)?;
+
+        let secret = self.viewing_key_to_secret(&keys).await?;
+
+        let res = sqlx::query(
+            "INSERT INTO wallets (name, secret_key, scan_config, address, balance, watch_only) VALUES (?,?,?,?,?,1)",
+        )
+        .bind(&name)
+        .bind(&secret)
+        .bind(&scan_config)
+        .bind(&address)
+        .bind(&"".to_string())
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(res.last_insert_rowid())
+    }
+
     pub async fn remove_wallet(&self, id: i64) -> Result<()> {
         let mut conn = self.db.acquire().await?;
         sqlx::query("delete from wallets where id = ?")
@@ -94,7 +170,7 @@ impl Config {
     pub async fn get_wallets(&self) -> Result<Vec<WalletData>> {
         let mut conn = self.db.acquire().await?;
 
-        let rows = sqlx::query("select id,name,address,balance from wallets")
+        let rows = sqlx::query("select id,name,address,balance,watch_only from wallets")
             .fetch_all(&mut *conn)
             .await?;
 
@@ -104,16 +180,40 @@ impl Config {
             let name = row.get::<String, _>("name");
             let address = row.get::<String, _>("address");
             let balance = row.get::<String, _>("balance");
+            let watch_only = row.get::<bool, _>("watch_only");
             wallets.push(WalletData {
                 id,
                 name,
                 address,
                 balance,
+                watch_only,
             })
         }
         Ok(wallets)
     }
 
+    pub async fn update_scan_config(&self, id: i64, scan_config: &ScanConfig) -> Result<()> {
+        let mut conn = self.db.acquire().await?;
+        sqlx::query("update wallets set scan_config = ? where id = ?")
+            .bind(serde_json::to_string(scan_config)?)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_wallet_ids(&self) -> Result<Vec<i64>> {
+        let mut conn = self.db.acquire().await?;
+
+        let rows = sqlx::query("select id from wallets order by id")
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| row.get::<i64, _>("id"))
+            .collect())
+    }
+
     pub async fn update_wallet_balance(&self, id: i64, balance: String) -> Result<()> {
         let mut conn = self.db.acquire().await?;
         sqlx::query("update wallets set balance = ? where id = ?")
@@ -138,6 +238,17 @@ impl Config {
         let phrase = phrase.split(" ").map(|v| v.to_string()).collect::<Vec<_>>();
         Ok(phrase)
     }
+
+    pub async fn viewing_key_to_secret(&self, keys: &WatchOnlyKeys) -> Result<Vec<u8>> {
+        let encoded = keys.encode()?;
+        crate::rpc::tls::aes::aes_encode(&self.decrypt_key.lock().await, encoded.as_bytes())
+    }
+
+    pub async fn secret_to_viewing_key(&self, secret: Vec<u8>) -> Result<WatchOnlyKeys> {
+        let decode_key = self.decrypt_key.lock().await.clone();
+        let encoded = crate::rpc::tls::aes::aes_decode(&decode_key, &secret)?;
+        WatchOnlyKeys::decode(&String::from_utf8(encoded)?)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -146,19 +257,22 @@ pub struct WalletData {
     name: String,
     address: String,
     balance: String,
+    watch_only: bool,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
 pub struct ScanConfig {
+    /// keys scanned beyond the highest used derivation index
     #[serde(default = "default_num_keys")]
     pub num_keys: u64,
+    /// first block scanned
     #[serde(default)]
     pub start_height: u64,
 }
 
 pub struct WalletConfig {
     pub id: i64,
-    pub key: WalletEntropy,
+    pub key: WalletKey,
     pub scan_config: ScanConfig,
     pub network: Network,
 }
@@ -24,38 +24,71 @@ pub use neptune_cash;
 pub fn add_commands<R: tauri::Runtime>(app: tauri::Builder<R>) -> tauri::Builder<R> {
     app.invoke_handler(tauri::generate_handler![
         command::commands::add_wallet,
+        command::commands::add_watch_only_wallet,
         command::commands::delete_cache,
+        command::commands::export_viewing_key,
         command::commands::export_wallet,
         command::commands::generate_snapshot_file,
+        command::commands::get_auto_consolidate,
         command::commands::get_disk_cache,
         command::commands::get_network,
+        command::commands::get_proof_upgrader,
+        command::commands::get_remote_prover,
+        command::commands::get_remote_prover_client_key,
         command::commands::get_remote_rest,
+        command::commands::get_synced_wallets,
+        command::commands::get_tx_proving_capability,
         command::commands::get_wallet_id,
         command::commands::get_wallets,
+        command::commands::get_webhooks,
         command::commands::has_password,
         command::commands::input_password,
         command::commands::list_cache,
         command::commands::remove_wallet,
         command::commands::reset_to_height,
+        command::commands::set_auto_consolidate,
         command::commands::set_disk_cache,
         command::commands::set_network,
         command::commands::set_password,
+        command::commands::set_proof_upgrader,
+        command::commands::set_remote_prover,
         command::commands::set_remote_rest,
+        command::commands::set_synced_wallets,
+        command::commands::set_tx_proving_capability,
         command::commands::set_wallet_id,
+        command::commands::set_webhooks,
         command::commands::snapshot_dir,
         command::commands::try_password,
         command::commands::wallet_address,
         rpc::commands::avaliable_utxos,
+        rpc::commands::broadcast_signed_transaction,
+        rpc::commands::bump_fee,
+        rpc::commands::cancel_job,
+        rpc::commands::cancel_send,
+        rpc::commands::consolidate,
+        rpc::commands::create_unsigned_transaction,
         rpc::commands::current_wallet_address,
+        rpc::commands::estimate_fee,
         rpc::commands::forget_tx,
+        rpc::commands::get_job,
+        rpc::commands::get_scan_config,
         rpc::commands::get_server_url,
         rpc::commands::get_tip_height,
         rpc::commands::history,
+        rpc::commands::list_jobs,
+        rpc::commands::list_upgrades,
         rpc::commands::pending_transactions,
+        rpc::commands::preview_send,
+        rpc::commands::rescan,
         rpc::commands::run_rpc_server,
         rpc::commands::send_to_address,
+        rpc::commands::sign_transaction,
         rpc::commands::stop_rpc_server,
+        rpc::commands::submit_send_job,
+        rpc::commands::submit_upgrade,
+        rpc::commands::sweep,
         rpc::commands::sync_state,
+        rpc::commands::update_scan_config,
         rpc::commands::wallet_balance,
         os::is_win11,
         os::os_info,
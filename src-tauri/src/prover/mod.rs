@@ -9,8 +9,19 @@ use tasm_lib::triton_vm::stark::Stark;
 use tasm_lib::triton_vm::vm::NonDeterminism;
 use tracing::*;
 
+mod progress;
 mod proof_collection;
+pub mod remote;
 mod single_proof;
+mod verify;
+
+pub use progress::CancellationToken;
+pub use progress::ProofCancelled;
+pub use progress::ProofMonitor;
+pub use progress::ProofProgress;
+pub use progress::ProofStage;
+pub use verify::verify_transaction;
+pub use verify::InvalidProof;
 
 pub struct ProofBuilder {
     gobble_fee: Option<NativeCurrencyAmount>,
@@ -0,0 +1,119 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::*;
+use utoipa::ToSchema;
+
+/// Shared flag to stop a proof, checked before every sub-proof.
+///
+/// A single `triton_vm::prove` call cannot be interrupted, the work stops
+/// once the running sub-proof completes.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Cancels the token when dropped, unless disarmed, so dropping the future
+/// awaiting a proof stops the blocking prover as well.
+pub struct CancelOnDrop(Option<CancellationToken>);
+
+impl CancelOnDrop {
+    pub fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(token) = self.0.take() {
+            token.cancel();
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("proving cancelled")]
+pub struct ProofCancelled;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "stage", content = "index")]
+pub enum ProofStage {
+    RemovalRecordsIntegrity,
+    CollectLockScripts,
+    KernelToOutputs,
+    CollectTypeScripts,
+    LockScript(usize),
+    TypeScript(usize),
+    SingleProof,
+    /// waiting for the remote prover
+    Remote,
+}
+
+/// Reported before a sub-proof starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProofProgress {
+    #[serde(flatten)]
+    pub stage: ProofStage,
+    /// sub-proofs completed so far
+    pub done: usize,
+    pub total: usize,
+}
+
+type ProgressCallback = Arc<dyn Fn(&ProofProgress) + Send + Sync>;
+
+/// Cancellation and progress reporting for [super::ProofBuilder].
+#[derive(Clone, Default)]
+pub struct ProofMonitor {
+    cancel: CancellationToken,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl ProofMonitor {
+    pub fn new(cancel: CancellationToken) -> Self {
+        Self {
+            cancel,
+            on_progress: None,
+        }
+    }
+
+    pub fn with_progress(mut self, f: impl Fn(&ProofProgress) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(f));
+        self
+    }
+
+    pub fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop(Some(self.cancel.clone()))
+    }
+
+    /// Fails with [ProofCancelled] if cancelled, reports `stage` otherwise.
+    pub(super) fn start(&self, stage: ProofStage, done: usize, total: usize) -> anyhow::Result<()> {
+        if self.cancel.is_cancelled() {
+            info!("proving cancelled before {:?}", stage);
+            return Err(ProofCancelled.into());
+        }
+
+        let progress = ProofProgress { stage, done, total };
+        debug!("proving {:?} ({}/{})", progress.stage, done, total);
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(&progress);
+        }
+
+        Ok(())
+    }
+}
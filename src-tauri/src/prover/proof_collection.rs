@@ -2,7 +2,9 @@ use anyhow::Result;
 use itertools::Itertools;
 use neptune_cash::api::export::Tip5;
 use neptune_cash::prelude::tasm_lib;
+use neptune_cash::prelude::tasm_lib::prelude::Digest;
 use neptune_cash::prelude::triton_vm::prelude::BFieldElement;
+use neptune_cash::prelude::triton_vm::proof::Proof;
 use neptune_cash::prelude::triton_vm::vm::PublicInput;
 use neptune_cash::protocol::consensus::transaction::primitive_witness::PrimitiveWitness;
 use neptune_cash::protocol::consensus::transaction::transaction_kernel::TransactionKernelField;
@@ -11,100 +13,208 @@ use neptune_cash::protocol::consensus::transaction::validity::collect_type_scrip
 use neptune_cash::protocol::consensus::transaction::validity::kernel_to_outputs::KernelToOutputsWitness;
 use neptune_cash::protocol::consensus::transaction::validity::proof_collection::ProofCollection;
 use neptune_cash::protocol::consensus::transaction::validity::removal_records_integrity::RemovalRecordsIntegrityWitness;
+use neptune_cash::protocol::consensus::type_scripts::TypeScriptAndWitness;
 use neptune_cash::protocol::proof_abstractions::mast_hash::MastHash;
 use neptune_cash::protocol::proof_abstractions::SecretWitness;
+use serde::Deserialize;
+use serde::Serialize;
 use tasm_lib::triton_vm::proof::Claim;
 use tracing::debug;
 use tracing::info;
 
+use super::ProofMonitor;
+use super::ProofStage;
+
+/// The part of a primitive witness that can be proven by a remote prover:
+/// everything but the lock script witnesses, which hold the unlock keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct DelegatedWitness {
+    removal_records_integrity: RemovalRecordsIntegrityWitness,
+    collect_lock_scripts: CollectLockScriptsWitness,
+    kernel_to_outputs: KernelToOutputsWitness,
+    collect_type_scripts: CollectTypeScriptsWitness,
+    type_scripts_and_witnesses: Vec<TypeScriptAndWitness>,
+    salted_inputs_hash: Digest,
+    salted_outputs_hash: Digest,
+}
+
+impl From<&PrimitiveWitness> for DelegatedWitness {
+    fn from(primitive_witness: &PrimitiveWitness) -> Self {
+        Self {
+            removal_records_integrity: RemovalRecordsIntegrityWitness::from(primitive_witness),
+            collect_lock_scripts: CollectLockScriptsWitness::from(primitive_witness),
+            kernel_to_outputs: KernelToOutputsWitness::from(primitive_witness),
+            collect_type_scripts: CollectTypeScriptsWitness::from(primitive_witness),
+            type_scripts_and_witnesses: primitive_witness.type_scripts_and_witnesses.clone(),
+            salted_inputs_hash: Tip5::hash(&primitive_witness.input_utxos),
+            salted_outputs_hash: Tip5::hash(&primitive_witness.output_utxos),
+        }
+    }
+}
+
+impl DelegatedWitness {
+    pub(super) fn kernel_mast_hash(&self) -> Digest {
+        self.kernel_to_outputs.kernel.mast_hash()
+    }
+
+    /// number of proofs produced from this witness
+    pub(super) fn num_proofs(&self) -> usize {
+        4 + self.type_scripts_and_witnesses.len()
+    }
+}
+
+/// Proofs of a [DelegatedWitness].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct DelegatedProofs {
+    removal_records_integrity: Proof,
+    collect_lock_scripts: Proof,
+    kernel_to_outputs: Proof,
+    collect_type_scripts: Proof,
+    type_scripts_halt: Vec<Proof>,
+}
+
 impl super::ProofBuilder {
     pub fn produce_proof_collection(
         primitive_witness: &PrimitiveWitness,
+        monitor: &ProofMonitor,
     ) -> Result<ProofCollection> {
-        let (
-            removal_records_integrity_witness,
-            collect_lock_scripts_witness,
-            kernel_to_outputs_witness,
-            collect_type_scripts_witness,
-        ) = Self::extract_specific_witnesses(primitive_witness);
+        let total = 4
+            + primitive_witness.lock_scripts_and_witnesses.len()
+            + primitive_witness.type_scripts_and_witnesses.len();
 
-        let txk_mast_hash = primitive_witness.kernel.mast_hash();
-        let txk_mast_hash_as_input = PublicInput::new(txk_mast_hash.reversed().values().to_vec());
-        let salted_inputs_hash = Tip5::hash(&primitive_witness.input_utxos);
-        let salted_outputs_hash = Tip5::hash(&primitive_witness.output_utxos);
+        let delegated = Self::produce_delegated_proofs(
+            &DelegatedWitness::from(primitive_witness),
+            monitor,
+            total,
+        )?;
+        let lock_scripts_halt = Self::produce_lock_script_proofs(primitive_witness, monitor)?;
+        info!("done proving proof collection");
+
+        Ok(Self::assemble_proof_collection(
+            primitive_witness,
+            delegated,
+            lock_scripts_halt,
+        ))
+    }
+
+    /// Proves everything but the lock scripts, the part of a proof
+    /// collection a remote prover can produce.
+    ///
+    /// `total` is the number of sub-proofs reported to `monitor`, the lock
+    /// scripts are reported last.
+    pub(super) fn produce_delegated_proofs(
+        witness: &DelegatedWitness,
+        monitor: &ProofMonitor,
+        total: usize,
+    ) -> Result<DelegatedProofs> {
+        let txk_mast_hash = witness.kernel_mast_hash();
         debug!("proving, txk hash: {}", txk_mast_hash);
-        debug!("proving, salted inputs hash: {}", salted_inputs_hash);
-        debug!("proving, salted outputs hash: {}", salted_outputs_hash);
+        debug!(
+            "proving, salted inputs hash: {}",
+            witness.salted_inputs_hash
+        );
+        debug!(
+            "proving, salted outputs hash: {}",
+            witness.salted_outputs_hash
+        );
 
-        // prove
-        debug!("proving RemovalRecordsIntegrity");
+        monitor.start(ProofStage::RemovalRecordsIntegrity, 0, total)?;
         let removal_records_integrity = Self::produce(
-            removal_records_integrity_witness.program(),
-            removal_records_integrity_witness.claim(),
-            removal_records_integrity_witness.nondeterminism(),
-        )?
-        .into();
+            witness.removal_records_integrity.program(),
+            witness.removal_records_integrity.claim(),
+            witness.removal_records_integrity.nondeterminism(),
+        )?;
 
-        debug!("proving CollectLockScripts");
+        monitor.start(ProofStage::CollectLockScripts, 1, total)?;
         let collect_lock_scripts = Self::produce(
-            collect_lock_scripts_witness.program(),
-            collect_lock_scripts_witness.claim(),
-            collect_lock_scripts_witness.nondeterminism(),
-        )?
-        .into();
+            witness.collect_lock_scripts.program(),
+            witness.collect_lock_scripts.claim(),
+            witness.collect_lock_scripts.nondeterminism(),
+        )?;
 
-        debug!("proving KernelToOutputs");
+        monitor.start(ProofStage::KernelToOutputs, 2, total)?;
         let kernel_to_outputs = Self::produce(
-            kernel_to_outputs_witness.program(),
-            kernel_to_outputs_witness.claim(),
-            kernel_to_outputs_witness.nondeterminism(),
-        )?
-        .into();
+            witness.kernel_to_outputs.program(),
+            witness.kernel_to_outputs.claim(),
+            witness.kernel_to_outputs.nondeterminism(),
+        )?;
 
-        debug!("proving CollectTypeScripts");
+        monitor.start(ProofStage::CollectTypeScripts, 3, total)?;
         let collect_type_scripts = Self::produce(
-            collect_type_scripts_witness.program(),
-            collect_type_scripts_witness.claim(),
-            collect_type_scripts_witness.nondeterminism(),
-        )?
-        .into();
-
-        debug!("proving lock scripts");
-        let mut lock_scripts_halt = vec![];
-        for lock_script_and_witness in &primitive_witness.lock_scripts_and_witnesses {
-            let claim = Claim::new(lock_script_and_witness.program.hash())
-                .with_input(txk_mast_hash_as_input.clone().individual_tokens);
-            let lock_script_and_witness = Self::produce(
-                lock_script_and_witness.program.clone(),
-                claim,
-                lock_script_and_witness.nondeterminism(),
-            )?
-            .into();
-            lock_scripts_halt.push(lock_script_and_witness);
-        }
+            witness.collect_type_scripts.program(),
+            witness.collect_type_scripts.claim(),
+            witness.collect_type_scripts.nondeterminism(),
+        )?;
 
         debug!("proving type scripts");
         let mut type_scripts_halt = vec![];
-        for (i, tsaw) in primitive_witness
-            .type_scripts_and_witnesses
-            .iter()
-            .enumerate()
-        {
+        for (i, tsaw) in witness.type_scripts_and_witnesses.iter().enumerate() {
             debug!("proving type script number {i}: {}", tsaw.program.hash());
-            let input: Vec<BFieldElement> =
-                [txk_mast_hash, salted_inputs_hash, salted_outputs_hash]
-                    .into_iter()
-                    .flat_map(|d| d.reversed().values())
-                    .collect_vec();
+            monitor.start(ProofStage::TypeScript(i), 4 + i, total)?;
+            let input: Vec<BFieldElement> = [
+                txk_mast_hash,
+                witness.salted_inputs_hash,
+                witness.salted_outputs_hash,
+            ]
+            .into_iter()
+            .flat_map(|d| d.reversed().values())
+            .collect_vec();
             let claim = Claim::new(tsaw.program.hash()).with_input(input);
 
             let type_script_halt =
-                Self::produce(tsaw.program.clone(), claim, tsaw.nondeterminism())?.into();
+                Self::produce(tsaw.program.clone(), claim, tsaw.nondeterminism())?;
 
             type_scripts_halt.push(type_script_halt);
         }
-        info!("done proving proof collection");
 
+        Ok(DelegatedProofs {
+            removal_records_integrity,
+            collect_lock_scripts,
+            kernel_to_outputs,
+            collect_type_scripts,
+            type_scripts_halt,
+        })
+    }
+
+    /// Proves the lock scripts, their witnesses never leave the wallet.
+    pub(super) fn produce_lock_script_proofs(
+        primitive_witness: &PrimitiveWitness,
+        monitor: &ProofMonitor,
+    ) -> Result<Vec<Proof>> {
+        let total = 4
+            + primitive_witness.lock_scripts_and_witnesses.len()
+            + primitive_witness.type_scripts_and_witnesses.len();
+        let done = 4 + primitive_witness.type_scripts_and_witnesses.len();
+
+        let txk_mast_hash = primitive_witness.kernel.mast_hash();
+        let txk_mast_hash_as_input = PublicInput::new(txk_mast_hash.reversed().values().to_vec());
+
+        debug!("proving lock scripts");
+        let mut lock_scripts_halt = vec![];
+        for (i, lock_script_and_witness) in primitive_witness
+            .lock_scripts_and_witnesses
+            .iter()
+            .enumerate()
+        {
+            monitor.start(ProofStage::LockScript(i), done + i, total)?;
+            let claim = Claim::new(lock_script_and_witness.program.hash())
+                .with_input(txk_mast_hash_as_input.clone().individual_tokens);
+            let lock_script_halt = Self::produce(
+                lock_script_and_witness.program.clone(),
+                claim,
+                lock_script_and_witness.nondeterminism(),
+            )?;
+            lock_scripts_halt.push(lock_script_halt);
+        }
+
+        Ok(lock_scripts_halt)
+    }
+
+    pub(super) fn assemble_proof_collection(
+        primitive_witness: &PrimitiveWitness,
+        delegated: DelegatedProofs,
+        lock_scripts_halt: Vec<Proof>,
+    ) -> ProofCollection {
         // collect hashes
         let lock_script_hashes = primitive_witness
             .lock_scripts_and_witnesses
@@ -121,42 +231,19 @@ impl super::ProofBuilder {
             .kernel
             .mast_path(TransactionKernelField::MergeBit);
 
-        Ok(ProofCollection {
-            removal_records_integrity,
-            collect_lock_scripts,
-            lock_scripts_halt,
-            kernel_to_outputs,
-            collect_type_scripts,
-            type_scripts_halt,
+        ProofCollection {
+            removal_records_integrity: delegated.removal_records_integrity.into(),
+            collect_lock_scripts: delegated.collect_lock_scripts.into(),
+            lock_scripts_halt: lock_scripts_halt.into_iter().map_into().collect(),
+            kernel_to_outputs: delegated.kernel_to_outputs.into(),
+            collect_type_scripts: delegated.collect_type_scripts.into(),
+            type_scripts_halt: delegated.type_scripts_halt.into_iter().map_into().collect(),
             lock_script_hashes,
             type_script_hashes,
-            kernel_mast_hash: txk_mast_hash,
-            salted_inputs_hash,
-            salted_outputs_hash,
+            kernel_mast_hash: primitive_witness.kernel.mast_hash(),
+            salted_inputs_hash: Tip5::hash(&primitive_witness.input_utxos),
+            salted_outputs_hash: Tip5::hash(&primitive_witness.output_utxos),
             merge_bit_mast_path,
-        })
-    }
-
-    fn extract_specific_witnesses(
-        primitive_witness: &PrimitiveWitness,
-    ) -> (
-        RemovalRecordsIntegrityWitness,
-        CollectLockScriptsWitness,
-        KernelToOutputsWitness,
-        CollectTypeScriptsWitness,
-    ) {
-        // collect witnesses
-        let removal_records_integrity_witness =
-            RemovalRecordsIntegrityWitness::from(primitive_witness);
-        let collect_lock_scripts_witness = CollectLockScriptsWitness::from(primitive_witness);
-        let kernel_to_outputs_witness = KernelToOutputsWitness::from(primitive_witness);
-        let collect_type_scripts_witness = CollectTypeScriptsWitness::from(primitive_witness);
-
-        (
-            removal_records_integrity_witness,
-            collect_lock_scripts_witness,
-            kernel_to_outputs_witness,
-            collect_type_scripts_witness,
-        )
+        }
     }
 }
@@ -0,0 +1,279 @@
+//! Offloads proof collections to a remote prover.
+//!
+//! The wallet sends the witnesses of the removal records integrity, kernel to
+//! outputs, lock and type script collection and type script proofs. These
+//! reveal the inputs, outputs, amounts and sender randomness of the
+//! transaction to the prover, so only use a prover you trust. The lock script
+//! witnesses, which hold the unlock keys, never leave the wallet: the lock
+//! scripts are proven locally.
+//!
+//! Requests and responses are encrypted with AES-256-GCM under a key derived
+//! from a P-256 ECDH between the prover identity of the wallet and the pinned
+//! server key, which authenticates both sides. The prover identity is derived
+//! from the wallet secret, it is not the RPC token.
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use hkdf::Hkdf;
+use neptune_cash::api::export::Network;
+use neptune_cash::protocol::consensus::transaction::primitive_witness::PrimitiveWitness;
+use neptune_cash::protocol::consensus::transaction::validity::proof_collection::ProofCollection;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tracing::*;
+
+use super::proof_collection::DelegatedProofs;
+use super::proof_collection::DelegatedWitness;
+use super::ProofBuilder;
+use super::ProofMonitor;
+use super::ProofStage;
+use crate::rpc::tls;
+use crate::rpc::tls::aes;
+
+const PROTOCOL_VERSION: u8 = 2;
+
+/// HKDF info of the prover identity derived from the wallet secret
+const PROVER_IDENTITY_LABEL: &[u8] = b"neptune-wallet remote prover identity";
+
+/// Remote prover used for proof collections, stored in the config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteProverConfig {
+    /// e.g. `http://127.0.0.1:9810`
+    pub url: String,
+    /// hex encoded public key printed by the prover server
+    pub server_key: String,
+}
+
+/// Body of requests and responses.
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedMessage {
+    /// hex encoded prover identity of the wallet, unset in responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client: Option<String>,
+    /// hex encoded nonce and AES-GCM cipher text
+    payload: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProveRequest {
+    version: u8,
+    network: Network,
+    witness: DelegatedWitness,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum ProveResponse {
+    Proofs(DelegatedProofs),
+    Error(String),
+}
+
+/// Secret key the wallet authenticates to remote provers with, derived from
+/// the wallet secret so the RPC token is never shown to a prover.
+fn prover_identity(wallet_secret: &[u8]) -> Result<Vec<u8>> {
+    let hk = Hkdf::<Sha256>::new(None, wallet_secret);
+    let mut okm = [0u8; 32];
+    hk.expand(PROVER_IDENTITY_LABEL, &mut okm)
+        .expect("32 is a valid length for Sha256 to output");
+    let secret = p256::SecretKey::from_slice(&okm).context("invalid prover identity")?;
+    Ok(secret.to_bytes().to_vec())
+}
+
+/// Hex encoded public key identifying the wallet to remote provers, to be
+/// allowed on the prover with `--allow`.
+pub fn client_public_key(wallet_secret: &[u8]) -> Result<String> {
+    Ok(hex::encode(tls::get_p256_pubkey(&prover_identity(
+        wallet_secret,
+    )?)))
+}
+
+fn session_key(peer_public_key: &[u8], own_secret_key: &[u8]) -> Result<Vec<u8>> {
+    let shared = tls::generate_p256_shared_secret(peer_public_key, own_secret_key)?;
+    Ok(tls::shared_secret_to_symmetric_secret(&shared))
+}
+
+fn seal<T: Serialize>(key: &[u8], value: &T) -> Result<String> {
+    Ok(hex::encode(aes::aes_encode(
+        key,
+        &bincode::serialize(value)?,
+    )?))
+}
+
+fn open<T: for<'de> Deserialize<'de>>(key: &[u8], payload: &str) -> Result<T> {
+    let plaintext = aes::aes_decode(key, &hex::decode(payload)?)?;
+    Ok(bincode::deserialize(&plaintext)?)
+}
+
+impl RemoteProverConfig {
+    /// Produces a proof collection with the help of the remote prover, which
+    /// the caller has to verify like any other proof. The lock scripts are
+    /// proven locally.
+    pub async fn prove(
+        &self,
+        wallet_secret: &[u8],
+        witness: &PrimitiveWitness,
+        network: Network,
+        monitor: &ProofMonitor,
+    ) -> Result<ProofCollection> {
+        monitor.start(ProofStage::Remote, 0, 1)?;
+
+        let server_key = hex::decode(&self.server_key).context("invalid remote prover key")?;
+        let client_secret = prover_identity(wallet_secret)?;
+        let key = session_key(&server_key, &client_secret)?;
+        let request = EncryptedMessage {
+            client: Some(hex::encode(tls::get_p256_pubkey(&client_secret))),
+            payload: seal(
+                &key,
+                &ProveRequest {
+                    version: PROTOCOL_VERSION,
+                    network,
+                    witness: DelegatedWitness::from(witness),
+                },
+            )?,
+        };
+
+        info!("requesting proof collection from {}", self.url);
+        let response = reqwest::Client::new()
+            .post(format!("{}/prove", self.url.trim_end_matches('/')))
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<EncryptedMessage>()
+            .await?;
+
+        let delegated = match open(&key, &response.payload)? {
+            ProveResponse::Proofs(proofs) => proofs,
+            ProveResponse::Error(e) => bail!("remote prover failed: {}", e),
+        };
+
+        // stops the prover if this future is dropped
+        let guard = monitor.cancel_on_drop();
+        let worker_monitor = monitor.clone();
+        let worker_witness = witness.clone();
+        let lock_scripts_halt = tokio::task::spawn_blocking(move || {
+            ProofBuilder::produce_lock_script_proofs(&worker_witness, &worker_monitor)
+        })
+        .await;
+        guard.disarm();
+
+        Ok(ProofBuilder::assemble_proof_collection(
+            witness,
+            delegated,
+            lock_scripts_halt??,
+        ))
+    }
+}
+
+/// Reads the hex encoded secret key of the prover server, creating it if the
+/// file does not exist.
+pub fn load_or_create_server_key(path: &Path) -> Result<Vec<u8>> {
+    if path.exists() {
+        let key = std::fs::read_to_string(path)?;
+        return hex::decode(key.trim()).context("invalid server key file");
+    }
+
+    let key = tls::generate_p256_secret()?;
+    std::fs::write(path, hex::encode(&key))?;
+    Ok(key)
+}
+
+pub fn server_public_key(secret_key: &[u8]) -> String {
+    hex::encode(tls::get_p256_pubkey(secret_key))
+}
+
+struct ServerState {
+    secret_key: Vec<u8>,
+    /// hex encoded prover identities of the wallets allowed to use the
+    /// prover, any wallet if empty
+    allowed_clients: Vec<String>,
+    jobs: Semaphore,
+}
+
+/// Runs the reference prover server until it fails.
+pub async fn run_server(
+    addr: SocketAddr,
+    secret_key: Vec<u8>,
+    allowed_clients: Vec<String>,
+    max_jobs: usize,
+) -> Result<()> {
+    if allowed_clients.is_empty() {
+        warn!("no allowed clients configured, any wallet can use this prover");
+    }
+
+    let state = Arc::new(ServerState {
+        secret_key,
+        allowed_clients,
+        jobs: Semaphore::new(max_jobs.max(1)),
+    });
+    let app = Router::new().route("/prove", post(prove)).with_state(state);
+
+    let listener = TcpListener::bind(addr).await?;
+    info!("prover listening on {}", addr);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+async fn prove(
+    State(state): State<Arc<ServerState>>,
+    Json(request): Json<EncryptedMessage>,
+) -> Result<Json<EncryptedMessage>, StatusCode> {
+    let client = request.client.ok_or(StatusCode::BAD_REQUEST)?;
+    if !state.allowed_clients.is_empty() && !state.allowed_clients.contains(&client) {
+        warn!("rejected prove request from {}", client);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let client_key = hex::decode(&client).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let key = session_key(&client_key, &state.secret_key).map_err(|_| StatusCode::BAD_REQUEST)?;
+    // fails unless the client holds the secret key of `client`
+    let request: ProveRequest =
+        open(&key, &request.payload).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let response = match request.version {
+        PROTOCOL_VERSION => {
+            let _permit = state.jobs.acquire().await.unwrap();
+            info!(
+                "proving {} on {} for {}",
+                request.witness.kernel_mast_hash(),
+                request.network,
+                client
+            );
+            let result = tokio::task::spawn_blocking(move || {
+                let total = request.witness.num_proofs();
+                ProofBuilder::produce_delegated_proofs(
+                    &request.witness,
+                    &ProofMonitor::default(),
+                    total,
+                )
+            })
+            .await;
+
+            match result {
+                Ok(Ok(proofs)) => ProveResponse::Proofs(proofs),
+                Ok(Err(e)) => ProveResponse::Error(e.to_string()),
+                Err(e) => ProveResponse::Error(e.to_string()),
+            }
+        }
+        version => ProveResponse::Error(format!("unsupported protocol version {version}")),
+    };
+
+    let payload = seal(&key, &response).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(EncryptedMessage {
+        client: None,
+        payload,
+    }))
+}
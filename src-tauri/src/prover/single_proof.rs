@@ -13,8 +13,9 @@ use neptune_cash::api::export::Transaction;
 use neptune_cash::api::export::TransactionDetails;
 use neptune_cash::api::export::TransactionProof;
 use neptune_cash::prelude::twenty_first::util_types::mmr::mmr_successor_proof::MmrSuccessorProof;
+use neptune_cash::protocol::consensus::block::block_height::BlockHeight;
 use neptune_cash::protocol::consensus::block::mutator_set_update::MutatorSetUpdate;
-use neptune_cash::protocol::consensus::block::Block;
+use neptune_cash::protocol::consensus::transaction::primitive_witness::PrimitiveWitness;
 use neptune_cash::protocol::consensus::transaction::transaction_kernel::TransactionKernelModifier;
 use neptune_cash::protocol::consensus::transaction::validity::single_proof::SingleProof;
 use neptune_cash::protocol::consensus::transaction::validity::single_proof::SingleProofWitness;
@@ -29,16 +30,24 @@ use rand::rng;
 use rand::Rng;
 use tracing::info;
 
+use super::ProofMonitor;
+use super::ProofStage;
+
 impl super::ProofBuilder {
+    /// Upgrades `transaction` to a single proof, merged with a gobbler if a
+    /// gobble fee is set. `mutator_set` must be the one the transaction was
+    /// proven against.
     pub fn upgrade_proof(
         &self,
         transaction: Transaction,
-        block: &Block,
+        mutator_set: &MutatorSetAccumulator,
+        block_height: BlockHeight,
         own_wallet_entropy: Option<WalletEntropy>,
+        monitor: &ProofMonitor,
     ) -> Result<Transaction> {
         let proof_collection = match &transaction.proof {
             TransactionProof::Witness(primitive_witness) => {
-                let proof_collection = Self::produce_proof_collection(&primitive_witness)?;
+                let proof_collection = Self::produce_proof_collection(&primitive_witness, monitor)?;
                 proof_collection
             }
             TransactionProof::SingleProof(_) => bail!("single proof do not need upgrade"),
@@ -46,7 +55,7 @@ impl super::ProofBuilder {
         };
 
         let single_proof_witness = SingleProofWitness::from_collection(proof_collection);
-        let proof = Self::single_proof_from_witness(&single_proof_witness)?;
+        let proof = Self::single_proof_from_witness(&single_proof_witness, monitor)?;
 
         let new_tx = Transaction {
             kernel: transaction.kernel.clone(),
@@ -57,22 +66,35 @@ impl super::ProofBuilder {
             let gobble_tx = self.build_gobbler(
                 fee,
                 &own_wallet_entropy.context("gobbler requires wallet_entropy")?,
-                block,
+                mutator_set,
+                block_height,
                 &transaction,
                 self.network,
+                monitor,
             )?;
 
-            return self.merge_single_proof(gobble_tx, new_tx);
+            return self.merge_single_proof(gobble_tx, new_tx, monitor);
         }
 
         Ok(new_tx)
     }
 
+    /// Proves a transaction from scratch, through a proof collection.
+    pub fn produce_single_proof(
+        primitive_witness: &PrimitiveWitness,
+        monitor: &ProofMonitor,
+    ) -> Result<NeptuneProof> {
+        let proof_collection = Self::produce_proof_collection(primitive_witness, monitor)?;
+        let single_proof_witness = SingleProofWitness::from_collection(proof_collection);
+        Self::single_proof_from_witness(&single_proof_witness, monitor)
+    }
+
     pub fn update_single_proof(
         &self,
         tx: Transaction,
         previous_mutator_set_accumulator: &MutatorSetAccumulator,
         mutator_set_update: MutatorSetUpdate,
+        monitor: &ProofMonitor,
     ) -> Result<Transaction> {
         let old_transaction_kernel = tx.kernel;
         let old_single_proof = match tx.proof {
@@ -142,7 +164,7 @@ impl super::ProofBuilder {
         let new_single_proof_witness = SingleProofWitness::from_update(update_witness);
 
         info!("starting single proof via update ...");
-        let proof = Self::single_proof_from_witness(&new_single_proof_witness)?;
+        let proof = Self::single_proof_from_witness(&new_single_proof_witness, monitor)?;
         info!("done.");
 
         Ok(Transaction {
@@ -151,7 +173,11 @@ impl super::ProofBuilder {
         })
     }
 
-    fn single_proof_from_witness(witness: &SingleProofWitness) -> Result<NeptuneProof> {
+    fn single_proof_from_witness(
+        witness: &SingleProofWitness,
+        monitor: &ProofMonitor,
+    ) -> Result<NeptuneProof> {
+        monitor.start(ProofStage::SingleProof, 0, 1)?;
         let claim = witness.claim();
 
         let proof = Self::produce(SingleProof.program(), claim, witness.nondeterminism())?.into();
@@ -163,12 +189,12 @@ impl super::ProofBuilder {
         &self,
         gobbling_fee: NativeCurrencyAmount,
         own_wallet_entropy: &WalletEntropy,
-        block: &Block,
+        mutator_set: &MutatorSetAccumulator,
+        current_block_height: BlockHeight,
         old_tx: &Transaction,
         network: Network,
+        monitor: &ProofMonitor,
     ) -> Result<Transaction> {
-        let mutator_set = block.mutator_set_accumulator_after()?;
-        let current_block_height = block.header().height;
         let old_tx_timestamp = old_tx.kernel.timestamp;
 
         info!("Producing gobbler-transaction for a value of {gobbling_fee}");
@@ -178,7 +204,7 @@ impl super::ProofBuilder {
         let gobbler = TransactionDetails::fee_gobbler(
             gobbling_fee,
             own_wallet_entropy.generate_sender_randomness(current_block_height, receiver_digest),
-            mutator_set,
+            mutator_set.clone(),
             old_tx_timestamp,
             utxo_notification_method,
             network,
@@ -186,9 +212,9 @@ impl super::ProofBuilder {
 
         let gobbler_witness = gobbler.primitive_witness();
 
-        let proof_collection = Self::produce_proof_collection(&gobbler_witness)?;
+        let proof_collection = Self::produce_proof_collection(&gobbler_witness, monitor)?;
         let single_proof_witness = SingleProofWitness::from_collection(proof_collection);
-        let proof = Self::single_proof_from_witness(&single_proof_witness)?;
+        let proof = Self::single_proof_from_witness(&single_proof_witness, monitor)?;
 
         info!("Done producing gobbler-transaction for a value of {gobbling_fee}");
         let gobbler_tx = Transaction {
@@ -213,7 +239,12 @@ impl super::ProofBuilder {
         (fee_notification_method, receiver_preimage)
     }
 
-    fn merge_single_proof(&self, tx1: Transaction, tx2: Transaction) -> Result<Transaction> {
+    fn merge_single_proof(
+        &self,
+        tx1: Transaction,
+        tx2: Transaction,
+        monitor: &ProofMonitor,
+    ) -> Result<Transaction> {
         ensure!(
             tx1.kernel.mutator_set_hash == tx2.kernel.mutator_set_hash,
             "Mutator sets must be equal for transaction merger."
@@ -235,7 +266,7 @@ impl super::ProofBuilder {
         let new_kernel = merge_witness.new_kernel.clone();
         let new_single_proof_witness = SingleProofWitness::from_merge(merge_witness);
 
-        let proof = Self::single_proof_from_witness(&new_single_proof_witness)?;
+        let proof = Self::single_proof_from_witness(&new_single_proof_witness, monitor)?;
 
         Ok(Transaction {
             kernel: new_kernel,
@@ -0,0 +1,263 @@
+use neptune_cash::api::export::NeptuneProof;
+use neptune_cash::api::export::Network;
+use neptune_cash::api::export::Transaction;
+use neptune_cash::api::export::TransactionProof;
+use neptune_cash::prelude::tasm_lib::prelude::Digest;
+use neptune_cash::prelude::tasm_lib::triton_vm;
+use neptune_cash::protocol::consensus::transaction::validity::proof_collection::ProofCollection;
+use neptune_cash::protocol::consensus::transaction::validity::single_proof::SingleProof;
+use neptune_cash::protocol::proof_abstractions::mast_hash::MastHash;
+use neptune_cash::protocol::proof_abstractions::tasm::program::ConsensusProgram;
+use thiserror::Error;
+use tracing::*;
+use triton_vm::proof::Claim;
+use triton_vm::stark::Stark;
+
+use super::ProofStage;
+
+/// Reason [verify_transaction] rejected a transaction.
+#[derive(Debug, Error)]
+pub enum InvalidProof {
+    #[error("proof is for kernel {proof}, but the transaction kernel is {kernel}")]
+    KernelMismatch { kernel: Digest, proof: Digest },
+    #[error("expected {expected} {kind} script proofs, got {got}")]
+    ProofCount {
+        kind: &'static str,
+        expected: usize,
+        got: usize,
+    },
+    #[error("{0:?} proof does not verify")]
+    Rejected(ProofStage),
+    #[error("invalid primitive witness: {0}")]
+    Witness(String),
+    #[error("verifier failed: {0}")]
+    Verifier(String),
+}
+
+/// Checks that the proof of `transaction` is valid for its kernel, every
+/// sub-proof of a proof collection on its own, before it is broadcast.
+pub async fn verify_transaction(
+    transaction: &Transaction,
+    network: Network,
+) -> Result<(), InvalidProof> {
+    let kernel = transaction.kernel.mast_hash();
+
+    let claims = match &transaction.proof {
+        TransactionProof::Witness(witness) => {
+            check_kernel(kernel, witness.kernel.mast_hash())?;
+            return witness
+                .validate()
+                .await
+                .map_err(|e| InvalidProof::Witness(e.to_string()));
+        }
+        TransactionProof::SingleProof(proof) => {
+            let claim =
+                Claim::about_program(&SingleProof.program()).with_input(kernel.reversed().values());
+            vec![(ProofStage::SingleProof, claim, proof.clone())]
+        }
+        TransactionProof::ProofCollection(proof_collection) => {
+            check_kernel(kernel, proof_collection.kernel_mast_hash)?;
+            proof_collection_claims(proof_collection)?
+        }
+    };
+
+    let result = tokio::task::spawn_blocking(move || verify_claims(claims, network)).await;
+    result.map_err(|e| InvalidProof::Verifier(e.to_string()))?
+}
+
+fn check_kernel(kernel: Digest, proof: Digest) -> Result<(), InvalidProof> {
+    match kernel == proof {
+        true => Ok(()),
+        false => Err(InvalidProof::KernelMismatch { kernel, proof }),
+    }
+}
+
+fn proof_collection_claims(
+    pc: &ProofCollection,
+) -> Result<Vec<(ProofStage, Claim, NeptuneProof)>, InvalidProof> {
+    // the claims are zipped with the proofs, a missing proof would go unchecked
+    let counts = [
+        (
+            "lock",
+            pc.lock_script_hashes.len(),
+            pc.lock_scripts_halt.len(),
+        ),
+        (
+            "type",
+            pc.type_script_hashes.len(),
+            pc.type_scripts_halt.len(),
+        ),
+    ];
+    for (kind, expected, got) in counts {
+        if expected != got {
+            return Err(InvalidProof::ProofCount {
+                kind,
+                expected,
+                got,
+            });
+        }
+    }
+
+    let mut claims = vec![
+        (
+            ProofStage::RemovalRecordsIntegrity,
+            pc.removal_records_integrity_claim(),
+            pc.removal_records_integrity.clone(),
+        ),
+        (
+            ProofStage::CollectLockScripts,
+            pc.collect_lock_scripts_claim(),
+            pc.collect_lock_scripts.clone(),
+        ),
+        (
+            ProofStage::KernelToOutputs,
+            pc.kernel_to_outputs_claim(),
+            pc.kernel_to_outputs.clone(),
+        ),
+        (
+            ProofStage::CollectTypeScripts,
+            pc.collect_type_scripts_claim(),
+            pc.collect_type_scripts.clone(),
+        ),
+    ];
+    for (i, (claim, proof)) in pc
+        .lock_script_claims()
+        .into_iter()
+        .zip(&pc.lock_scripts_halt)
+        .enumerate()
+    {
+        claims.push((ProofStage::LockScript(i), claim, proof.clone()));
+    }
+    for (i, (claim, proof)) in pc
+        .type_script_claims()
+        .into_iter()
+        .zip(&pc.type_scripts_halt)
+        .enumerate()
+    {
+        claims.push((ProofStage::TypeScript(i), claim, proof.clone()));
+    }
+
+    Ok(claims)
+}
+
+fn verify_claims(
+    claims: Vec<(ProofStage, Claim, NeptuneProof)>,
+    network: Network,
+) -> Result<(), InvalidProof> {
+    for (stage, claim, proof) in claims {
+        debug!("verifying {:?} proof", stage);
+        // networks with mock proofs accept nothing else, like the node does
+        let valid = match network.use_mock_proof() {
+            true => proof.is_valid_mock(),
+            false => triton_vm::verify(Stark::default(), &claim, &proof.into()),
+        };
+        if !valid {
+            warn!("{:?} proof does not verify", stage);
+            return Err(InvalidProof::Rejected(stage));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use neptune_cash::prelude::triton_vm::prelude::BFieldElement;
+
+    use super::*;
+
+    fn digest(i: u64) -> Digest {
+        Digest::new([BFieldElement::new(i); Digest::LEN])
+    }
+
+    fn collection(num_lock_scripts: usize, num_type_scripts: usize) -> ProofCollection {
+        ProofCollection {
+            removal_records_integrity: NeptuneProof::mock(true),
+            collect_lock_scripts: NeptuneProof::mock(true),
+            lock_scripts_halt: vec![NeptuneProof::mock(true); num_lock_scripts],
+            kernel_to_outputs: NeptuneProof::mock(true),
+            collect_type_scripts: NeptuneProof::mock(true),
+            type_scripts_halt: vec![NeptuneProof::mock(true); num_type_scripts],
+            lock_script_hashes: (0..num_lock_scripts as u64).map(digest).collect(),
+            type_script_hashes: (0..num_type_scripts as u64).map(digest).collect(),
+            kernel_mast_hash: digest(7),
+            salted_inputs_hash: digest(8),
+            salted_outputs_hash: digest(9),
+            merge_bit_mast_path: vec![],
+        }
+    }
+
+    #[test]
+    fn kernel_mismatch_is_rejected() {
+        assert!(check_kernel(digest(1), digest(1)).is_ok());
+        assert!(matches!(
+            check_kernel(digest(1), digest(2)),
+            Err(InvalidProof::KernelMismatch { kernel, proof })
+                if kernel == digest(1) && proof == digest(2)
+        ));
+    }
+
+    #[test]
+    fn missing_lock_script_proof_is_rejected() {
+        let mut pc = collection(2, 1);
+        pc.lock_scripts_halt.pop();
+
+        assert!(matches!(
+            proof_collection_claims(&pc),
+            Err(InvalidProof::ProofCount {
+                kind: "lock",
+                expected: 2,
+                got: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn extra_type_script_proof_is_rejected() {
+        let mut pc = collection(1, 1);
+        pc.type_scripts_halt.push(NeptuneProof::mock(true));
+
+        assert!(matches!(
+            proof_collection_claims(&pc),
+            Err(InvalidProof::ProofCount {
+                kind: "type",
+                expected: 1,
+                got: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn every_proof_is_claimed() {
+        let stages = proof_collection_claims(&collection(2, 1))
+            .unwrap()
+            .into_iter()
+            .map(|(stage, _, _)| stage)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            stages,
+            vec![
+                ProofStage::RemovalRecordsIntegrity,
+                ProofStage::CollectLockScripts,
+                ProofStage::KernelToOutputs,
+                ProofStage::CollectTypeScripts,
+                ProofStage::LockScript(0),
+                ProofStage::LockScript(1),
+                ProofStage::TypeScript(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_sub_proof_is_rejected() {
+        let mut pc = collection(2, 1);
+        assert!(verify_claims(proof_collection_claims(&pc).unwrap(), Network::RegTest).is_ok());
+
+        pc.lock_scripts_halt[1] = NeptuneProof::mock(false);
+        assert!(matches!(
+            verify_claims(proof_collection_claims(&pc).unwrap(), Network::RegTest),
+            Err(InvalidProof::Rejected(ProofStage::LockScript(1)))
+        ));
+    }
+}
@@ -1,9 +1,26 @@
+use anyhow::anyhow;
 use anyhow::Result;
+use serde::de::DeserializeOwned;
 
+use super::consolidate::ConsolidateParams;
+use super::consolidate::ConsolidateResponse;
+use super::error::RestError;
+use super::fee::FeeEstimateResponse;
+use super::jobs::SubmitJobResponse;
+use super::offline::BroadcastTransactionParams;
+use super::offline::SignTransactionParams;
+use super::offline::SignedTransactionResponse;
+use super::offline::UnsignedTransactionResponse;
+use super::sweep::SweepParams;
+use super::sweep::SweepResponse;
+use super::transaction_status::BumpFeeParams;
+use super::transaction_status::BumpFeeResponse;
 use super::SendResponse;
 use super::SendToAddressParams;
 use crate::config;
 use crate::wallet::balance::WalletHistory;
+use crate::wallet::job::Job;
+use crate::wallet::SendPreview;
 
 pub struct RestRpcClient {
     client: reqwest::Client,
@@ -30,7 +47,15 @@ impl RestRpcClient {
     pub async fn history(&self) -> Result<Vec<WalletHistory>> {
         let url = format!("{}/rpc/wallet/history", Self::api_url());
 
-        let resp = self.client.get(url).send().await?.json().await?;
+        let resp = decode(self.client.get(url).send().await?).await?;
+
+        Ok(resp)
+    }
+
+    pub async fn estimate_fee(&self) -> Result<FeeEstimateResponse> {
+        let url = format!("{}/rpc/fee/estimate", Self::api_url());
+
+        let resp = decode(self.client.get(url).send().await?).await?;
 
         Ok(resp)
     }
@@ -38,15 +63,131 @@ impl RestRpcClient {
     pub async fn send(&self, params: &SendToAddressParams) -> Result<String> {
         let url = format!("{}/rpc/send", Self::api_url());
 
+        let resp = self.client.post(url).json(&params).send().await?;
+        let resp = decode::<SendResponse>(resp).await?;
+
+        Ok(resp.txid)
+    }
+
+    pub async fn submit_send_job(&self, params: &SendToAddressParams) -> Result<i64> {
+        let url = format!("{}/rpc/jobs/send", Self::api_url());
+
+        let resp = self.client.post(url).json(&params).send().await?;
+        let resp = decode::<SubmitJobResponse>(resp).await?;
+
+        Ok(resp.id)
+    }
+
+    pub async fn list_jobs(&self) -> Result<Vec<Job>> {
+        let url = format!("{}/rpc/jobs", Self::api_url());
+
+        let resp = decode(self.client.get(url).send().await?).await?;
+
+        Ok(resp)
+    }
+
+    pub async fn get_job(&self, id: i64) -> Result<Job> {
+        let url = format!("{}/rpc/jobs/{}", Self::api_url(), id);
+
+        let resp = decode(self.client.get(url).send().await?).await?;
+
+        Ok(resp)
+    }
+
+    pub async fn cancel_job(&self, id: i64) -> Result<()> {
+        let url = format!("{}/rpc/jobs/{}/cancel", Self::api_url(), id);
+
+        decode::<bool>(self.client.post(url).send().await?).await?;
+
+        Ok(())
+    }
+
+    pub async fn preview_send(&self, params: &SendToAddressParams) -> Result<SendPreview> {
+        let url = format!("{}/rpc/send/preview", Self::api_url());
+
+        let resp = self.client.post(url).json(&params).send().await?;
+        let resp = decode::<SendPreview>(resp).await?;
+
+        Ok(resp)
+    }
+
+    pub async fn consolidate(&self, params: &ConsolidateParams) -> Result<ConsolidateResponse> {
+        let url = format!("{}/rpc/wallet/consolidate", Self::api_url());
+
+        let resp = self.client.post(url).json(&params).send().await?;
+        let resp = decode::<ConsolidateResponse>(resp).await?;
+
+        Ok(resp)
+    }
+
+    pub async fn sweep(&self, params: &SweepParams) -> Result<SweepResponse> {
+        let url = format!("{}/rpc/wallet/sweep", Self::api_url());
+
+        let resp = self.client.post(url).json(&params).send().await?;
+        let resp = decode::<SweepResponse>(resp).await?;
+
+        Ok(resp)
+    }
+
+    pub async fn create_unsigned_transaction(
+        &self,
+        params: &SendToAddressParams,
+    ) -> Result<UnsignedTransactionResponse> {
+        let url = format!("{}/rpc/tx/create", Self::api_url());
+
+        let resp = self.client.post(url).json(&params).send().await?;
+        let resp = decode::<UnsignedTransactionResponse>(resp).await?;
+
+        Ok(resp)
+    }
+
+    pub async fn sign_transaction(&self, unsigned_tx: String) -> Result<SignedTransactionResponse> {
+        let url = format!("{}/rpc/tx/sign", Self::api_url());
+
         let resp = self
             .client
             .post(url)
-            .json(&params)
+            .json(&SignTransactionParams { unsigned_tx })
             .send()
-            .await?
-            .json::<SendResponse>()
             .await?;
+        let resp = decode::<SignedTransactionResponse>(resp).await?;
 
-        Ok(resp.txid)
+        Ok(resp)
+    }
+
+    pub async fn broadcast_signed_transaction(&self, signed_tx: String) -> Result<String> {
+        let url = format!("{}/rpc/tx/broadcast", Self::api_url());
+
+        let resp = self
+            .client
+            .post(url)
+            .json(&BroadcastTransactionParams { signed_tx })
+            .send()
+            .await?;
+        let resp = decode::<String>(resp).await?;
+
+        Ok(resp)
     }
+
+    pub async fn bump_fee(&self, params: &BumpFeeParams) -> Result<BumpFeeResponse> {
+        let url = format!("{}/rpc/tx/bump_fee", Self::api_url());
+
+        let resp = self.client.post(url).json(&params).send().await?;
+        let resp = decode::<BumpFeeResponse>(resp).await?;
+
+        Ok(resp)
+    }
+}
+
+/// Decodes a response, turning an error response into its [RestError].
+async fn decode<T: DeserializeOwned>(resp: reqwest::Response) -> Result<T> {
+    let status = resp.status();
+    if !status.is_success() {
+        return match resp.json::<RestError>().await {
+            Ok(err) => Err(err.into()),
+            Err(_) => Err(anyhow!("request failed with status {status}")),
+        };
+    }
+
+    Ok(resp.json().await?)
 }
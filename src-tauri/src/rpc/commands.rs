@@ -5,15 +5,34 @@ use tracing::*;
 
 use super::tls;
 use crate::command::{Result, TauriCommandResultExt};
+use crate::config::wallet::ScanConfig;
 use crate::config::Config;
 use crate::rpc::block::BlockInfoRpc;
+use crate::rpc::consolidate::{ConsolidateParams, ConsolidateResponse, ConsolidateRpc};
 use crate::rpc::error::RestError;
-use crate::rpc::transaction_status::{TransactionStatus, TransactionStatusRpc};
+use crate::rpc::fee::{FeeEstimateResponse, FeeRpc};
+use crate::rpc::jobs::{JobRpc, SubmitJobResponse};
+use crate::rpc::offline::{
+    BroadcastTransactionParams, OfflineTransactionRpc, SignTransactionParams,
+    SignedTransactionResponse, UnsignedTransactionResponse,
+};
+use crate::rpc::rescan::{RescanParams, RescanRpc};
+use crate::rpc::scan_config::ScanConfigRpc;
+use crate::rpc::sweep::{SweepParams, SweepResponse, SweepRpc};
+use crate::rpc::transaction_status::{
+    BumpFeeParams, BumpFeeResponse, TransactionStatus, TransactionStatusRpc,
+};
+use crate::rpc::upgrader::{SubmitUpgradeParams, SubmitUpgradeResponse, UpgraderRpc};
 use crate::rpc::{
     SendResponse, SendToAddressParams, Utxo, WalletBalance, WalletRpc, WalletRpcImpl,
 };
 use crate::wallet::balance::WalletHistory;
-use crate::wallet::sync::{SyncState, SyncStatus};
+use crate::wallet::job::Job;
+use crate::wallet::registry::WalletRegistry;
+use crate::wallet::rescan::RescanResult;
+use crate::wallet::sync::SyncStatus;
+use crate::wallet::upgrader::UpgradeItem;
+use crate::wallet::SendPreview;
 
 #[cfg_attr(feature = "gui", tauri::command)]
 #[cfg_attr(not(feature = "gui"), allow(unused))]
@@ -40,9 +59,8 @@ pub async fn get_token() -> Result<String> {
 #[cfg_attr(feature = "gui", tauri::command)]
 pub async fn run_rpc_server() -> Result<()> {
     start_rpc_server_inner().await.map_err(|e| {
-        let err = e.to_string();
-        error!("error start rpc: {}", err);
-        err
+        error!("error start rpc: {}", e);
+        e
     })
 }
 
@@ -50,7 +68,7 @@ pub async fn start_rpc_server_inner() -> Result<()> {
     let mut rpc_handler = super::RPC_CLOSER.lock().await;
     if let Some(handler) = rpc_handler.deref() {
         if !handler.is_finished() {
-            return Err("rpc server is already running".to_string());
+            return Err(RestError::conflict("rpc server is already running"));
         };
         rpc_handler
             .take()
@@ -61,15 +79,8 @@ pub async fn start_rpc_server_inner() -> Result<()> {
     }
     drop(rpc_handler);
 
-    if let Some(old) = crate::service::try_get_state::<Arc<SyncState>>() {
-        old.cancel_sync().await;
-    }
-
     let config = crate::service::get_state::<Arc<Config>>();
-
-    let sync_state = Arc::new(SyncState::new(&config).await.into_tauri_result()?);
-    crate::service::manage_or_replace(sync_state.clone());
-    sync_state.sync().await;
+    WalletRegistry::restart(&config).await.into_tauri_result()?;
 
     super::start_rpc_server().await.into_tauri_result()?;
 
@@ -78,9 +89,9 @@ pub async fn start_rpc_server_inner() -> Result<()> {
 
 #[cfg_attr(feature = "gui", tauri::command)]
 pub async fn stop_rpc_server() -> Result<()> {
-    if let Some(sync_state) = crate::service::try_get_state::<Arc<SyncState>>() {
+    if let Some(registry) = crate::service::try_get_state::<Arc<WalletRegistry>>() {
         super::stop_rpc_server().await.into_tauri_result()?;
-        sync_state.cancel_sync().await;
+        registry.stop().await;
     };
 
     Ok(())
@@ -89,8 +100,8 @@ pub async fn stop_rpc_server() -> Result<()> {
 impl<T> TauriCommandResultExt for std::result::Result<T, RestError> {
     type Output = T;
 
-    fn into_tauri_result(self) -> std::result::Result<T, String> {
-        self.map_err(|e| e.0)
+    fn into_tauri_result(self) -> std::result::Result<T, RestError> {
+        self
     }
 }
 
@@ -128,6 +139,41 @@ pub async fn send_to_address(params: SendToAddressParams) -> Result<SendResponse
         .into_tauri_result()
 }
 
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn cancel_send() -> bool {
+    WalletRpcImpl::cancel_send().await
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn create_unsigned_transaction(
+    params: SendToAddressParams,
+) -> Result<UnsignedTransactionResponse> {
+    WalletRpcImpl::create_unsigned_transaction(params)
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn sign_transaction(unsigned_tx: String) -> Result<SignedTransactionResponse> {
+    WalletRpcImpl::sign_transaction(SignTransactionParams { unsigned_tx })
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn broadcast_signed_transaction(signed_tx: String) -> Result<String> {
+    WalletRpcImpl::broadcast_signed_transaction(BroadcastTransactionParams { signed_tx })
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn preview_send(params: SendToAddressParams) -> Result<SendPreview> {
+    WalletRpcImpl::preview_send(params)
+        .await
+        .into_tauri_result()
+}
+
 #[cfg_attr(feature = "gui", tauri::command)]
 pub async fn pending_transactions() -> Result<Vec<TransactionStatus>> {
     WalletRpcImpl::pending_transactions()
@@ -140,7 +186,84 @@ pub async fn forget_tx(txid: String) -> Result<()> {
     WalletRpcImpl::forget_tx(txid).await.into_tauri_result()
 }
 
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn bump_fee(txid: String, fee: String) -> Result<BumpFeeResponse> {
+    WalletRpcImpl::bump_fee(BumpFeeParams { txid, fee })
+        .await
+        .into_tauri_result()
+}
+
 #[cfg_attr(feature = "gui", tauri::command)]
 pub async fn get_tip_height() -> Result<u64> {
     WalletRpcImpl::get_tip_height().await.into_tauri_result()
-}
\ No newline at end of file
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn estimate_fee(inputs: Option<u64>) -> Result<FeeEstimateResponse> {
+    WalletRpcImpl::estimate_fee(inputs)
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn consolidate(max_inputs: Option<usize>, fee: String) -> Result<ConsolidateResponse> {
+    WalletRpcImpl::consolidate(ConsolidateParams { max_inputs, fee })
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn sweep(params: SweepParams) -> Result<SweepResponse> {
+    WalletRpcImpl::sweep(params).await.into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_scan_config() -> Result<ScanConfig> {
+    WalletRpcImpl::scan_config().await.into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn update_scan_config(scan_config: ScanConfig) -> Result<RescanResult> {
+    WalletRpcImpl::update_scan_config(scan_config)
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn rescan(params: RescanParams) -> Result<RescanResult> {
+    WalletRpcImpl::rescan(params).await.into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn submit_send_job(params: SendToAddressParams) -> Result<SubmitJobResponse> {
+    WalletRpcImpl::submit_send_job(params)
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn list_jobs() -> Result<Vec<Job>> {
+    WalletRpcImpl::list_jobs().await.into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn get_job(id: i64) -> Result<Job> {
+    WalletRpcImpl::get_job(id).await.into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn cancel_job(id: i64) -> Result<()> {
+    WalletRpcImpl::cancel_job(id).await.into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn submit_upgrade(params: SubmitUpgradeParams) -> Result<SubmitUpgradeResponse> {
+    WalletRpcImpl::submit_upgrade(params)
+        .await
+        .into_tauri_result()
+}
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub async fn list_upgrades() -> Result<Vec<UpgradeItem>> {
+    WalletRpcImpl::list_upgrades().await.into_tauri_result()
+}
@@ -0,0 +1,58 @@
+use axum::Json;
+use axum_extra::response::ErasedJson;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+use utoipa::ToSchema;
+
+use super::error::RestError;
+use super::parse_amount;
+use crate::rpc::WalletRpcImpl;
+use crate::wallet::consolidate::DEFAULT_CONSOLIDATE_MAX_INPUTS;
+use crate::wallet::registry::current_sync_state;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ConsolidateParams {
+    /// defaults to [DEFAULT_CONSOLIDATE_MAX_INPUTS]
+    pub max_inputs: Option<usize>,
+    pub fee: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ConsolidateResponse {
+    /// `None` if there was nothing to consolidate
+    pub txid: Option<String>,
+    pub inputs: usize,
+}
+
+pub async fn consolidate(Json(params): Json<ConsolidateParams>) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::consolidate(params).await?,
+    ))
+}
+
+pub trait ConsolidateRpc {
+    async fn consolidate(params: ConsolidateParams) -> Result<ConsolidateResponse, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let fee = parse_amount(&params.fee)?;
+        let max_inputs = params.max_inputs.unwrap_or(DEFAULT_CONSOLIDATE_MAX_INPUTS);
+
+        let tx = wallet.consolidate(max_inputs, fee).await?;
+
+        Ok(match tx {
+            Some(tx) => {
+                info!("proven consolidation tx {}", tx.txid());
+                ConsolidateResponse {
+                    txid: Some(tx.txid().to_string()),
+                    inputs: tx.kernel.inputs.len(),
+                }
+            }
+            None => ConsolidateResponse {
+                txid: None,
+                inputs: 0,
+            },
+        })
+    }
+}
+
+impl ConsolidateRpc for WalletRpcImpl {}
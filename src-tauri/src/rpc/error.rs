@@ -1,28 +1,224 @@
+use std::fmt::Display;
+
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::config::PasswordError;
+use crate::prover::InvalidProof;
+use crate::prover::ProofCancelled;
+use crate::rpc_client::BroadcastError;
+use crate::wallet::job::JobError;
+use crate::wallet::rescan::RescanError;
+use crate::wallet::InsufficientFunds;
+use crate::wallet::SendError;
+
+/// Machine readable error codes of the REST API and the Tauri commands.
+///
+/// The codes are stable, clients should match on them rather than on the
+/// message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidParams,
+    InvalidAddress,
+    InvalidAmount,
+    Unauthorized,
+    WrongPassword,
+    /// the password has not been entered yet
+    Locked,
+    NotFound,
+    InsufficientFunds,
+    WatchOnly,
+    Conflict,
+    Cancelled,
+    ProvingFailed,
+    InvalidProof,
+    /// the node could not be reached or timed out
+    NodeUnavailable,
+    NodeBusy,
+    NodeRejected,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidParams | ErrorCode::InvalidAddress | ErrorCode::InvalidAmount => {
+                StatusCode::BAD_REQUEST
+            }
+            ErrorCode::Unauthorized | ErrorCode::WrongPassword | ErrorCode::Locked => {
+                StatusCode::UNAUTHORIZED
+            }
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::InsufficientFunds
+            | ErrorCode::WatchOnly
+            | ErrorCode::Conflict
+            | ErrorCode::Cancelled => StatusCode::CONFLICT,
+            ErrorCode::NodeUnavailable | ErrorCode::NodeBusy => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::NodeRejected => StatusCode::BAD_GATEWAY,
+            ErrorCode::ProvingFailed | ErrorCode::InvalidProof | ErrorCode::Internal => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+/// Error returned by the REST API and the Tauri commands, serialized as
+/// `{"code": "...", "message": "..."}`.
+#[derive(Debug, Clone, Error, Serialize, Deserialize, ToSchema)]
+#[error("{message}")]
+pub struct RestError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl RestError {
+    pub fn new(code: ErrorCode, message: impl Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn invalid_params(message: impl Display) -> Self {
+        Self::new(ErrorCode::InvalidParams, message)
+    }
+
+    pub fn not_found(message: impl Display) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
 
-/// An enum of error handlers for the REST API server.
-#[derive(Debug)]
-pub struct RestError(pub String);
+    pub fn conflict(message: impl Display) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+}
 
 impl IntoResponse for RestError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Something went wrong: {}", self.0),
-        )
-            .into_response()
+        (self.code.status(), Json(self)).into_response()
     }
 }
 
 impl From<anyhow::Error> for RestError {
     fn from(err: anyhow::Error) -> Self {
-        Self(err.to_string())
+        let code = err
+            .chain()
+            .find_map(error_code)
+            .unwrap_or(ErrorCode::Internal);
+        Self::new(code, format!("{:#}", err))
+    }
+}
+
+impl From<SendError> for RestError {
+    fn from(err: SendError) -> Self {
+        Self::new(send_error_code(&err), format!("{:#}", err))
     }
 }
 
-impl Into<String> for RestError {
-    fn into(self) -> String {
-        self.0
+/// Code of the known error types, the first match in the chain wins.
+fn error_code(err: &(dyn std::error::Error + 'static)) -> Option<ErrorCode> {
+    if let Some(e) = err.downcast_ref::<RestError>() {
+        return Some(e.code);
+    }
+    if let Some(e) = err.downcast_ref::<SendError>() {
+        return Some(send_error_code(e));
+    }
+    if let Some(e) = err.downcast_ref::<BroadcastError>() {
+        return Some(broadcast_error_code(e));
+    }
+    if let Some(e) = err.downcast_ref::<PasswordError>() {
+        return Some(match e {
+            PasswordError::Wrong => ErrorCode::WrongPassword,
+            PasswordError::NotSet | PasswordError::Locked => ErrorCode::Locked,
+        });
+    }
+    if let Some(e) = err.downcast_ref::<reqwest::Error>() {
+        if e.is_connect() || e.is_timeout() {
+            return Some(ErrorCode::NodeUnavailable);
+        }
+    }
+    if let Some(e) = err.downcast_ref::<JobError>() {
+        return Some(match e {
+            JobError::NotFound(_) => ErrorCode::NotFound,
+            JobError::Finished(_) | JobError::Broadcasting(_) => ErrorCode::Conflict,
+        });
+    }
+    if err.is::<RescanError>() {
+        return Some(ErrorCode::InvalidParams);
+    }
+    if err.is::<InsufficientFunds>() {
+        return Some(ErrorCode::InsufficientFunds);
+    }
+    if err.is::<InvalidProof>() {
+        return Some(ErrorCode::InvalidProof);
+    }
+    if err.is::<ProofCancelled>() {
+        return Some(ErrorCode::Cancelled);
+    }
+    None
+}
+
+fn send_error_code(err: &SendError) -> ErrorCode {
+    match err {
+        SendError::Proof(e) => e
+            .chain()
+            .find_map(error_code)
+            .unwrap_or(ErrorCode::ProvingFailed),
+        SendError::InvalidProof(_) => ErrorCode::InvalidProof,
+        SendError::Broadcast(e) => broadcast_error_code(e),
+        SendError::WatchOnly => ErrorCode::WatchOnly,
+        SendError::Cancelled => ErrorCode::Cancelled,
+        SendError::UnknownTransaction(_) => ErrorCode::NotFound,
+        SendError::FeeNotIncreased(_) => ErrorCode::InvalidParams,
+    }
+}
+
+fn broadcast_error_code(err: &BroadcastError) -> ErrorCode {
+    match err {
+        BroadcastError::Busy => ErrorCode::NodeBusy,
+        BroadcastError::Timeout | BroadcastError::Connection(_) => ErrorCode::NodeUnavailable,
+        BroadcastError::Server(_) => ErrorCode::NodeRejected,
+        BroadcastError::Internal(_) => ErrorCode::Internal,
+    }
+}
+
+impl From<RestError> for String {
+    fn from(err: RestError) -> Self {
+        err.message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn finds_code_in_error_chain() {
+        let err = Err::<(), _>(PasswordError::Wrong)
+            .context("failed to decrypt config")
+            .unwrap_err();
+        let err = RestError::from(err);
+
+        assert_eq!(err.code, ErrorCode::WrongPassword);
+        assert_eq!(err.code.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(err.message, "failed to decrypt config: password is wrong");
+    }
+
+    #[test]
+    fn unknown_errors_are_internal() {
+        let err = RestError::from(anyhow::anyhow!("disk full"));
+
+        assert_eq!(err.code, ErrorCode::Internal);
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({ "code": "internal", "message": "disk full" })
+        );
     }
 }
@@ -0,0 +1,59 @@
+use std::convert::Infallible;
+
+use axum::extract::Query;
+use axum::response::sse::Event;
+use axum::response::sse::KeepAlive;
+use axum::response::sse::Sse;
+use futures::Stream;
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::*;
+
+use crate::wallet::events::WalletEvent;
+use crate::wallet::registry::current_sync_state;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EventsParams {
+    /// comma separated event types to receive, e.g.
+    /// `utxo_received,balance_changed`, all events if unset
+    pub types: Option<String>,
+}
+
+impl EventsParams {
+    fn accepts(&self, event: &WalletEvent) -> bool {
+        match &self.types {
+            Some(types) => types.split(',').any(|t| t.trim() == event.kind()),
+            None => true,
+        }
+    }
+}
+
+/// Server-sent events of the wallet, the event name is the type of the event
+/// and the data its JSON.
+///
+/// A `lagged` event with the number of dropped events is sent if the client
+/// reads too slowly, it should reload the wallet state then.
+pub async fn events(
+    Query(params): Query<EventsParams>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = current_sync_state().wallet.events.subscribe();
+
+    let stream = futures::stream::unfold((receiver, params), |(mut receiver, params)| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) if params.accepts(&event) => Event::default()
+                    .event(event.kind())
+                    .data(serde_json::to_string(&event).unwrap_or_default()),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("event subscriber lagged, dropped {} events", missed);
+                    Event::default().event("lagged").data(missed.to_string())
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(event), (receiver, params)));
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,49 @@
+use axum::extract::Query;
+use axum_extra::response::ErasedJson;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use super::error::RestError;
+use crate::rpc::WalletRpcImpl;
+use crate::wallet::registry::current_sync_state;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FeeEstimateParams {
+    /// number of inputs the transaction is expected to spend, defaults to 1
+    pub inputs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FeeEstimateResponse {
+    pub low: String,
+    pub medium: String,
+    pub high: String,
+    pub samples: usize,
+}
+
+pub async fn estimate_fee(
+    Query(params): Query<FeeEstimateParams>,
+) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::estimate_fee(params.inputs).await?,
+    ))
+}
+
+pub trait FeeRpc {
+    async fn estimate_fee(inputs: Option<u64>) -> Result<FeeEstimateResponse, RestError> {
+        let sync_state = current_sync_state();
+        let estimate = sync_state.estimate_fee(inputs.unwrap_or(1)).await?;
+
+        Ok(FeeEstimateResponse {
+            low: estimate.low.display_lossless(),
+            medium: estimate.medium.display_lossless(),
+            high: estimate.high.display_lossless(),
+            samples: estimate.samples,
+        })
+    }
+}
+
+impl FeeRpc for WalletRpcImpl {}
@@ -0,0 +1,78 @@
+use axum::extract::Path;
+use axum::Json;
+use axum_extra::response::ErasedJson;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+use utoipa::ToSchema;
+
+use super::error::RestError;
+use super::SendToAddressParams;
+use crate::rpc::WalletRpcImpl;
+use crate::wallet::job::Job;
+use crate::wallet::job::SendRequest;
+use crate::wallet::registry::current_sync_state;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SubmitJobResponse {
+    pub id: i64,
+}
+
+pub async fn submit_send_job(
+    Json(params): Json<SendToAddressParams>,
+) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::submit_send_job(params).await?,
+    ))
+}
+
+pub async fn list_jobs() -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(WalletRpcImpl::list_jobs().await?))
+}
+
+pub async fn get_job(Path(id): Path<i64>) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(WalletRpcImpl::get_job(id).await?))
+}
+
+pub async fn cancel_job(Path(id): Path<i64>) -> Result<ErasedJson, RestError> {
+    WalletRpcImpl::cancel_job(id).await?;
+    Ok(ErasedJson::pretty(true))
+}
+
+pub trait JobRpc {
+    /// Queues the send and returns immediately, poll [JobRpc::get_job] for
+    /// progress.
+    async fn submit_send_job(params: SendToAddressParams) -> Result<SubmitJobResponse, RestError> {
+        let state = current_sync_state();
+        let request = SendRequest {
+            outputs: params.parse_outputs(state.wallet.network)?,
+            fee: params.parse_fee()?,
+            rule: params.input_rule(),
+            proving: params.proving_capability()?,
+            must_include_utxos: params.inputs,
+        };
+
+        let id = state.submit_send_job(request).await?;
+        info!("queued send job {}", id);
+
+        Ok(SubmitJobResponse { id })
+    }
+    async fn list_jobs() -> Result<Vec<Job>, RestError> {
+        let wallet = &current_sync_state().wallet;
+        Ok(wallet.get_jobs().await?)
+    }
+    async fn get_job(id: i64) -> Result<Job, RestError> {
+        let wallet = &current_sync_state().wallet;
+        wallet
+            .get_job(id)
+            .await?
+            .ok_or_else(|| RestError::not_found(format!("job {id} not found")))
+    }
+    async fn cancel_job(id: i64) -> Result<(), RestError> {
+        let wallet = &current_sync_state().wallet;
+        wallet.cancel_job(id).await?;
+        Ok(())
+    }
+}
+
+impl JobRpc for WalletRpcImpl {}
@@ -0,0 +1,392 @@
+//! JSON-RPC 2.0 endpoint at `POST /`, following the conventions of the
+//! neptune-core JSON-RPC server: methods are named `namespace_methodName` and
+//! take positional params.
+//!
+//! Only `node_network` and `chain_height` match methods of neptune-core. The
+//! `wallet_*` methods are extensions of this wallet, neptune-core's `wallet`
+//! namespace serves light wallets and has no balance, address, history, utxo
+//! or send methods:
+//!
+//! - `wallet_balance`
+//! - `wallet_address`, params `[index]`
+//! - `wallet_history`
+//! - `wallet_listUtxos`
+//! - `wallet_previewSend` and `wallet_send`, params as for `POST /send`
+//!
+//! Named params are accepted as well, e.g. `{"index": 1}` for
+//! `wallet_address`. Batches are answered in order, notifications (requests
+//! without an `id`) are executed but not answered.
+
+use axum::extract::rejection::JsonRejection;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::Json;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::*;
+
+use super::block::BlockInfoRpc;
+use super::error::ErrorCode;
+use super::error::RestError;
+use super::SendToAddressParams;
+use super::Utxo;
+use super::WalletRpc;
+use super::WalletRpcImpl;
+use crate::wallet::balance::WalletHistory;
+use crate::wallet::registry::current_sync_state;
+
+pub const JSON_RPC_VERSION: &str = "2.0";
+
+#[derive(Debug, Deserialize)]
+struct JsonRequest {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    id: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonResponse {
+    pub jsonrpc: &'static str,
+    pub id: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonError>,
+}
+
+impl JsonResponse {
+    fn new(id: Option<Value>, result: Result<Value, JsonError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: JSON_RPC_VERSION,
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// Error object of a response, `data` holds the [RestError] of failed calls.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonError {
+    pub code: i32,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonError {
+    pub const PARSE_ERROR: i32 = -32700;
+    pub const INVALID_REQUEST: i32 = -32600;
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    pub const INVALID_PARAMS: i32 = -32602;
+    pub const INTERNAL_ERROR: i32 = -32603;
+    pub const SERVER_ERROR: i32 = -32000;
+
+    fn new(code: i32, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    fn parse_error() -> Self {
+        Self::new(Self::PARSE_ERROR, "Parse error")
+    }
+
+    fn invalid_request(message: impl ToString) -> Self {
+        Self::new(Self::INVALID_REQUEST, message)
+    }
+
+    fn method_not_found(method: &str) -> Self {
+        Self::new(
+            Self::METHOD_NOT_FOUND,
+            format!("Method not found: {method}"),
+        )
+    }
+}
+
+impl From<RestError> for JsonError {
+    fn from(err: RestError) -> Self {
+        let code = match err.code {
+            ErrorCode::InvalidParams | ErrorCode::InvalidAddress | ErrorCode::InvalidAmount => {
+                Self::INVALID_PARAMS
+            }
+            ErrorCode::Internal => Self::INTERNAL_ERROR,
+            _ => Self::SERVER_ERROR,
+        };
+        Self {
+            code,
+            message: err.message.clone(),
+            data: serde_json::to_value(&err).ok(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NoParams {}
+
+#[derive(Debug, Deserialize)]
+struct AddressParams {
+    /// derivation index, defaults to 0
+    #[serde(default)]
+    index: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct NetworkResponse {
+    network: String,
+}
+
+#[derive(Debug, Serialize)]
+struct HeightResponse {
+    height: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct AddressResponse {
+    address: String,
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryResponse {
+    history: Vec<WalletHistory>,
+}
+
+#[derive(Debug, Serialize)]
+struct ListUtxosResponse {
+    utxos: Vec<Utxo>,
+}
+
+/// Handles a request or a batch of requests, answers nothing if all of them
+/// are notifications.
+pub async fn handle(body: Result<Json<Value>, JsonRejection>) -> Response {
+    let Ok(Json(body)) = body else {
+        return reply(JsonResponse::new(None, Err(JsonError::parse_error())));
+    };
+
+    match body {
+        Value::Array(requests) if requests.is_empty() => reply(JsonResponse::new(
+            None,
+            Err(JsonError::invalid_request("empty batch")),
+        )),
+        Value::Array(requests) => {
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in requests {
+                if let Some(response) = handle_request(request).await {
+                    responses.push(response_value(response));
+                }
+            }
+            if responses.is_empty() {
+                return StatusCode::NO_CONTENT.into_response();
+            }
+            Json(Value::Array(responses)).into_response()
+        }
+        request => match handle_request(request).await {
+            Some(response) => reply(response),
+            None => StatusCode::NO_CONTENT.into_response(),
+        },
+    }
+}
+
+fn reply(response: JsonResponse) -> Response {
+    Json(response_value(response)).into_response()
+}
+
+fn response_value(response: JsonResponse) -> Value {
+    serde_json::to_value(response).unwrap_or(Value::Null)
+}
+
+/// Runs a single request, returns `None` for notifications.
+async fn handle_request(request: Value) -> Option<JsonResponse> {
+    let notification = request.is_object() && request.get("id").is_none();
+    let request: JsonRequest = match serde_json::from_value(request) {
+        Ok(request) => request,
+        Err(e) => return Some(JsonResponse::new(None, Err(JsonError::invalid_request(e)))),
+    };
+    if request
+        .jsonrpc
+        .as_deref()
+        .is_some_and(|v| v != JSON_RPC_VERSION)
+    {
+        return Some(JsonResponse::new(
+            request.id,
+            Err(JsonError::invalid_request("unsupported jsonrpc version")),
+        ));
+    }
+
+    debug!("json rpc call {}", request.method);
+    let result = call(&request.method, request.params).await;
+    if notification {
+        if let Err(e) = result {
+            warn!(
+                "json rpc notification {} failed: {}",
+                request.method, e.message
+            );
+        }
+        return None;
+    }
+    Some(JsonResponse::new(request.id, result))
+}
+
+async fn call(method: &str, params: Value) -> Result<Value, JsonError> {
+    match method {
+        "node_network" => {
+            parse_params::<NoParams>(params)?;
+            let network = current_sync_state().wallet.network;
+            to_result(NetworkResponse {
+                network: network.to_string(),
+            })
+        }
+        "chain_height" => {
+            parse_params::<NoParams>(params)?;
+            let height = WalletRpcImpl::get_tip_height().await?;
+            to_result(HeightResponse { height })
+        }
+        "wallet_balance" => {
+            parse_params::<NoParams>(params)?;
+            to_result(WalletRpcImpl::wallet_balance().await?)
+        }
+        "wallet_address" => {
+            let params = parse_params::<AddressParams>(params)?;
+            let address = WalletRpcImpl::current_wallet_address(params.index).await?;
+            to_result(AddressResponse { address })
+        }
+        "wallet_history" => {
+            parse_params::<NoParams>(params)?;
+            let history = WalletRpcImpl::history().await?;
+            to_result(HistoryResponse { history })
+        }
+        "wallet_listUtxos" => {
+            parse_params::<NoParams>(params)?;
+            let utxos = WalletRpcImpl::avaliable_utxos().await?;
+            to_result(ListUtxosResponse { utxos })
+        }
+        "wallet_previewSend" => {
+            let params = parse_params::<SendToAddressParams>(params)?;
+            to_result(WalletRpcImpl::preview_send(params).await?)
+        }
+        "wallet_send" => {
+            let params = parse_params::<SendToAddressParams>(params)?;
+            if params.preview {
+                return Err(RestError::invalid_params("use wallet_previewSend to preview").into());
+            }
+            to_result(WalletRpcImpl::send_to_address(params).await?)
+        }
+        method => Err(JsonError::method_not_found(method)),
+    }
+}
+
+/// Reads positional or named params, missing params are an empty list.
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, JsonError> {
+    let params = match params {
+        Value::Null => Value::Array(vec![]),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(|e| RestError::invalid_params(e).into())
+}
+
+fn to_result<T: Serialize>(result: T) -> Result<Value, JsonError> {
+    serde_json::to_value(result).map_err(|e| RestError::new(ErrorCode::Internal, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn accepts_positional_and_named_params() {
+        let positional: AddressParams = parse_params(json!([3])).unwrap();
+        let named: AddressParams = parse_params(json!({ "index": 3 })).unwrap();
+        let missing: AddressParams = parse_params(Value::Null).unwrap();
+        assert_eq!(positional.index, 3);
+        assert_eq!(named.index, 3);
+        assert_eq!(missing.index, 0);
+
+        let send: SendToAddressParams = parse_params(json!([
+            [{ "address": "nolgam1", "amount": "1" }],
+            "0.1"
+        ]))
+        .unwrap();
+        assert_eq!(send.fee, "0.1");
+        assert!(!send.preview);
+
+        let err = parse_params::<NoParams>(json!([1])).unwrap_err();
+        assert_eq!(err.code, JsonError::INVALID_PARAMS);
+    }
+
+    async fn body(response: Response) -> Option<Value> {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (!bytes.is_empty()).then(|| serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn answers_batches_and_errors() {
+        let response = handle(Ok(Json(json!([
+            { "jsonrpc": "2.0", "method": "wallet_mine", "id": 1 },
+            { "jsonrpc": "1.0", "method": "chain_height", "id": 2 },
+            { "id": 3 },
+            { "jsonrpc": "2.0", "method": "wallet_mine" }
+        ]))))
+        .await;
+
+        assert_eq!(
+            body(response).await.unwrap(),
+            json!([
+                {
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": { "code": -32601, "message": "Method not found: wallet_mine" }
+                },
+                {
+                    "jsonrpc": "2.0",
+                    "id": 2,
+                    "error": { "code": -32600, "message": "unsupported jsonrpc version" }
+                },
+                {
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": { "code": -32600, "message": "missing field `method`" }
+                }
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn notifications_are_not_answered() {
+        let single = handle(Ok(Json(
+            json!({ "jsonrpc": "2.0", "method": "wallet_mine" }),
+        )))
+        .await;
+        assert_eq!(single.status(), StatusCode::NO_CONTENT);
+        assert_eq!(body(single).await, None);
+
+        let batch = handle(Ok(Json(json!([
+            { "jsonrpc": "2.0", "method": "wallet_mine" },
+            { "jsonrpc": "2.0", "method": "wallet_mine", "params": [] }
+        ]))))
+        .await;
+        assert_eq!(batch.status(), StatusCode::NO_CONTENT);
+
+        let null_id = handle(Ok(Json(
+            json!({ "jsonrpc": "2.0", "method": "wallet_mine", "id": null }),
+        )))
+        .await;
+        assert_eq!(body(null_id).await.unwrap()["id"], Value::Null);
+    }
+}
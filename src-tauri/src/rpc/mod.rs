@@ -1,6 +1,7 @@
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
 use std::net::SocketAddrV4;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::anyhow;
@@ -13,20 +14,38 @@ use axum::http;
 use axum::middleware::Next;
 use axum::middleware::{self};
 use axum::response::Response;
+use axum::routing::delete;
 use axum::routing::get;
 use axum::routing::post;
 use axum::Json;
 use axum_extra::response::ErasedJson;
 use block::get_tip_height;
+use consolidate::consolidate;
+use error::ErrorCode;
 use error::RestError;
+use events::events;
+use fee::estimate_fee;
 use http::StatusCode;
+use jobs::cancel_job;
+use jobs::get_job;
+use jobs::list_jobs;
+use jobs::submit_send_job;
 use neptune_cash::api::export::NativeCurrencyAmount;
+use neptune_cash::api::export::Network;
 use neptune_cash::api::export::ReceivingAddress;
 use neptune_cash::api::export::Timestamp;
+use neptune_cash::api::export::TxProvingCapability;
 use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMedium;
+use offline::broadcast_signed_transaction;
+use offline::create_unsigned_transaction;
+use offline::sign_transaction;
 use once_cell::sync::Lazy;
+use rescan::rescan;
+use scan_config::get_scan_config;
+use scan_config::update_scan_config;
 use serde::Deserialize;
 use serde::Serialize;
+use sweep::sweep;
 use tokio::net::TcpListener;
 use tokio::sync::oneshot::Sender;
 use tokio::sync::Mutex;
@@ -34,24 +53,42 @@ use tokio::task::JoinHandle;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 use tracing::*;
+use transaction_status::bump_fee;
 use transaction_status::forget_tx;
 use transaction_status::get_pending_transaction;
+use upgrader::list_upgrades;
+use upgrader::submit_upgrade;
+use utoipa::ToSchema;
+use wallets::list_wallets;
 
 use crate::config::consts::RPC_PORT;
 use crate::config::Config;
-use crate::service::get_state;
 use crate::wallet::balance::WalletHistory;
-use crate::wallet::sync::SyncState;
+use crate::wallet::fee::subtract_fee_from_amounts;
+use crate::wallet::registry::current_sync_state;
 use crate::wallet::sync::SyncStatus;
 use crate::wallet::InputSelectionRule;
+use crate::wallet::SendPreview;
 // mod middleware;
 mod block;
 #[cfg(feature = "cli")]
 pub mod client;
 pub mod commands;
-mod error;
+pub mod consolidate;
+pub mod error;
+mod events;
+mod fee;
+pub mod jobs;
+pub mod json_rpc;
+mod offline;
+pub mod rescan;
+pub mod scan_config;
+pub mod sweep;
 pub mod tls;
-mod transaction_status;
+pub mod transaction_status;
+pub mod upgrader;
+pub mod v1;
+mod wallets;
 
 static RPC_CLOSER: Lazy<Mutex<Option<RpcHandler>>> = Lazy::new(|| Mutex::new(None));
 
@@ -89,7 +126,7 @@ impl RpcHandler {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct WalletBalance {
     pub available_balance: String,
     pub total_balance: String,
@@ -101,11 +138,11 @@ impl WalletRpc for WalletRpcImpl {}
 //TODO: move to crate::command
 pub trait WalletRpc {
     async fn sync_state() -> SyncStatus {
-        crate::service::get_state::<Arc<SyncState>>().status().await
+        current_sync_state().status().await
     }
 
     async fn wallet_balance() -> Result<WalletBalance, RestError> {
-        let wallet = &get_state::<Arc<SyncState>>().wallet;
+        let wallet = &current_sync_state().wallet;
         let (available_balance, total_balance) = wallet.get_all_balance().await?;
         Ok(WalletBalance {
             available_balance: available_balance.display_lossless(),
@@ -113,17 +150,17 @@ pub trait WalletRpc {
         })
     }
     async fn current_wallet_address(index: u64) -> Result<String, RestError> {
-        let wallet = &get_state::<Arc<SyncState>>().wallet;
+        let wallet = &current_sync_state().wallet;
         let address = wallet.get_address(index).await?;
         Ok(address)
     }
     async fn history() -> Result<Vec<WalletHistory>, RestError> {
-        let wallet = &get_state::<Arc<SyncState>>().wallet;
+        let wallet = &current_sync_state().wallet;
         let history = wallet.get_balance_history().await?;
         Ok(history)
     }
     async fn avaliable_utxos() -> Result<Vec<Utxo>, RestError> {
-        let wallet = &get_state::<Arc<SyncState>>().wallet;
+        let wallet = &current_sync_state().wallet;
         let mut utxos = wallet.get_unspent_utxos().await?;
         utxos.sort_by_key(|v| v.recovery_data.utxo.get_native_currency_amount());
         let now = Timestamp::now();
@@ -148,33 +185,45 @@ pub trait WalletRpc {
             .collect::<Vec<_>>();
         Ok(utxos)
     }
-    async fn send_to_address(params: SendToAddressParams) -> Result<SendResponse, RestError> {
-        let mut outputs = Vec::with_capacity(params.outputs.len());
-
-        let wallet = &get_state::<Arc<SyncState>>().wallet;
-        for output in params.outputs {
-            let address = ReceivingAddress::from_bech32m(&output.address, wallet.network)?;
-            let amount = NativeCurrencyAmount::coins_from_str(&output.amount)?;
-            outputs.push((address, amount));
-        }
+    async fn preview_send(params: SendToAddressParams) -> Result<SendPreview, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let outputs = params.parse_outputs(wallet.network)?;
 
         let utxo_notification_media = (
             UtxoNotificationMedium::OnChain,
             UtxoNotificationMedium::OnChain,
         );
 
-        let fee = NativeCurrencyAmount::coins_from_str(&params.fee)?;
+        let fee = params.parse_fee()?;
+        let rule = params.input_rule();
 
-        let rule = if let Some(input_rule) = params.input_rule {
-            InputSelectionRule::from_str(&input_rule).unwrap_or_default()
-        } else {
-            InputSelectionRule::default()
-        };
+        Ok(wallet
+            .preview_send_to_address(outputs, utxo_notification_media, fee, rule, params.inputs)
+            .await?)
+    }
+    async fn send_to_address(params: SendToAddressParams) -> Result<SendResponse, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let outputs = params.parse_outputs(wallet.network)?;
+
+        let utxo_notification_media = (
+            UtxoNotificationMedium::OnChain,
+            UtxoNotificationMedium::OnChain,
+        );
+
+        let fee = params.parse_fee()?;
+        let rule = params.input_rule();
+        let proving = params.proving_capability()?;
 
         let tx = wallet
-            .send_to_address(outputs, utxo_notification_media, fee, rule, params.inputs)
-            .await
-            .map_err(|e| anyhow!("{}", e))?;
+            .send_to_address(
+                outputs,
+                utxo_notification_media,
+                fee,
+                rule,
+                params.inputs,
+                proving,
+            )
+            .await?;
 
         info!("proven tx {}", tx.txid());
 
@@ -188,6 +237,11 @@ pub trait WalletRpc {
                 .collect::<Vec<_>>(),
         })
     }
+    /// Stops the send being proven, returns false if there is none.
+    async fn cancel_send() -> bool {
+        let wallet = &current_sync_state().wallet;
+        wallet.cancel_send()
+    }
 }
 
 pub async fn start_rpc_server() -> Result<(), anyhow::Error> {
@@ -199,6 +253,7 @@ pub async fn start_rpc_server() -> Result<(), anyhow::Error> {
         .allow_methods([
             axum::http::Method::GET,
             axum::http::Method::POST,
+            axum::http::Method::DELETE,
             axum::http::Method::OPTIONS,
         ])
         .allow_headers([
@@ -208,25 +263,56 @@ pub async fn start_rpc_server() -> Result<(), anyhow::Error> {
 
     let router = {
         let routes = axum::Router::new()
-            .route("/rpc/scan/{start}/{end}", get(scan_blocks))
+            .route("/", post(json_rpc::handle))
             .route("/rpc/scan/state", get(sync_state))
+            .route("/rpc/wallets", get(list_wallets))
+            .route("/rpc/events", get(events))
             .route("/rpc/wallet/balance", get(wallet_balance))
             .route("/rpc/wallet/address/{index}", get(wallet_address))
             .route("/rpc/wallet/history", get(history))
             .route("/rpc/wallet/available_utxos", get(avaliable_utxos))
             .route("/rpc/mempool/pendingtx", get(get_pending_transaction))
-            .route("/rpc/forget_tx/{id}", get(forget_tx))
+            .route("/rpc/forget_tx/{id}", delete(forget_tx))
             .route("/rpc/send", post(send_to_address))
-            .route("/rpc/block/tip_height", get(get_tip_height));
-
-        routes
+            .route("/rpc/send/preview", post(preview_send))
+            .route("/rpc/send/cancel", post(cancel_send))
+            .route("/rpc/jobs", get(list_jobs))
+            .route("/rpc/jobs/send", post(submit_send_job))
+            .route("/rpc/jobs/{id}", get(get_job))
+            .route("/rpc/jobs/{id}/cancel", post(cancel_job))
+            .route("/rpc/wallet/consolidate", post(consolidate))
+            .route("/rpc/wallet/sweep", post(sweep))
+            .route(
+                "/rpc/wallet/scan_config",
+                get(get_scan_config).post(update_scan_config),
+            )
+            .route("/rpc/wallet/rescan", post(rescan))
+            .route("/rpc/tx/create", post(create_unsigned_transaction))
+            .route("/rpc/tx/sign", post(sign_transaction))
+            .route("/rpc/tx/broadcast", post(broadcast_signed_transaction))
+            .route("/rpc/tx/bump_fee", post(bump_fee))
+            .route("/rpc/upgrader", get(list_upgrades))
+            .route("/rpc/upgrader/submit", post(submit_upgrade))
+            .route("/rpc/block/tip_height", get(get_tip_height))
+            .route("/rpc/fee/estimate", get(estimate_fee))
+            .merge(v1::router());
+
+        let routes = routes
+            .layer(middleware::from_fn(wallets::scope_wallet))
             // Pass in `Rest` to make t
             // Enable tower-http tracing.
             .layer(TraceLayer::new_for_http())
             .layer(middleware::from_fn(auth_middleware))
+            .route("/v1/openapi.json", get(v1::openapi))
             .layer(middleware::from_fn(log_middleware))
             // Enable CORS.
-            .layer(cors)
+            .layer(cors);
+
+        // layers of a router run after routing, wallet scoped uris are
+        // rewritten by an outer router
+        axum::Router::new()
+            .fallback_service(routes)
+            .layer(middleware::map_request(wallets::rewrite_uri))
     };
 
     let listener = TcpListener::bind(address).await?;
@@ -261,7 +347,7 @@ async fn log_middleware(
 ) -> Result<Response, StatusCode> {
     let path = request.uri().path().to_string();
     match path.as_str() {
-        "/rpc/scan/state" | "/rpc/block/tip_height" => {}
+        "/rpc/scan/state" | "/rpc/block/tip_height" | "/v1/sync" | "/v1/chain/tip" => {}
         _ => {
             info!(
                 "Received '{} {}' from '{addr}'",
@@ -272,11 +358,19 @@ async fn log_middleware(
     }
 
     let response = next.run(request).await;
+    // event streams never end, their body cannot be buffered
+    if response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"))
+    {
+        return Ok(response);
+    }
     let (res_parts, res_body) = response.into_parts();
 
     let body_bytes = axum::body::to_bytes(res_body, usize::MAX).await.unwrap();
 
-    if res_parts.status != StatusCode::OK {
+    if !res_parts.status.is_success() {
         error!(
             "Response error: '{}' {}",
             path,
@@ -288,12 +382,9 @@ async fn log_middleware(
     Ok(res)
 }
 
-async fn auth_middleware(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
+async fn auth_middleware(req: Request<Body>, next: Next) -> Result<Response, RestError> {
     let config = crate::service::get_state::<Arc<Config>>();
-    let secret = config
-        .get_secret_key()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let secret = config.get_secret_key().await?;
 
     let token = tls::get_p256_pubkey(&secret);
     let token = hex::encode(token);
@@ -305,20 +396,19 @@ async fn auth_middleware(req: Request<Body>, next: Next) -> Result<Response, Sta
     let auth_header = if let Some(auth_header) = auth_header {
         auth_header
     } else {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(RestError::new(
+            ErrorCode::Unauthorized,
+            "missing authorization header",
+        ));
     };
 
     if auth_header != format!("Bearer {}", token) {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(RestError::new(ErrorCode::Unauthorized, "invalid token"));
     }
 
     Ok(next.run(req).await)
 }
 
-async fn scan_blocks(Path((_start, end)): Path<(u64, u64)>) -> Result<ErasedJson, RestError> {
-    Ok(ErasedJson::pretty(end))
-}
-
 async fn sync_state() -> Result<ErasedJson, RestError> {
     Ok(ErasedJson::pretty(WalletRpcImpl::sync_state().await))
 }
@@ -337,37 +427,158 @@ async fn history() -> Result<ErasedJson, RestError> {
     Ok(ErasedJson::pretty(WalletRpcImpl::history().await?))
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct SendToAddressParams {
     pub outputs: Vec<Output>,
     pub fee: String,
+    /// one of `minimum`, `maximum`, `oldest`, `newest`, `random`,
+    /// `branch_and_bound`, `min_inputs` or `privacy`
+    #[serde(default)]
     pub input_rule: Option<String>,
     #[serde(default)]
     pub inputs: Vec<i64>,
+    /// only select inputs and compute change, see [WalletRpc::preview_send]
+    #[serde(default)]
+    pub preview: bool,
+    /// `proofcollection` or `singleproof`, defaults to the configured
+    /// capability
+    #[serde(default)]
+    pub proving: Option<String>,
+}
+
+impl SendToAddressParams {
+    pub fn parse_outputs(
+        &self,
+        network: Network,
+    ) -> Result<Vec<(ReceivingAddress, NativeCurrencyAmount)>> {
+        let mut addresses = Vec::with_capacity(self.outputs.len());
+        let mut amounts = Vec::with_capacity(self.outputs.len());
+        for output in &self.outputs {
+            addresses.push(parse_address(&output.address, network)?);
+            amounts.push(parse_amount(&output.amount)?);
+        }
+
+        if self.outputs.iter().any(|output| output.subtract_fee) {
+            if self
+                .outputs
+                .iter()
+                .any(|output| output.subtract_fee && output.priority_fee)
+            {
+                return Err(RestError::invalid_params(
+                    "the fee cannot be subtracted from a priority fee output",
+                )
+                .into());
+            }
+
+            let priority_fee: NativeCurrencyAmount = self
+                .outputs
+                .iter()
+                .zip(&amounts)
+                .filter(|(output, _)| output.priority_fee)
+                .map(|(_, amount)| *amount)
+                .sum();
+            let subtract = self
+                .outputs
+                .iter()
+                .map(|output| output.subtract_fee)
+                .collect::<Vec<_>>();
+            subtract_fee_from_amounts(&mut amounts, &subtract, self.parse_fee()? + priority_fee)
+                .map_err(|e| RestError::new(ErrorCode::InvalidAmount, e))?;
+        }
+
+        Ok(addresses.into_iter().zip(amounts).collect())
+    }
+
+    pub fn parse_fee(&self) -> Result<NativeCurrencyAmount> {
+        Ok(parse_amount(&self.fee)?)
+    }
+
+    pub fn proving_capability(&self) -> Result<Option<TxProvingCapability>, RestError> {
+        self.proving
+            .as_deref()
+            .map(|proving| match proving.to_lowercase().as_str() {
+                "proofcollection" => Ok(TxProvingCapability::ProofCollection),
+                "singleproof" => Ok(TxProvingCapability::SingleProof),
+                _ => Err(RestError::invalid_params(format!(
+                    "proving must be proofcollection or singleproof, not {proving}"
+                ))),
+            })
+            .transpose()
+    }
+
+    pub fn input_rule(&self) -> InputSelectionRule {
+        if let Some(input_rule) = &self.input_rule {
+            InputSelectionRule::from_str(input_rule).unwrap_or_default()
+        } else {
+            InputSelectionRule::default()
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+pub fn parse_address(address: &str, network: Network) -> Result<ReceivingAddress, RestError> {
+    ReceivingAddress::from_bech32m(address, network).map_err(|e| {
+        RestError::new(
+            ErrorCode::InvalidAddress,
+            format!("invalid address {address}: {e}"),
+        )
+    })
+}
+
+pub fn parse_amount(amount: &str) -> Result<NativeCurrencyAmount, RestError> {
+    NativeCurrencyAmount::coins_from_str(amount).map_err(|e| {
+        RestError::new(
+            ErrorCode::InvalidAmount,
+            format!("invalid amount {amount}: {e}"),
+        )
+    })
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Output {
     pub address: String,
     pub amount: String,
+    /// deduct the fee, and any priority fee, from this output, split with the
+    /// other flagged outputs in proportion to their amounts
+    #[serde(default)]
+    pub subtract_fee: bool,
+    /// marks the output paying the priority fee
+    #[serde(default)]
+    pub priority_fee: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SendResponse {
     txid: String,
     outputs: Vec<String>,
 }
 
 async fn send_to_address(Json(params): Json<SendToAddressParams>) -> Result<ErasedJson, RestError> {
+    if params.preview {
+        return Ok(ErasedJson::pretty(
+            WalletRpcImpl::preview_send(params).await?,
+        ));
+    }
     Ok(ErasedJson::pretty(
         WalletRpcImpl::send_to_address(params).await?,
     ))
 }
 
-#[derive(Serialize)]
+async fn cancel_send() -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(WalletRpcImpl::cancel_send().await))
+}
+
+async fn preview_send(Json(params): Json<SendToAddressParams>) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::preview_send(params).await?,
+    ))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct Utxo {
     pub id: i64,
     pub hash: String,
+    /// milliseconds since the unix epoch
+    #[schema(value_type = u64)]
     pub confirm_timestamp: Timestamp,
     // this two values are used to rollback
     pub confirm_height: i64,
@@ -0,0 +1,109 @@
+use axum::Json;
+use axum_extra::response::ErasedJson;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::error::RestError;
+use super::SendToAddressParams;
+use crate::rpc::WalletRpcImpl;
+use crate::wallet::offline::SignedTransaction;
+use crate::wallet::offline::UnsignedTransaction;
+use crate::wallet::registry::current_sync_state;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UnsignedTransactionResponse {
+    pub unsigned_tx: String,
+    pub inputs: Vec<i64>,
+    pub input_amount: String,
+    pub fee: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SignTransactionParams {
+    pub unsigned_tx: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SignedTransactionResponse {
+    pub signed_tx: String,
+    pub txid: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BroadcastTransactionParams {
+    pub signed_tx: String,
+}
+
+pub async fn create_unsigned_transaction(
+    Json(params): Json<SendToAddressParams>,
+) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::create_unsigned_transaction(params).await?,
+    ))
+}
+
+pub async fn sign_transaction(
+    Json(params): Json<SignTransactionParams>,
+) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::sign_transaction(params).await?,
+    ))
+}
+
+pub async fn broadcast_signed_transaction(
+    Json(params): Json<BroadcastTransactionParams>,
+) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::broadcast_signed_transaction(params).await?,
+    ))
+}
+
+pub trait OfflineTransactionRpc {
+    async fn create_unsigned_transaction(
+        params: SendToAddressParams,
+    ) -> Result<UnsignedTransactionResponse, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let outputs = params.parse_outputs(wallet.network)?;
+        let fee = params.parse_fee()?;
+        let rule = params.input_rule();
+
+        let unsigned = wallet
+            .create_unsigned_transaction(outputs, fee, rule, params.inputs)
+            .await?;
+
+        Ok(UnsignedTransactionResponse {
+            unsigned_tx: unsigned.encode()?,
+            inputs: unsigned.inputs.iter().map(|input| input.id).collect(),
+            input_amount: unsigned.input_amount().display_lossless(),
+            fee: unsigned.fee.display_lossless(),
+        })
+    }
+
+    async fn sign_transaction(
+        params: SignTransactionParams,
+    ) -> Result<SignedTransactionResponse, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let unsigned = UnsignedTransaction::decode(&params.unsigned_tx)
+            .map_err(|e| RestError::invalid_params(format!("{e:#}")))?;
+
+        let signed = wallet.sign_unsigned_transaction(unsigned).await?;
+
+        Ok(SignedTransactionResponse {
+            signed_tx: signed.encode()?,
+            txid: signed.transaction.txid().to_string(),
+        })
+    }
+
+    async fn broadcast_signed_transaction(
+        params: BroadcastTransactionParams,
+    ) -> Result<String, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let signed = SignedTransaction::decode(&params.signed_tx)
+            .map_err(|e| RestError::invalid_params(format!("{e:#}")))?;
+
+        Ok(wallet.broadcast_signed_transaction(signed).await?)
+    }
+}
+
+impl OfflineTransactionRpc for WalletRpcImpl {}
@@ -0,0 +1,39 @@
+use std::range::Range;
+
+use axum::Json;
+use axum_extra::response::ErasedJson;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::error::RestError;
+use crate::rpc::WalletRpcImpl;
+use crate::wallet::registry::current_sync_state;
+use crate::wallet::rescan::KeyFilter;
+use crate::wallet::rescan::RescanResult;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RescanParams {
+    pub start_height: u64,
+    /// height after the last block to scan, the wallet tip if unset
+    pub end_height: Option<u64>,
+    #[serde(flatten)]
+    pub keys: KeyFilter,
+}
+
+pub async fn rescan(Json(params): Json<RescanParams>) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(WalletRpcImpl::rescan(params).await?))
+}
+
+pub trait RescanRpc {
+    /// Responds once the blocks are rescanned.
+    async fn rescan(params: RescanParams) -> Result<RescanResult, RestError> {
+        let range = Range {
+            start: params.start_height,
+            end: params.end_height.unwrap_or(u64::MAX),
+        };
+        Ok(current_sync_state().rescan(range, &params.keys).await?)
+    }
+}
+
+impl RescanRpc for WalletRpcImpl {}
@@ -0,0 +1,33 @@
+use axum::Json;
+use axum_extra::response::ErasedJson;
+
+use super::error::RestError;
+use crate::config::wallet::ScanConfig;
+use crate::rpc::WalletRpcImpl;
+use crate::wallet::registry::current_sync_state;
+use crate::wallet::rescan::RescanResult;
+
+pub async fn get_scan_config() -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(WalletRpcImpl::scan_config().await?))
+}
+
+pub async fn update_scan_config(
+    Json(scan_config): Json<ScanConfig>,
+) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::update_scan_config(scan_config).await?,
+    ))
+}
+
+pub trait ScanConfigRpc {
+    async fn scan_config() -> Result<ScanConfig, RestError> {
+        Ok(current_sync_state().wallet.scan_config())
+    }
+
+    /// Responds once the blocks covered by the new config are rescanned.
+    async fn update_scan_config(scan_config: ScanConfig) -> Result<RescanResult, RestError> {
+        Ok(current_sync_state().update_scan_config(scan_config).await?)
+    }
+}
+
+impl ScanConfigRpc for WalletRpcImpl {}
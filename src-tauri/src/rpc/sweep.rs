@@ -0,0 +1,53 @@
+use axum::Json;
+use axum_extra::response::ErasedJson;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+use utoipa::ToSchema;
+
+use super::error::RestError;
+use super::parse_address;
+use super::parse_amount;
+use crate::rpc::WalletRpcImpl;
+use crate::wallet::registry::current_sync_state;
+use crate::wallet::sweep::SweepFilter;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SweepParams {
+    pub address: String,
+    pub fee: String,
+    #[serde(flatten)]
+    pub filter: SweepFilter,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SweepResponse {
+    pub txid: String,
+    pub inputs: usize,
+    /// amount received by the destination, after the fee
+    pub amount: String,
+}
+
+pub async fn sweep(Json(params): Json<SweepParams>) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(WalletRpcImpl::sweep(params).await?))
+}
+
+pub trait SweepRpc {
+    async fn sweep(params: SweepParams) -> Result<SweepResponse, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let address = parse_address(&params.address, wallet.network)?;
+        let fee = parse_amount(&params.fee)?;
+
+        let (tx, amount) = wallet.sweep(address, fee, &params.filter).await?;
+
+        info!("proven sweep tx {}", tx.txid());
+
+        Ok(SweepResponse {
+            txid: tx.txid().to_string(),
+            inputs: tx.kernel.inputs.len(),
+            amount: amount.display_lossless(),
+        })
+    }
+}
+
+impl SweepRpc for WalletRpcImpl {}
@@ -1,24 +1,30 @@
-use std::sync::Arc;
-
-use crate::{rpc::WalletRpcImpl, service::get_state, wallet::sync::SyncState};
+use crate::{rpc::WalletRpcImpl, wallet::registry::current_sync_state};
 
 use super::error::RestError;
+use super::parse_amount;
 use axum::extract::Path;
+use axum::Json;
 use axum_extra::response::ErasedJson;
+use serde::Deserialize;
 use serde::Serialize;
+use tracing::*;
+use utoipa::ToSchema;
+
+pub use crate::wallet::tx_status::TransactionStatus;
 
-#[derive(Debug, Serialize)]
-pub struct TransactionStatus {
-    tx_id: String,
-    status: TransactionStatusEnum,
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BumpFeeParams {
+    pub txid: String,
+    /// total fee of the replacing transaction
+    pub fee: String,
 }
 
-#[derive(Debug, Serialize)]
-pub enum TransactionStatusEnum {
-    Pending,
-    // Proving,
-    // Composing,
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BumpFeeResponse {
+    pub txid: String,
+    pub replaced: String,
 }
+
 pub async fn get_pending_transaction() -> Result<ErasedJson, RestError> {
     Ok(ErasedJson::pretty(
         WalletRpcImpl::pending_transactions().await?,
@@ -30,25 +36,33 @@ pub async fn forget_tx(Path(id): Path<String>) -> Result<ErasedJson, RestError>
     Ok(ErasedJson::pretty(true))
 }
 
+pub async fn bump_fee(Json(params): Json<BumpFeeParams>) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(WalletRpcImpl::bump_fee(params).await?))
+}
+
 pub trait TransactionStatusRpc {
     async fn pending_transactions() -> Result<Vec<TransactionStatus>, RestError> {
-        let wallet = &get_state::<Arc<SyncState>>().wallet;
-        let txs = wallet.get_pending_transactions().await?;
-        let mut result = vec![];
-        for tx in txs {
-            let status = TransactionStatus {
-                tx_id: tx,
-                status: TransactionStatusEnum::Pending,
-            };
-            result.push(status);
-        }
-        Ok(result)
+        let wallet = &current_sync_state().wallet;
+        Ok(wallet.get_transaction_statuses().await?)
     }
     async fn forget_tx(txid: String) -> Result<(), RestError> {
-        let wallet = &get_state::<Arc<SyncState>>().wallet;
+        let wallet = &current_sync_state().wallet;
         wallet.forget_tx(&txid).await?;
         Ok(())
     }
+    async fn bump_fee(params: BumpFeeParams) -> Result<BumpFeeResponse, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let fee = parse_amount(&params.fee)?;
+
+        let tx = wallet.bump_fee(&params.txid, fee).await?;
+
+        info!("replaced tx {} with {}", params.txid, tx.txid());
+
+        Ok(BumpFeeResponse {
+            txid: tx.txid().to_string(),
+            replaced: params.txid,
+        })
+    }
 }
 
 impl TransactionStatusRpc for WalletRpcImpl {}
@@ -0,0 +1,60 @@
+use axum::Json;
+use axum_extra::response::ErasedJson;
+use neptune_cash::protocol::consensus::transaction::Transaction;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+use utoipa::ToSchema;
+
+use super::error::RestError;
+use crate::rpc::WalletRpcImpl;
+use crate::wallet::registry::current_sync_state;
+use crate::wallet::upgrader::UpgradeItem;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SubmitUpgradeParams {
+    /// hex encoded transaction with a proof collection or single proof
+    pub transaction: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SubmitUpgradeResponse {
+    pub txid: String,
+}
+
+pub async fn submit_upgrade(
+    Json(params): Json<SubmitUpgradeParams>,
+) -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(
+        WalletRpcImpl::submit_upgrade(params).await?,
+    ))
+}
+
+pub async fn list_upgrades() -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(WalletRpcImpl::list_upgrades().await?))
+}
+
+pub trait UpgraderRpc {
+    /// Queues a transaction to be upgraded to a single proof and kept synced
+    /// to the tip until it is mined.
+    async fn submit_upgrade(
+        params: SubmitUpgradeParams,
+    ) -> Result<SubmitUpgradeResponse, RestError> {
+        let wallet = &current_sync_state().wallet;
+        let bytes = hex::decode(params.transaction.trim())
+            .map_err(|_| RestError::invalid_params("transaction is not valid hex"))?;
+        let transaction: Transaction = bincode::deserialize(&bytes)
+            .map_err(|e| RestError::invalid_params(format!("invalid transaction: {e}")))?;
+
+        let txid = wallet.submit_upgrade(transaction).await?;
+        info!("queued upgrade of {}", txid);
+
+        Ok(SubmitUpgradeResponse { txid })
+    }
+    async fn list_upgrades() -> Result<Vec<UpgradeItem>, RestError> {
+        let wallet = &current_sync_state().wallet;
+        Ok(wallet.get_upgrades())
+    }
+}
+
+impl UpgraderRpc for WalletRpcImpl {}
@@ -0,0 +1,580 @@
+//! Versioned REST API, described by the OpenAPI document served at
+//! `/v1/openapi.json`.
+//!
+//! Responses are wrapped as `{"data": ...}`, lists add a `page` object with
+//! the offset, limit and total, errors are `{"error": {"code", "message"}}`
+//! with the status of the [ErrorCode](super::error::ErrorCode).
+//!
+//! Routes act on the current wallet, every synced wallet is served the same
+//! routes under `/v1/wallets/{id}/`, e.g. `/v1/wallets/2/balance`.
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::rejection::PathRejection;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::delete;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use axum_extra::extract::WithRejection;
+use axum_extra::response::ErasedJson;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::openapi::security::Http;
+use utoipa::openapi::security::HttpAuthScheme;
+use utoipa::openapi::security::SecurityScheme;
+use utoipa::IntoParams;
+use utoipa::IntoResponses;
+use utoipa::Modify;
+use utoipa::OpenApi;
+use utoipa::ToSchema;
+
+use super::block::BlockInfoRpc;
+use super::consolidate::ConsolidateParams;
+use super::consolidate::ConsolidateResponse;
+use super::consolidate::ConsolidateRpc;
+use super::error::RestError;
+use super::fee::FeeEstimateParams;
+use super::fee::FeeEstimateResponse;
+use super::fee::FeeRpc;
+use super::jobs::JobRpc;
+use super::jobs::SubmitJobResponse;
+use super::offline::BroadcastTransactionParams;
+use super::offline::OfflineTransactionRpc;
+use super::offline::SignTransactionParams;
+use super::offline::SignedTransactionResponse;
+use super::offline::UnsignedTransactionResponse;
+use super::rescan::RescanParams;
+use super::rescan::RescanRpc;
+use super::scan_config::ScanConfigRpc;
+use super::sweep::SweepParams;
+use super::sweep::SweepResponse;
+use super::sweep::SweepRpc;
+use super::transaction_status::BumpFeeParams;
+use super::transaction_status::BumpFeeResponse;
+use super::transaction_status::TransactionStatus;
+use super::transaction_status::TransactionStatusRpc;
+use super::upgrader::SubmitUpgradeParams;
+use super::upgrader::SubmitUpgradeResponse;
+use super::upgrader::UpgraderRpc;
+use super::wallets::synced_wallets;
+use super::wallets::SyncedWallet;
+use super::SendResponse;
+use super::SendToAddressParams;
+use super::Utxo;
+use super::WalletBalance;
+use super::WalletRpc;
+use super::WalletRpcImpl;
+use crate::config::wallet::ScanConfig;
+use crate::wallet::balance::WalletHistory;
+use crate::wallet::job::Job;
+use crate::wallet::rescan::RescanResult;
+use crate::wallet::sync::SyncStatus;
+use crate::wallet::upgrader::UpgradeItem;
+use crate::wallet::SendPreview;
+
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+pub const MAX_PAGE_LIMIT: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Envelope<T> {
+    pub data: T,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub page: PageInfo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PageInfo {
+    pub offset: usize,
+    pub limit: usize,
+    /// number of items in the whole list
+    pub total: usize,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    /// items to skip
+    #[serde(default)]
+    pub offset: usize,
+    /// items to return, defaults to 50, at most 500
+    pub limit: Option<usize>,
+}
+
+impl PageParams {
+    pub fn paginate<T>(&self, items: Vec<T>) -> Page<T> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+        let total = items.len();
+        let data = items.into_iter().skip(self.offset).take(limit).collect();
+
+        Page {
+            data,
+            page: PageInfo {
+                offset: self.offset,
+                limit,
+                total,
+            },
+        }
+    }
+}
+
+/// Error response of every endpoint, with the status of its code.
+#[derive(Debug, Serialize, IntoResponses)]
+#[response(status = "default")]
+pub struct ApiError {
+    pub error: RestError,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.error.code.status(), Json(self)).into_response()
+    }
+}
+
+impl From<RestError> for ApiError {
+    fn from(error: RestError) -> Self {
+        Self { error }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        RestError::invalid_params(rejection.body_text()).into()
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        RestError::invalid_params(rejection.body_text()).into()
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        RestError::invalid_params(rejection.body_text()).into()
+    }
+}
+
+type ApiResult<T = ErasedJson> = Result<T, ApiError>;
+
+fn data<T: Serialize>(data: T) -> ErasedJson {
+    ErasedJson::pretty(Envelope { data })
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AddressResponse {
+    pub index: u64,
+    pub address: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TipResponse {
+    pub height: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CancelSendResponse {
+    /// false if no send was being proven
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BumpFeeRequest {
+    /// total fee of the replacing transaction
+    pub fee: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BroadcastResponse {
+    pub txid: String,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Neptune Wallet API"),
+    paths(
+        sync_status,
+        wallets,
+        tip_height,
+        estimate_fee,
+        balance,
+        address,
+        history,
+        utxos,
+        consolidate,
+        sweep,
+        scan_config,
+        update_scan_config,
+        rescan,
+        list_transactions,
+        send,
+        preview_send,
+        cancel_send,
+        forget_transaction,
+        bump_fee,
+        list_jobs,
+        submit_job,
+        get_job,
+        cancel_job,
+        create_unsigned_transaction,
+        sign_transaction,
+        broadcast_transaction,
+        list_upgrades,
+        submit_upgrade,
+    ),
+    components(schemas(RestError)),
+    modifiers(&BearerAuth),
+    security(("bearer" = [])),
+    tags(
+        (name = "chain"),
+        (name = "wallet"),
+        (name = "transactions"),
+        (name = "jobs", description = "sends proven in the background"),
+        (name = "offline", description = "signing on an offline wallet"),
+        (name = "upgrades", description = "upgrading proof collections to single proofs"),
+    )
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        let mut http = Http::new(HttpAuthScheme::Bearer);
+        http.description = Some("hex encoded public key of the wallet rpc secret".to_string());
+        components.add_security_scheme("bearer", SecurityScheme::Http(http));
+    }
+}
+
+/// Routes of the API, the OpenAPI document is served by [openapi] so that it
+/// can be fetched without a token.
+pub fn router() -> Router {
+    Router::new()
+        .route("/v1/sync", get(sync_status))
+        .route("/v1/wallets", get(wallets))
+        .route("/v1/chain/tip", get(tip_height))
+        .route("/v1/fees/estimate", get(estimate_fee))
+        .route("/v1/wallet/balance", get(balance))
+        .route("/v1/wallet/addresses/{index}", get(address))
+        .route("/v1/wallet/history", get(history))
+        .route("/v1/wallet/utxos", get(utxos))
+        .route("/v1/wallet/consolidate", post(consolidate))
+        .route("/v1/wallet/sweep", post(sweep))
+        .route(
+            "/v1/wallet/scan-config",
+            get(scan_config).put(update_scan_config),
+        )
+        .route("/v1/wallet/rescan", post(rescan))
+        .route("/v1/transactions", get(list_transactions).post(send))
+        .route("/v1/transactions/preview", post(preview_send))
+        .route("/v1/transactions/cancel", post(cancel_send))
+        .route("/v1/transactions/{txid}", delete(forget_transaction))
+        .route("/v1/transactions/{txid}/bump-fee", post(bump_fee))
+        .route("/v1/jobs", get(list_jobs).post(submit_job))
+        .route("/v1/jobs/{id}", get(get_job))
+        .route("/v1/jobs/{id}/cancel", post(cancel_job))
+        .route("/v1/offline/unsigned", post(create_unsigned_transaction))
+        .route("/v1/offline/sign", post(sign_transaction))
+        .route("/v1/offline/broadcast", post(broadcast_transaction))
+        .route("/v1/upgrades", get(list_upgrades).post(submit_upgrade))
+}
+
+pub async fn openapi() -> ErasedJson {
+    ErasedJson::pretty(ApiDoc::openapi())
+}
+
+#[utoipa::path(get, path = "/v1/sync", tag = "chain",
+    responses((status = 200, body = Envelope<SyncStatus>), ApiError))]
+async fn sync_status() -> ApiResult {
+    Ok(data(WalletRpcImpl::sync_state().await))
+}
+
+#[utoipa::path(get, path = "/v1/wallets", tag = "wallet",
+    responses((status = 200, description = "wallets synced by the daemon", body = Envelope<Vec<SyncedWallet>>), ApiError))]
+async fn wallets() -> ApiResult {
+    Ok(data(synced_wallets().await))
+}
+
+#[utoipa::path(get, path = "/v1/chain/tip", tag = "chain",
+    responses((status = 200, body = Envelope<TipResponse>), ApiError))]
+async fn tip_height() -> ApiResult {
+    let height = WalletRpcImpl::get_tip_height().await?;
+    Ok(data(TipResponse { height }))
+}
+
+#[utoipa::path(get, path = "/v1/fees/estimate", tag = "chain", params(FeeEstimateParams),
+    responses((status = 200, body = Envelope<FeeEstimateResponse>), ApiError))]
+async fn estimate_fee(
+    WithRejection(Query(params), _): WithRejection<Query<FeeEstimateParams>, ApiError>,
+) -> ApiResult {
+    Ok(data(WalletRpcImpl::estimate_fee(params.inputs).await?))
+}
+
+#[utoipa::path(get, path = "/v1/wallet/balance", tag = "wallet",
+    responses((status = 200, body = Envelope<WalletBalance>), ApiError))]
+async fn balance() -> ApiResult {
+    Ok(data(WalletRpcImpl::wallet_balance().await?))
+}
+
+#[utoipa::path(get, path = "/v1/wallet/addresses/{index}", tag = "wallet",
+    params(("index" = u64, Path, description = "derivation index")),
+    responses((status = 200, body = Envelope<AddressResponse>), ApiError))]
+async fn address(WithRejection(Path(index), _): WithRejection<Path<u64>, ApiError>) -> ApiResult {
+    let address = WalletRpcImpl::current_wallet_address(index).await?;
+    Ok(data(AddressResponse { index, address }))
+}
+
+#[utoipa::path(get, path = "/v1/wallet/history", tag = "wallet", params(PageParams),
+    responses((status = 200, body = Page<WalletHistory>), ApiError))]
+async fn history(
+    WithRejection(Query(page), _): WithRejection<Query<PageParams>, ApiError>,
+) -> ApiResult {
+    let history = WalletRpcImpl::history().await?;
+    Ok(ErasedJson::pretty(page.paginate(history)))
+}
+
+#[utoipa::path(get, path = "/v1/wallet/utxos", tag = "wallet", params(PageParams),
+    responses((status = 200, description = "unspent utxos, smallest first", body = Page<Utxo>), ApiError))]
+async fn utxos(
+    WithRejection(Query(page), _): WithRejection<Query<PageParams>, ApiError>,
+) -> ApiResult {
+    let utxos = WalletRpcImpl::avaliable_utxos().await?;
+    Ok(ErasedJson::pretty(page.paginate(utxos)))
+}
+
+#[utoipa::path(post, path = "/v1/wallet/consolidate", tag = "wallet",
+    request_body = ConsolidateParams,
+    responses((status = 200, body = Envelope<ConsolidateResponse>), ApiError))]
+async fn consolidate(
+    WithRejection(Json(params), _): WithRejection<Json<ConsolidateParams>, ApiError>,
+) -> ApiResult {
+    Ok(data(WalletRpcImpl::consolidate(params).await?))
+}
+
+#[utoipa::path(post, path = "/v1/wallet/sweep", tag = "wallet", request_body = SweepParams,
+    responses((status = 200, body = Envelope<SweepResponse>), ApiError))]
+async fn sweep(
+    WithRejection(Json(params), _): WithRejection<Json<SweepParams>, ApiError>,
+) -> ApiResult {
+    Ok(data(WalletRpcImpl::sweep(params).await?))
+}
+
+#[utoipa::path(get, path = "/v1/wallet/scan-config", tag = "wallet",
+    responses((status = 200, body = Envelope<ScanConfig>), ApiError))]
+async fn scan_config() -> ApiResult {
+    Ok(data(WalletRpcImpl::scan_config().await?))
+}
+
+/// Widens the key window or lowers the start height, responds once the blocks
+/// the new config covers are rescanned.
+#[utoipa::path(put, path = "/v1/wallet/scan-config", tag = "wallet", request_body = ScanConfig,
+    responses((status = 200, body = Envelope<RescanResult>), ApiError))]
+async fn update_scan_config(
+    WithRejection(Json(scan_config), _): WithRejection<Json<ScanConfig>, ApiError>,
+) -> ApiResult {
+    Ok(data(WalletRpcImpl::update_scan_config(scan_config).await?))
+}
+
+/// Adds the utxos of the keys that the wallet missed in a range of synced
+/// blocks, responds once the blocks are rescanned.
+#[utoipa::path(post, path = "/v1/wallet/rescan", tag = "wallet", request_body = RescanParams,
+    responses((status = 200, body = Envelope<RescanResult>), ApiError))]
+async fn rescan(
+    WithRejection(Json(params), _): WithRejection<Json<RescanParams>, ApiError>,
+) -> ApiResult {
+    Ok(data(WalletRpcImpl::rescan(params).await?))
+}
+
+#[utoipa::path(get, path = "/v1/transactions", tag = "transactions", params(PageParams),
+    responses((status = 200, body = Page<TransactionStatus>), ApiError))]
+async fn list_transactions(
+    WithRejection(Query(page), _): WithRejection<Query<PageParams>, ApiError>,
+) -> ApiResult {
+    let transactions = WalletRpcImpl::pending_transactions().await?;
+    Ok(ErasedJson::pretty(page.paginate(transactions)))
+}
+
+/// Proves and broadcasts the transaction before responding, see `/v1/jobs` to
+/// prove in the background.
+#[utoipa::path(post, path = "/v1/transactions", tag = "transactions",
+    request_body = SendToAddressParams,
+    responses((status = 200, body = Envelope<SendResponse>), ApiError))]
+async fn send(
+    WithRejection(Json(params), _): WithRejection<Json<SendToAddressParams>, ApiError>,
+) -> ApiResult {
+    if params.preview {
+        return Err(RestError::invalid_params("use /v1/transactions/preview to preview").into());
+    }
+    Ok(data(WalletRpcImpl::send_to_address(params).await?))
+}
+
+#[utoipa::path(post, path = "/v1/transactions/preview", tag = "transactions",
+    request_body = SendToAddressParams,
+    responses((status = 200, body = Envelope<SendPreview>), ApiError))]
+async fn preview_send(
+    WithRejection(Json(params), _): WithRejection<Json<SendToAddressParams>, ApiError>,
+) -> ApiResult {
+    Ok(data(WalletRpcImpl::preview_send(params).await?))
+}
+
+/// Stops the send being proven.
+#[utoipa::path(post, path = "/v1/transactions/cancel", tag = "transactions",
+    responses((status = 200, body = Envelope<CancelSendResponse>), ApiError))]
+async fn cancel_send() -> ApiResult {
+    let cancelled = WalletRpcImpl::cancel_send().await;
+    Ok(data(CancelSendResponse { cancelled }))
+}
+
+/// Stops tracking a pending transaction and releases its inputs.
+#[utoipa::path(delete, path = "/v1/transactions/{txid}", tag = "transactions",
+    params(("txid" = String, Path)),
+    responses((status = 204, description = "forgotten"), ApiError))]
+async fn forget_transaction(
+    WithRejection(Path(txid), _): WithRejection<Path<String>, ApiError>,
+) -> ApiResult<StatusCode> {
+    WalletRpcImpl::forget_tx(txid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(post, path = "/v1/transactions/{txid}/bump-fee", tag = "transactions",
+    params(("txid" = String, Path, description = "transaction to replace")),
+    request_body = BumpFeeRequest,
+    responses((status = 200, body = Envelope<BumpFeeResponse>), ApiError))]
+async fn bump_fee(
+    WithRejection(Path(txid), _): WithRejection<Path<String>, ApiError>,
+    WithRejection(Json(params), _): WithRejection<Json<BumpFeeRequest>, ApiError>,
+) -> ApiResult {
+    let params = BumpFeeParams {
+        txid,
+        fee: params.fee,
+    };
+    Ok(data(WalletRpcImpl::bump_fee(params).await?))
+}
+
+#[utoipa::path(get, path = "/v1/jobs", tag = "jobs", params(PageParams),
+    responses((status = 200, body = Page<Job>), ApiError))]
+async fn list_jobs(
+    WithRejection(Query(page), _): WithRejection<Query<PageParams>, ApiError>,
+) -> ApiResult {
+    let jobs = WalletRpcImpl::list_jobs().await?;
+    Ok(ErasedJson::pretty(page.paginate(jobs)))
+}
+
+#[utoipa::path(post, path = "/v1/jobs", tag = "jobs", request_body = SendToAddressParams,
+    responses((status = 202, description = "queued", body = Envelope<SubmitJobResponse>), ApiError))]
+async fn submit_job(
+    WithRejection(Json(params), _): WithRejection<Json<SendToAddressParams>, ApiError>,
+) -> ApiResult<(StatusCode, ErasedJson)> {
+    let job = WalletRpcImpl::submit_send_job(params).await?;
+    Ok((StatusCode::ACCEPTED, data(job)))
+}
+
+#[utoipa::path(get, path = "/v1/jobs/{id}", tag = "jobs", params(("id" = i64, Path)),
+    responses((status = 200, body = Envelope<Job>), ApiError))]
+async fn get_job(WithRejection(Path(id), _): WithRejection<Path<i64>, ApiError>) -> ApiResult {
+    Ok(data(WalletRpcImpl::get_job(id).await?))
+}
+
+#[utoipa::path(post, path = "/v1/jobs/{id}/cancel", tag = "jobs", params(("id" = i64, Path)),
+    responses((status = 200, description = "the cancelled job", body = Envelope<Job>), ApiError))]
+async fn cancel_job(WithRejection(Path(id), _): WithRejection<Path<i64>, ApiError>) -> ApiResult {
+    WalletRpcImpl::cancel_job(id).await?;
+    Ok(data(WalletRpcImpl::get_job(id).await?))
+}
+
+#[utoipa::path(post, path = "/v1/offline/unsigned", tag = "offline",
+    request_body = SendToAddressParams,
+    responses((status = 200, body = Envelope<UnsignedTransactionResponse>), ApiError))]
+async fn create_unsigned_transaction(
+    WithRejection(Json(params), _): WithRejection<Json<SendToAddressParams>, ApiError>,
+) -> ApiResult {
+    Ok(data(
+        WalletRpcImpl::create_unsigned_transaction(params).await?,
+    ))
+}
+
+#[utoipa::path(post, path = "/v1/offline/sign", tag = "offline",
+    request_body = SignTransactionParams,
+    responses((status = 200, body = Envelope<SignedTransactionResponse>), ApiError))]
+async fn sign_transaction(
+    WithRejection(Json(params), _): WithRejection<Json<SignTransactionParams>, ApiError>,
+) -> ApiResult {
+    Ok(data(WalletRpcImpl::sign_transaction(params).await?))
+}
+
+#[utoipa::path(post, path = "/v1/offline/broadcast", tag = "offline",
+    request_body = BroadcastTransactionParams,
+    responses((status = 200, body = Envelope<BroadcastResponse>), ApiError))]
+async fn broadcast_transaction(
+    WithRejection(Json(params), _): WithRejection<Json<BroadcastTransactionParams>, ApiError>,
+) -> ApiResult {
+    let txid = WalletRpcImpl::broadcast_signed_transaction(params).await?;
+    Ok(data(BroadcastResponse { txid }))
+}
+
+#[utoipa::path(get, path = "/v1/upgrades", tag = "upgrades", params(PageParams),
+    responses((status = 200, body = Page<UpgradeItem>), ApiError))]
+async fn list_upgrades(
+    WithRejection(Query(page), _): WithRejection<Query<PageParams>, ApiError>,
+) -> ApiResult {
+    let upgrades = WalletRpcImpl::list_upgrades().await?;
+    Ok(ErasedJson::pretty(page.paginate(upgrades)))
+}
+
+#[utoipa::path(post, path = "/v1/upgrades", tag = "upgrades", request_body = SubmitUpgradeParams,
+    responses((status = 202, description = "queued", body = Envelope<SubmitUpgradeResponse>), ApiError))]
+async fn submit_upgrade(
+    WithRejection(Json(params), _): WithRejection<Json<SubmitUpgradeParams>, ApiError>,
+) -> ApiResult<(StatusCode, ErasedJson)> {
+    let upgrade = WalletRpcImpl::submit_upgrade(params).await?;
+    Ok((StatusCode::ACCEPTED, data(upgrade)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paginates_and_caps_limit() {
+        let params = PageParams {
+            offset: 2,
+            limit: Some(1000),
+        };
+        let page = params.paginate((0..10).collect());
+
+        assert_eq!(page.data, (2..10).collect::<Vec<_>>());
+        assert_eq!(
+            page.page,
+            PageInfo {
+                offset: 2,
+                limit: MAX_PAGE_LIMIT,
+                total: 10
+            }
+        );
+    }
+
+    #[test]
+    fn openapi_document_describes_routes() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        assert!(doc["paths"]["/v1/transactions/{txid}"]["delete"].is_object());
+        assert!(doc["paths"]["/v1/jobs"]["post"].is_object());
+        assert!(doc["paths"]["/v1/wallets"]["get"].is_object());
+        assert!(doc["paths"]["/v1/wallet/scan-config"]["put"].is_object());
+        assert!(doc["paths"]["/v1/wallet/rescan"]["post"].is_object());
+        assert_eq!(
+            doc["components"]["securitySchemes"]["bearer"]["scheme"],
+            "bearer"
+        );
+    }
+}
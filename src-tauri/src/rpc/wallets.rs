@@ -0,0 +1,176 @@
+//! Routes scoped to one of the synced wallets.
+//!
+//! `/rpc/wallets/{id}/...` and `/v1/wallets/{id}/...` serve the routes of the
+//! current wallet for wallet `id`, e.g. `/rpc/wallets/2/balance` is
+//! `/rpc/wallet/balance` and `/v1/wallets/2/transactions` is
+//! `/v1/transactions` of wallet 2.
+
+use std::sync::Arc;
+
+use axum::extract::Request;
+use axum::http::Uri;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum_extra::response::ErasedJson;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::error::RestError;
+use super::v1::ApiError;
+use crate::service::try_get_state;
+use crate::wallet::registry::with_wallet;
+use crate::wallet::registry::WalletRegistry;
+use crate::wallet::sync::SyncStatus;
+
+/// Route prefixes, with the routes of the prefix that live under `wallet/`.
+const SCOPED_PREFIXES: [(&str, &[&str]); 2] = [
+    (
+        "/rpc",
+        &[
+            "balance",
+            "address",
+            "history",
+            "available_utxos",
+            "consolidate",
+            "sweep",
+            "scan_config",
+            "rescan",
+        ],
+    ),
+    (
+        "/v1",
+        &[
+            "balance",
+            "addresses",
+            "history",
+            "utxos",
+            "consolidate",
+            "sweep",
+            "scan-config",
+            "rescan",
+        ],
+    ),
+];
+
+/// Wallet a request was scoped to by [rewrite_uri].
+#[derive(Debug, Clone, Copy)]
+struct ScopedWallet(i64);
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncedWallet {
+    pub id: i64,
+    #[serde(flatten)]
+    pub status: SyncStatus,
+}
+
+/// Wallet id and unscoped path of a scoped path.
+fn unscoped_path(path: &str) -> Option<(i64, String)> {
+    for (prefix, wallet_routes) in SCOPED_PREFIXES {
+        let Some(rest) = path
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_prefix("/wallets/"))
+        else {
+            continue;
+        };
+        let (id, rest) = rest.split_once('/')?;
+        let id = id.parse().ok()?;
+        let route = rest.split('/').next().unwrap_or_default();
+        let path = match wallet_routes.contains(&route) {
+            true => format!("{prefix}/wallet/{rest}"),
+            false => format!("{prefix}/{rest}"),
+        };
+        return Some((id, path));
+    }
+    None
+}
+
+/// Maps scoped paths to the unscoped routes, runs before routing.
+pub async fn rewrite_uri(mut request: Request) -> Request {
+    let Some((id, path)) = unscoped_path(request.uri().path()) else {
+        return request;
+    };
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    };
+
+    let mut parts = request.uri().clone().into_parts();
+    if let Ok(path_and_query) = path_and_query.parse() {
+        parts.path_and_query = Some(path_and_query);
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *request.uri_mut() = uri;
+            request.extensions_mut().insert(ScopedWallet(id));
+        }
+    }
+    request
+}
+
+/// Runs scoped requests with the wallet of the scope, after authentication.
+pub async fn scope_wallet(request: Request, next: Next) -> Response {
+    let Some(ScopedWallet(id)) = request.extensions().get().copied() else {
+        return next.run(request).await;
+    };
+
+    match try_get_state::<Arc<WalletRegistry>>().and_then(|registry| registry.get(id)) {
+        Some(wallet) => with_wallet(wallet, next.run(request)).await,
+        None => {
+            let err = RestError::not_found(format!("wallet {id} is not synced"));
+            match request.uri().path().starts_with("/v1/") {
+                true => ApiError::from(err).into_response(),
+                false => err.into_response(),
+            }
+        }
+    }
+}
+
+pub async fn list_wallets() -> Result<ErasedJson, RestError> {
+    Ok(ErasedJson::pretty(synced_wallets().await))
+}
+
+pub async fn synced_wallets() -> Vec<SyncedWallet> {
+    let Some(registry) = try_get_state::<Arc<WalletRegistry>>() else {
+        return vec![];
+    };
+
+    let mut wallets = vec![];
+    for state in registry.wallets() {
+        wallets.push(SyncedWallet {
+            id: state.wallet.id(),
+            status: state.status().await,
+        });
+    }
+    wallets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_scoped_paths() {
+        assert_eq!(
+            unscoped_path("/rpc/wallets/2/balance"),
+            Some((2, "/rpc/wallet/balance".to_string()))
+        );
+        assert_eq!(
+            unscoped_path("/rpc/wallets/2/address/5"),
+            Some((2, "/rpc/wallet/address/5".to_string()))
+        );
+        assert_eq!(
+            unscoped_path("/rpc/wallets/2/jobs/1/cancel"),
+            Some((2, "/rpc/jobs/1/cancel".to_string()))
+        );
+        assert_eq!(
+            unscoped_path("/v1/wallets/3/addresses/0"),
+            Some((3, "/v1/wallet/addresses/0".to_string()))
+        );
+        assert_eq!(
+            unscoped_path("/v1/wallets/3/transactions"),
+            Some((3, "/v1/transactions".to_string()))
+        );
+        assert_eq!(unscoped_path("/rpc/wallets"), None);
+        assert_eq!(unscoped_path("/rpc/wallets/x/balance"), None);
+        assert_eq!(unscoped_path("/rpc/wallet/balance"), None);
+    }
+}
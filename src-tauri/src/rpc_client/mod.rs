@@ -80,6 +80,15 @@ impl NodeRpcClient {
         Ok(block_header.header)
     }
 
+    /// Digest of the canonical block at `height`, `None` above the tip.
+    pub async fn get_block_digest(&self, height: u64) -> Result<Option<Digest>> {
+        let block_digest = Self::get_client()
+            .get_block_digest(BlockSelector::Height(height.into()))
+            .await?;
+
+        Ok(block_digest.digest)
+    }
+
     pub async fn request_block_by_digest(&self, digest: &str) -> Result<Option<WalletBlock>> {
         let selector = BlockSelector::Digest(Digest::try_from_hex(digest)?);
         let block_header = Self::get_client().get_block_header(selector).await?.header;
@@ -3,6 +3,7 @@ use neptune_cash::api::export::Timestamp;
 use neptune_cash::protocol::consensus::type_scripts::native_currency_amount::NativeCurrencyAmount;
 use serde::Deserialize;
 use serde::Serialize;
+use utoipa::ToSchema;
 
 impl super::WalletState {
     pub async fn get_balance(&self) -> Result<NativeCurrencyAmount> {
@@ -83,12 +84,15 @@ impl super::WalletState {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct WalletHistory {
     pub amount: String,
+    /// milliseconds since the unix epoch
+    #[schema(value_type = u64)]
     pub timestamp: Timestamp,
     pub height: u64,
     pub index: u64,
+    #[schema(value_type = Option<u64>)]
     pub release_date: Option<Timestamp>,
     pub txid: Option<String>,
 }
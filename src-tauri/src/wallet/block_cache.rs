@@ -315,17 +315,16 @@ impl BlockCache for PersistBlockCache {
         self.memory_cache.add_blocks_temp(blocks).await
     }
 
-    async fn delete_block_by_start_height(&self, start_height: u64) -> Result<()> {
+    async fn delete_block(&self, height: u64, digest: Digest) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
-        sqlx::query("DELETE FROM block_cache WHERE height >= ?")
-            .bind(start_height as i64)
+        sqlx::query("DELETE FROM block_cache WHERE height = ? AND hash = ?")
+            .bind(height as i64)
+            .bind(digest.to_hex())
             .execute(&mut *conn)
             .await
-            .map_err(|err| anyhow::anyhow!("Could not delete blocks from database: {err}"))?;
+            .map_err(|err| anyhow::anyhow!("Could not delete block from database: {err}"))?;
 
-        self.memory_cache
-            .delete_block_by_start_height(start_height)
-            .await?;
+        self.memory_cache.delete_block(height, digest).await?;
         Ok(())
     }
 
@@ -432,9 +431,9 @@ impl BlockCache for MemoryBlockCache {
         Ok(None)
     }
 
-    async fn delete_block_by_start_height(&self, start_height: u64) -> Result<()> {
+    async fn delete_block(&self, height: u64, digest: Digest) -> Result<()> {
         let mut cache = self.cache.lock().await;
-        cache.retain(|b| b.kernel.header.height < start_height.into());
+        cache.retain(|b| b.kernel.header.height != height.into() || b.hash() != digest);
         Ok(())
     }
 }
@@ -447,7 +446,7 @@ pub(super) trait BlockCache {
     async fn has_block_by_height(&self, height: u64) -> Result<bool>;
     async fn get_block_by_height(&self, height: u64) -> Result<Option<WalletBlock>>;
     async fn get_block_by_digest(&self, digest: Digest) -> Result<Option<WalletBlock>>;
-    async fn delete_block_by_start_height(&self, start_height: u64) -> Result<()>;
+    async fn delete_block(&self, height: u64, digest: Digest) -> Result<()>;
 }
 
 #[enum_dispatch]
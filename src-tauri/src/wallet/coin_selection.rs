@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use tracing::*;
+
+use super::wallet_state_table::UtxoDbData;
+
+/// upper bound on the number of branches visited by [branch_and_bound]
+const BNB_MAX_TRIES: usize = 100_000;
+
+fn amount(utxo: &UtxoDbData) -> i128 {
+    utxo.recovery_data
+        .utxo
+        .get_native_currency_amount()
+        .to_nau()
+}
+
+fn total(utxos: &[UtxoDbData]) -> i128 {
+    utxos.iter().map(amount).sum()
+}
+
+/// Takes utxos in the given order until `target` is covered.
+pub fn greedy(utxos: Vec<UtxoDbData>, target: i128) -> Vec<UtxoDbData> {
+    let mut selected = vec![];
+    let mut selected_amount = 0;
+    for utxo in utxos {
+        if selected_amount >= target {
+            break;
+        }
+        selected_amount += amount(&utxo);
+        selected.push(utxo);
+    }
+    selected
+}
+
+/// Searches for a set of utxos whose amounts add up to exactly `target`, so
+/// that no change output is needed.
+///
+/// Returns `None` if no such set is found within [BNB_MAX_TRIES] branches.
+pub fn branch_and_bound(utxos: &[UtxoDbData], target: i128) -> Option<Vec<UtxoDbData>> {
+    let utxos = utxos
+        .iter()
+        .sorted_by_key(|utxo| std::cmp::Reverse(amount(utxo)))
+        .collect_vec();
+    let amounts = utxos.iter().map(|utxo| amount(utxo)).collect_vec();
+
+    // remaining[i] is the sum of amounts[i..]
+    let mut remaining = vec![0; amounts.len() + 1];
+    for i in (0..amounts.len()).rev() {
+        remaining[i] = remaining[i + 1] + amounts[i];
+    }
+
+    let mut selected = vec![];
+    let mut tries = BNB_MAX_TRIES;
+    if !bnb_search(
+        &amounts,
+        &remaining,
+        0,
+        0,
+        target,
+        &mut selected,
+        &mut tries,
+    ) {
+        debug!("branch and bound found no exact match");
+        return None;
+    }
+
+    Some(selected.into_iter().map(|i| utxos[i].clone()).collect())
+}
+
+fn bnb_search(
+    amounts: &[i128],
+    remaining: &[i128],
+    index: usize,
+    current: i128,
+    target: i128,
+    selected: &mut Vec<usize>,
+    tries: &mut usize,
+) -> bool {
+    if current == target {
+        return true;
+    }
+    if index == amounts.len() || *tries == 0 || current + remaining[index] < target {
+        return false;
+    }
+    *tries -= 1;
+
+    if current + amounts[index] <= target {
+        selected.push(index);
+        if bnb_search(
+            amounts,
+            remaining,
+            index + 1,
+            current + amounts[index],
+            target,
+            selected,
+            tries,
+        ) {
+            return true;
+        }
+        selected.pop();
+    }
+
+    bnb_search(
+        amounts,
+        remaining,
+        index + 1,
+        current,
+        target,
+        selected,
+        tries,
+    )
+}
+
+/// Covers `target` with as few inputs as possible, which keeps the proof small.
+///
+/// Among selections of the minimal size, the last input is the smallest one
+/// that still covers the target, to reduce change.
+pub fn min_inputs(utxos: Vec<UtxoDbData>, target: i128) -> Vec<UtxoDbData> {
+    let mut utxos = utxos;
+    utxos.sort_by_key(|utxo| std::cmp::Reverse(amount(utxo)));
+
+    let mut sum = 0;
+    let Some(count) = utxos.iter().position(|utxo| {
+        sum += amount(utxo);
+        sum >= target
+    }) else {
+        // not enough funds, let transaction creation report it
+        return utxos;
+    };
+
+    let mut rest = utxos.split_off(count);
+    let missing = target - total(&utxos);
+    // rest is sorted descending and rest[0] covers `missing`
+    let last = rest
+        .iter()
+        .rposition(|utxo| amount(utxo) >= missing)
+        .unwrap_or(0);
+    utxos.push(rest.swap_remove(last));
+    utxos
+}
+
+/// Only spends utxos received on a single address when possible, so the
+/// transaction does not link different derivation indexes together.
+pub fn privacy(utxos: Vec<UtxoDbData>, target: i128) -> Vec<UtxoDbData> {
+    let mut groups: HashMap<_, Vec<UtxoDbData>> = HashMap::new();
+    for utxo in utxos.iter() {
+        groups
+            .entry(utxo.recovery_data.utxo.lock_script_hash())
+            .or_default()
+            .push(utxo.clone());
+    }
+
+    let best = groups
+        .into_values()
+        .filter(|group| total(group) >= target)
+        .map(|group| min_inputs(group, target))
+        // ties go to the smallest utxo id, the map iterates in random order
+        .min_by_key(|selected| {
+            let first_id = selected.iter().map(|utxo| utxo.id).min();
+            (selected.len(), total(selected), first_id)
+        });
+
+    match best {
+        Some(selected) => selected,
+        None => {
+            warn!("no single address covers the amount, inputs from several addresses are linked");
+            min_inputs(utxos, target)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use neptune_cash::api::export::NativeCurrencyAmount;
+    use neptune_cash::api::export::Timestamp;
+    use neptune_cash::api::export::Tip5;
+    use neptune_cash::api::export::Utxo;
+    use neptune_cash::prelude::tasm_lib::prelude::Digest;
+
+    use super::*;
+    use crate::wallet::wallet_state_table::UtxoBlockInfo;
+    use crate::wallet::UtxoRecoveryData;
+
+    fn utxo(id: i64, coins: u32, key: u64) -> UtxoDbData {
+        UtxoDbData {
+            id,
+            hash: String::new(),
+            recovery_data: UtxoRecoveryData {
+                utxo: Utxo::new_native_currency(
+                    Tip5::hash(&key),
+                    NativeCurrencyAmount::coins(coins),
+                ),
+                sender_randomness: Digest::default(),
+                receiver_preimage: Digest::default(),
+                aocl_index: id as u64,
+            },
+            spent_in_block: None,
+            confirmed_in_block: UtxoBlockInfo {
+                block_height: id as u64,
+                block_digest: Digest::default(),
+                timestamp: Timestamp::now(),
+            },
+            confirm_height: id,
+            spent_height: None,
+            confirmed_txid: None,
+            spent_txid: None,
+        }
+    }
+
+    fn coins(coins: u32) -> i128 {
+        NativeCurrencyAmount::coins(coins).to_nau()
+    }
+
+    fn ids(utxos: &[UtxoDbData]) -> Vec<i64> {
+        utxos.iter().map(|utxo| utxo.id).sorted().collect()
+    }
+
+    #[test]
+    fn branch_and_bound_finds_exact_match() {
+        let utxos = vec![utxo(1, 5, 0), utxo(2, 3, 0), utxo(3, 4, 0), utxo(4, 10, 0)];
+
+        let selected = branch_and_bound(&utxos, coins(7)).unwrap();
+        assert_eq!(ids(&selected), vec![2, 3]);
+
+        assert!(branch_and_bound(&utxos, coins(23)).is_none());
+        assert!(branch_and_bound(&utxos, coins(2)).is_none());
+    }
+
+    #[test]
+    fn min_inputs_uses_fewest_utxos_and_least_change() {
+        let utxos = vec![
+            utxo(1, 1, 0),
+            utxo(2, 1, 0),
+            utxo(3, 1, 0),
+            utxo(4, 8, 0),
+            utxo(5, 3, 0),
+            utxo(6, 4, 0),
+        ];
+
+        // 8 + 3 covers 10 with two inputs, 8 + 4 would leave more change
+        let selected = min_inputs(utxos.clone(), coins(10));
+        assert_eq!(ids(&selected), vec![4, 5]);
+
+        let selected = min_inputs(utxos.clone(), coins(2));
+        assert_eq!(ids(&selected), vec![5]);
+
+        // not enough funds: everything is returned
+        let selected = min_inputs(utxos, coins(100));
+        assert_eq!(selected.len(), 6);
+    }
+
+    #[test]
+    fn privacy_spends_from_a_single_address() {
+        let utxos = vec![
+            utxo(1, 6, 1),
+            utxo(2, 6, 2),
+            utxo(3, 2, 2),
+            utxo(4, 3, 3),
+            utxo(5, 3, 3),
+        ];
+
+        let selected = privacy(utxos.clone(), coins(8));
+        assert_eq!(ids(&selected), vec![2, 3]);
+
+        // utxo 1 and 2 tie, the smaller id wins on every run
+        for _ in 0..16 {
+            let selected = privacy(utxos.clone(), coins(5));
+            assert_eq!(ids(&selected), vec![1]);
+        }
+
+        // no single address covers 10, falls back to linking addresses
+        let selected = privacy(utxos, coins(10));
+        assert!(total(&selected) >= coins(10));
+    }
+
+    #[test]
+    fn greedy_keeps_order() {
+        let utxos = vec![utxo(1, 1, 0), utxo(2, 5, 0), utxo(3, 2, 0)];
+
+        let selected = greedy(utxos, coins(4));
+        assert_eq!(ids(&selected), vec![1, 2]);
+    }
+}
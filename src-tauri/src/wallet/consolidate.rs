@@ -0,0 +1,86 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use neptune_cash::api::export::NativeCurrencyAmount;
+use neptune_cash::protocol::consensus::transaction::Transaction;
+use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMedium;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+
+use super::input::InputSelectionRule;
+use super::spend::SendError;
+use super::wallet_state_table::UtxoDbData;
+
+/// default limit of inputs merged by one consolidation transaction
+pub const DEFAULT_CONSOLIDATE_MAX_INPUTS: usize = 20;
+
+/// Settings of the background consolidation, disabled when not stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidateConfig {
+    /// inputs merged per transaction
+    pub max_inputs: usize,
+    /// only consolidate once the wallet holds at least this many spendable utxos
+    pub min_utxos: usize,
+    pub fee: String,
+}
+
+impl super::WalletState {
+    /// The `max_inputs` smallest spendable utxos.
+    pub async fn get_consolidation_candidates(&self, max_inputs: usize) -> Result<Vec<UtxoDbData>> {
+        let mut utxos = InputSelectionRule::Minimum.apply(self.get_spendable_utxos().await?);
+        utxos.truncate(max_inputs);
+        Ok(utxos)
+    }
+
+    /// Merges the smallest spendable utxos into a single change output on our
+    /// own symmetric key.
+    ///
+    /// Returns `None` if there are less than two utxos to merge.
+    pub async fn consolidate(
+        &self,
+        max_inputs: usize,
+        fee: NativeCurrencyAmount,
+    ) -> Result<Option<Transaction>, SendError> {
+        let candidates = self.get_consolidation_candidates(max_inputs).await?;
+        if candidates.len() < 2 {
+            debug!("nothing to consolidate");
+            return Ok(None);
+        }
+
+        let amount: NativeCurrencyAmount = candidates
+            .iter()
+            .map(|utxo| utxo.recovery_data.utxo.get_native_currency_amount())
+            .sum();
+        if amount <= fee {
+            return Err(anyhow!(
+                "consolidation fee {fee} exceeds the amount of the {} smallest utxos {amount}",
+                candidates.len()
+            )
+            .into());
+        }
+
+        info!(
+            "consolidating {} utxos with a total of {}",
+            candidates.len(),
+            amount
+        );
+
+        // no outputs, everything except the fee goes to the change output
+        let ids = candidates.iter().map(|utxo| utxo.id).collect();
+        let transaction = self
+            .send_to_address(
+                vec![],
+                (
+                    UtxoNotificationMedium::OnChain,
+                    UtxoNotificationMedium::OnChain,
+                ),
+                fee,
+                InputSelectionRule::Minimum,
+                ids,
+                None,
+            )
+            .await?;
+
+        Ok(Some(transaction))
+    }
+}
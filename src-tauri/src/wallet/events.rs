@@ -0,0 +1,134 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+use super::tx_status::TxState;
+use super::wallet_state_table::UtxoDbData;
+
+/// Events kept for subscribers that fall behind, older ones are dropped.
+const EVENT_CAPACITY: usize = 1024;
+
+/// Change of the wallet state, published to RPC clients by `/rpc/events`.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WalletEvent {
+    NewBlock {
+        height: u64,
+        digest: String,
+    },
+    /// blocks above `fork_height` were rolled back, the utxos they received
+    /// are removed and their spends are undone, followed by a
+    /// `balance_changed` event
+    Reorg {
+        fork_height: u64,
+    },
+    UtxoReceived {
+        hash: String,
+        amount: String,
+        height: u64,
+    },
+    UtxoSpent {
+        hash: String,
+        amount: String,
+        height: u64,
+    },
+    /// state of a transaction sent by this wallet, `id` and `txid` are set
+    /// when known
+    TxStateChanged {
+        id: Option<i64>,
+        txid: Option<String>,
+        state: TxState,
+    },
+    BalanceChanged {
+        available_balance: String,
+        total_balance: String,
+    },
+}
+
+impl WalletEvent {
+    pub(super) fn received(utxo: &UtxoDbData) -> Self {
+        WalletEvent::UtxoReceived {
+            hash: utxo.hash.clone(),
+            amount: utxo
+                .recovery_data
+                .utxo
+                .get_native_currency_amount()
+                .display_lossless(),
+            height: utxo.confirmed_in_block.block_height,
+        }
+    }
+
+    /// Name of the event, the `type` field of its JSON.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WalletEvent::NewBlock { .. } => "new_block",
+            WalletEvent::Reorg { .. } => "reorg",
+            WalletEvent::UtxoReceived { .. } => "utxo_received",
+            WalletEvent::UtxoSpent { .. } => "utxo_spent",
+            WalletEvent::TxStateChanged { .. } => "tx_state_changed",
+            WalletEvent::BalanceChanged { .. } => "balance_changed",
+        }
+    }
+}
+
+/// Fans wallet events out to every subscriber, events published without
+/// subscribers are dropped.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<WalletEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: WalletEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<WalletEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_with_type_tag() {
+        let event = WalletEvent::TxStateChanged {
+            id: Some(1),
+            txid: None,
+            state: TxState::Confirmed(10),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "type": event.kind(),
+                "id": 1,
+                "txid": null,
+                "state": { "status": "Confirmed", "detail": 10 }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn delivers_to_subscribers() {
+        let bus = EventBus::default();
+        bus.publish(WalletEvent::Reorg { fork_height: 1 });
+
+        let mut receiver = bus.subscribe();
+        bus.publish(WalletEvent::Reorg { fork_height: 2 });
+
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            WalletEvent::Reorg { fork_height: 2 }
+        );
+    }
+}
@@ -19,18 +19,24 @@ use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
 use tracing::*;
 
+use crate::config::Config;
 use crate::rpc_client;
 use crate::wallet::block::WalletBlock;
 use crate::wallet::block_cache::BlockCache;
 use crate::wallet::block_cache::BlockCacheImpl;
 
+/// Source of blocks, shared by all synced wallets so each block is requested
+/// once.
 #[derive(Clone)]
 pub struct FakeArchivalState {
     block_cache: Arc<BlockCacheImpl>,
     snapshot_reader: Arc<Option<SnapshotReader>>,
     network: Network,
+    /// wallets preparing the same batch wait for the first request
+    prepare_lock: Arc<Mutex<()>>,
 }
 
 impl FakeArchivalState {
@@ -43,11 +49,49 @@ impl FakeArchivalState {
             block_cache: Arc::new(cache),
             snapshot_reader: Arc::new(snapshot_reader),
             network,
+            prepare_lock: Arc::new(Mutex::new(())),
         }
     }
 
+    pub async fn new_from_config(config: &Config) -> Result<Self> {
+        let data_dir = config.get_data_dir().await?;
+        let network = config.get_network().await?;
+        let snapshot_reader = match SnapshotReader::new(&data_dir).await {
+            Ok(v) => {
+                debug!("snapshot reader created : {:?}", v);
+                Some(v)
+            }
+            Err(e) => {
+                error!("failed to create snapshot reader: {:#?}", e);
+                None
+            }
+        };
+
+        let block_cache = if config.get_disk_cache().await? {
+            info!("disk cache enabled");
+            BlockCacheImpl::new_persist(&data_dir, network, 200).await?
+        } else {
+            warn!("disk cache is disabled, this will cause performance issues");
+            BlockCacheImpl::new_memory(200)
+        };
+
+        Ok(Self::new(block_cache, network, snapshot_reader))
+    }
+
     pub async fn prepare(&self, height: u64, batch_size: u64) -> Result<()> {
-        if self.block_cache.is_persist() && self.block_cache.has_block_by_height(height).await? {
+        let _guard = self.prepare_lock.lock().await;
+
+        // the memory cache drops old blocks, only skip batches requested just
+        // before by another wallet
+        let cached = match self.block_cache.is_persist() {
+            true => self.block_cache.has_block_by_height(height).await?,
+            false => {
+                self.block_cache
+                    .has_block_by_height(height + batch_size - 1)
+                    .await?
+            }
+        };
+        if cached {
             debug!("Block {height} already in cache, skipping request");
             return Ok(());
         }
@@ -94,6 +138,11 @@ impl FakeArchivalState {
         Ok(result)
     }
 
+    /// Like [Self::get_block_by_height] but never requests the block from the rest server.
+    pub async fn get_cached_block(&self, height: u64) -> Result<Option<WalletBlock>> {
+        self.block_cache.get_block_by_height(height).await
+    }
+
     #[allow(dead_code)]
     pub async fn get_block_by_digest(&self, digest: Digest) -> Result<Option<WalletBlock>> {
         if let Some(block) = self.block_cache.get_block_by_digest(digest).await? {
@@ -109,10 +158,40 @@ impl FakeArchivalState {
             .await?)
     }
 
-    pub async fn reset_to_height(&self, height: u64) -> Result<()> {
-        self.block_cache
-            .delete_block_by_start_height(height + 1)
-            .await?;
+    /// Evicts the cached blocks above `height` that are not on the canonical
+    /// chain of the node. The cache is shared, blocks still valid for the
+    /// other wallets are kept.
+    pub async fn evict_orphaned_blocks(&self, height: u64) -> Result<()> {
+        let mut cached = vec![];
+        let mut next = height + 1;
+        while let Some(block) = self.block_cache.get_block_by_height(next).await? {
+            cached.push((next, block.hash(), block.kernel.header.prev_block_digest));
+            next += 1;
+        }
+
+        // the parent of a canonical block is canonical, the node is only asked
+        // when the chain of cached blocks breaks
+        let mut canonical_parent = None;
+        for (height, digest, prev_digest) in cached.into_iter().rev() {
+            let canonical = match canonical_parent {
+                Some(parent) => parent == digest,
+                None => {
+                    rpc_client::node_rpc_client()
+                        .get_block_digest(height)
+                        .await?
+                        == Some(digest)
+                }
+            };
+
+            if canonical {
+                canonical_parent = Some(prev_digest);
+            } else {
+                debug!("evicting orphaned block {} {}", height, digest.to_hex());
+                self.block_cache.delete_block(height, digest).await?;
+                canonical_parent = None;
+            }
+        }
+
         Ok(())
     }
 }
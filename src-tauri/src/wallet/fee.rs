@@ -0,0 +1,211 @@
+use std::ops::Range;
+
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Result;
+use neptune_cash::api::export::NativeCurrencyAmount;
+
+use super::block::WalletBlock;
+
+/// number of most recent synced blocks the estimate is derived from
+pub const FEE_ESTIMATE_BLOCKS: u64 = 50;
+
+/// used when none of the recent blocks contain transactions
+const FALLBACK_FEE_PER_INPUT: &str = "0.01";
+
+#[derive(Debug, Clone)]
+pub struct FeeEstimate {
+    pub low: NativeCurrencyAmount,
+    pub medium: NativeCurrencyAmount,
+    pub high: NativeCurrencyAmount,
+    /// number of blocks with transactions the estimate is based on
+    pub samples: usize,
+}
+
+/// Heights of the last [FEE_ESTIMATE_BLOCKS] synced blocks, `next_height` is
+/// the height the sync continues from.
+pub fn fee_estimate_window(next_height: u64) -> Range<u64> {
+    next_height.saturating_sub(FEE_ESTIMATE_BLOCKS)..next_height
+}
+
+/// Suggests fees for a transaction spending `num_inputs` inputs.
+///
+/// Blocks carry a single merged transaction, so individual fees are not
+/// visible. The fee paid per input of each block is used as the sample.
+pub fn estimate_fee_from_blocks(blocks: &[WalletBlock], num_inputs: u64) -> Result<FeeEstimate> {
+    let samples = blocks
+        .iter()
+        .map(|block| block.kernel.body.transaction_kernel())
+        .filter(|kernel| !kernel.inputs.is_empty())
+        .map(|kernel| kernel.fee.to_nau() / kernel.inputs.len() as i128)
+        .collect::<Vec<_>>();
+
+    estimate_fee_from_samples(samples, num_inputs)
+}
+
+/// Percentiles of the per-input fee samples, in nau.
+fn estimate_fee_from_samples(mut samples: Vec<i128>, num_inputs: u64) -> Result<FeeEstimate> {
+    samples.sort();
+
+    let num_inputs = num_inputs.max(1) as i128;
+    let fallback = NativeCurrencyAmount::coins_from_str(FALLBACK_FEE_PER_INPUT)?.to_nau();
+    let percentile = |p: usize| {
+        let per_input = if samples.is_empty() {
+            fallback
+        } else {
+            samples[(samples.len() - 1) * p / 100]
+        };
+        NativeCurrencyAmount::from_nau(per_input * num_inputs)
+    };
+
+    Ok(FeeEstimate {
+        low: percentile(25),
+        medium: percentile(50),
+        high: percentile(90),
+        samples: samples.len(),
+    })
+}
+
+/// Deducts `deduction` from the amounts flagged in `subtract`, split in
+/// proportion to the amounts.
+///
+/// The rounding remainder is taken from the last flagged amount, so exactly
+/// `deduction` is subtracted in total.
+pub fn subtract_fee_from_amounts(
+    amounts: &mut [NativeCurrencyAmount],
+    subtract: &[bool],
+    deduction: NativeCurrencyAmount,
+) -> Result<()> {
+    ensure!(
+        amounts.len() == subtract.len(),
+        "one flag per amount required"
+    );
+
+    let flagged = (0..amounts.len())
+        .filter(|i| subtract[*i])
+        .collect::<Vec<_>>();
+    let Some((&last, rest)) = flagged.split_last() else {
+        bail!("no output to subtract the fee from");
+    };
+
+    // amounts are reduced by their common divisor to keep the products small,
+    // if they still overflow the ratio is approximated, the remainder is
+    // settled exactly below anyway
+    let divisor = flagged
+        .iter()
+        .fold(0, |acc, i| gcd(acc, amounts[*i].to_nau()));
+    ensure!(divisor > 0, "outputs to subtract the fee from are empty");
+    let weights = amounts
+        .iter()
+        .map(|amount| amount.to_nau() / divisor)
+        .collect::<Vec<_>>();
+    let total = flagged.iter().map(|i| weights[*i]).sum::<i128>();
+
+    let deduction = deduction.to_nau();
+    let mut deducted = 0;
+    let mut shares = vec![0; amounts.len()];
+    for i in rest {
+        let share = match deduction.checked_mul(weights[*i]) {
+            Some(product) => product / total,
+            None => (deduction as f64 * (weights[*i] as f64 / total as f64)) as i128,
+        };
+        let share = share.clamp(0, deduction - deducted);
+        shares[*i] = share;
+        deducted += share;
+    }
+    shares[last] = deduction - deducted;
+
+    for i in flagged {
+        let remaining = amounts[i].to_nau() - shares[i];
+        ensure!(
+            remaining > 0,
+            "output amount {} does not cover its share {} of the fee",
+            amounts[i],
+            NativeCurrencyAmount::from_nau(shares[i])
+        );
+        amounts[i] = NativeCurrencyAmount::from_nau(remaining);
+    }
+
+    Ok(())
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coins(s: &str) -> NativeCurrencyAmount {
+        NativeCurrencyAmount::coins_from_str(s).unwrap()
+    }
+
+    #[test]
+    fn fee_is_split_proportionally() {
+        let mut amounts = vec![coins("3"), coins("1"), coins("5")];
+        subtract_fee_from_amounts(&mut amounts, &[true, true, false], coins("0.4")).unwrap();
+
+        assert_eq!(amounts, vec![coins("2.7"), coins("0.9"), coins("5")]);
+    }
+
+    #[test]
+    fn total_deduction_is_exact() {
+        let mut amounts = vec![coins("1"), coins("1"), coins("1")];
+        let fee = NativeCurrencyAmount::from_nau(100);
+        subtract_fee_from_amounts(&mut amounts, &[true, true, true], fee).unwrap();
+
+        let total: NativeCurrencyAmount = amounts.iter().copied().sum();
+        assert_eq!(total + fee, coins("3"));
+    }
+
+    #[test]
+    fn fee_larger_than_output_fails() {
+        let mut amounts = vec![coins("0.1"), coins("5")];
+        assert!(subtract_fee_from_amounts(&mut amounts, &[true, false], coins("0.2")).is_err());
+        assert!(subtract_fee_from_amounts(&mut amounts, &[false, false], coins("0.2")).is_err());
+    }
+
+    #[test]
+    fn window_ends_at_last_synced_block() {
+        assert_eq!(fee_estimate_window(100), 50..100);
+        assert_eq!(fee_estimate_window(10), 0..10);
+        assert!(fee_estimate_window(0).is_empty());
+    }
+
+    #[test]
+    fn no_samples_use_fallback() {
+        let estimate = estimate_fee_from_blocks(&[], 2).unwrap();
+
+        assert_eq!(estimate.samples, 0);
+        assert_eq!(estimate.low, coins("0.02"));
+        assert_eq!(estimate.medium, coins("0.02"));
+        assert_eq!(estimate.high, coins("0.02"));
+    }
+
+    #[test]
+    fn single_sample_is_every_percentile() {
+        let estimate = estimate_fee_from_samples(vec![coins("0.5").to_nau()], 3).unwrap();
+
+        assert_eq!(estimate.samples, 1);
+        assert_eq!(estimate.low, coins("1.5"));
+        assert_eq!(estimate.medium, coins("1.5"));
+        assert_eq!(estimate.high, coins("1.5"));
+    }
+
+    #[test]
+    fn outlier_only_moves_high() {
+        let mut samples = (1..=9).map(|i| i * 1000).collect::<Vec<_>>();
+        samples.push(coins("1000").to_nau());
+        let estimate = estimate_fee_from_samples(samples, 1).unwrap();
+
+        assert_eq!(estimate.samples, 10);
+        assert_eq!(estimate.low, NativeCurrencyAmount::from_nau(3000));
+        assert_eq!(estimate.medium, NativeCurrencyAmount::from_nau(5000));
+        assert_eq!(estimate.high, NativeCurrencyAmount::from_nau(9000));
+    }
+}
@@ -10,21 +10,42 @@ use neptune_cash::api::export::Timestamp;
 use neptune_cash::api::export::Tip5;
 use neptune_cash::api::export::Utxo;
 use neptune_cash::state::wallet::unlocked_utxo::UnlockedUtxo;
+use neptune_cash::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
 use neptune_cash::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
 use neptune_cash::util_types::mutator_set::removal_record::absolute_index_set::AbsoluteIndexSet;
 use rand::seq::SliceRandom;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
 use tracing::trace;
 
+use super::coin_selection;
 use super::wallet_state_table::UtxoDbData;
 use super::UtxoRecoveryData;
 use crate::rpc_client;
 
+/// The spendable utxos do not cover the outputs and the fee.
+#[derive(Debug, Error)]
+#[error("insufficient funds: {available} available, {required} required")]
+pub struct InsufficientFunds {
+    pub available: NativeCurrencyAmount,
+    pub required: NativeCurrencyAmount,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum InputSelectionRule {
     Minimum,
     Maximum,
     Oldest,
     Newest,
     Random,
+    /// exact match without change, falls back to [InputSelectionRule::MinInputs]
+    BranchAndBound,
+    /// fewest inputs, keeps the proof small
+    MinInputs,
+    /// avoids linking utxos received on different addresses
+    Privacy,
 }
 
 impl Default for InputSelectionRule {
@@ -41,6 +62,9 @@ impl InputSelectionRule {
             "oldest" => Some(InputSelectionRule::Oldest),
             "newest" => Some(InputSelectionRule::Newest),
             "random" => Some(InputSelectionRule::Random),
+            "branch_and_bound" | "bnb" => Some(InputSelectionRule::BranchAndBound),
+            "min_inputs" | "knapsack" => Some(InputSelectionRule::MinInputs),
+            "privacy" => Some(InputSelectionRule::Privacy),
             _ => None,
         }
     }
@@ -65,9 +89,25 @@ impl InputSelectionRule {
                 utxos.sort_by(|a, b| b.confirm_height.cmp(&a.confirm_height))
             }
             InputSelectionRule::Random => utxos.shuffle(&mut rand::rng()),
+            InputSelectionRule::BranchAndBound
+            | InputSelectionRule::MinInputs
+            | InputSelectionRule::Privacy => {}
         };
         utxos
     }
+
+    /// Selects utxos covering `target` nau according to the rule.
+    ///
+    /// If the utxos are insufficient, all of them are returned.
+    pub fn select(&self, utxos: Vec<UtxoDbData>, target: i128) -> Vec<UtxoDbData> {
+        match self {
+            InputSelectionRule::BranchAndBound => coin_selection::branch_and_bound(&utxos, target)
+                .unwrap_or_else(|| coin_selection::min_inputs(utxos, target)),
+            InputSelectionRule::MinInputs => coin_selection::min_inputs(utxos, target),
+            InputSelectionRule::Privacy => coin_selection::privacy(utxos, target),
+            _ => coin_selection::greedy(self.apply(utxos), target),
+        }
+    }
 }
 
 impl super::WalletState {
@@ -83,18 +123,37 @@ impl super::WalletState {
         MutatorSetAccumulator,
         BlockHeight,
     )> {
-        let mut utxos = self.get_unspent_utxos().await?;
-        trace!("Num unspent utxos (not mined): {}", utxos.len());
+        let (inputs, db_idxs) = self
+            .select_inputs(outputs, fee, rule, must_include_inputs)
+            .await?;
 
-        let pending_utxos = self.updater.get_pending_spent_utxos().await?;
-        utxos.retain(|utxo| !pending_utxos.contains(&utxo.id));
-        trace!(
-            "Num unspent utxos (not mined and not in mempool): {}",
-            utxos.len()
+        trace!("Selected a total of {} inputs", inputs.len());
+        let (inputs, tip_msa, tip_height) = self.unlock_utxos(inputs).await?;
+        trace!("Managed to unlock {} inputs", inputs.len());
+
+        trace!("Inputs length is: {}", inputs.len());
+        trace!("db_idxs.len() = {}", db_idxs.len());
+        ensure!(
+            inputs.len() == db_idxs.len(),
+            "Inputs and db_idxs must have the same length"
         );
 
-        let utxos = rule.apply(utxos);
-        let unspent: Vec<_> = utxos
+        Ok((inputs, db_idxs, tip_msa, tip_height))
+    }
+
+    /// Selects inputs covering `outputs` and `fee` without unlocking them.
+    ///
+    /// Returns the recovery data of the selected utxos and their database ids.
+    pub async fn select_inputs(
+        &self,
+        outputs: &[(ReceivingAddress, NativeCurrencyAmount)],
+        fee: NativeCurrencyAmount,
+        rule: InputSelectionRule,
+        must_include_inputs: Vec<i64>,
+    ) -> anyhow::Result<(Vec<UtxoRecoveryData>, Vec<i64>)> {
+        let unspent: Vec<_> = self
+            .get_spendable_utxos()
+            .await?
             .into_iter()
             .filter(|utxo| !must_include_inputs.contains(&utxo.id))
             .collect();
@@ -120,42 +179,56 @@ impl super::WalletState {
             .map(|input| input.recovery_data)
             .collect::<Vec<_>>();
 
-        let mut total_input_amount = inputs
+        let preselected_amount = inputs
             .iter()
             .map(|input| input.utxo.get_native_currency_amount().to_nau())
             .sum::<i128>();
 
-        let now = Timestamp::now();
         let mut db_idxs = must_include_inputs.clone();
-        for utxo in unspent {
-            let recovery_data = utxo.recovery_data;
-            if total_input_amount >= total_amount {
-                break;
+        let remaining = total_amount - preselected_amount;
+        if remaining > 0 {
+            for utxo in rule.select(unspent, remaining) {
+                inputs.push(utxo.recovery_data);
+                db_idxs.push(utxo.id);
             }
+        }
 
-            if let Some(release) = recovery_data.utxo.release_date() {
-                if release > now {
-                    continue;
-                }
+        let selected_amount = inputs
+            .iter()
+            .map(|input| input.utxo.get_native_currency_amount())
+            .sum::<NativeCurrencyAmount>();
+        if selected_amount.to_nau() < total_amount {
+            return Err(InsufficientFunds {
+                available: selected_amount,
+                required: NativeCurrencyAmount::from_nau(total_amount),
             }
-
-            total_input_amount += recovery_data.utxo.get_native_currency_amount().to_nau();
-            inputs.push(recovery_data);
-            db_idxs.push(utxo.id);
+            .into());
         }
 
-        trace!("Selected a total of {} inputs", inputs.len());
-        let (inputs, tip_msa, tip_height) = self.unlock_utxos(inputs).await?;
-        trace!("Managed to unlock {} inputs", inputs.len());
+        Ok((inputs, db_idxs))
+    }
 
-        trace!("Inputs length is: {}", inputs.len());
-        trace!("db_idxs.len() = {}", db_idxs.len());
-        ensure!(
-            inputs.len() == db_idxs.len(),
-            "Inputs and db_idxs must have the same length"
+    /// Unspent utxos that are neither timelocked nor used by a pending transaction.
+    pub async fn get_spendable_utxos(&self) -> anyhow::Result<Vec<UtxoDbData>> {
+        let mut utxos = self.get_unspent_utxos().await?;
+        trace!("Num unspent utxos (not mined): {}", utxos.len());
+
+        let pending_utxos = self.updater.get_pending_spent_utxos().await?;
+        utxos.retain(|utxo| !pending_utxos.contains(&utxo.id));
+        trace!(
+            "Num unspent utxos (not mined and not in mempool): {}",
+            utxos.len()
         );
 
-        Ok((inputs, db_idxs, tip_msa, tip_height))
+        let now = Timestamp::now();
+        utxos.retain(|utxo| {
+            utxo.recovery_data
+                .utxo
+                .release_date()
+                .is_none_or(|release| release <= now)
+        });
+
+        Ok(utxos)
     }
 
     /// Returns triple (list of unlocked UTXOs, tip mutator set, tip height)
@@ -163,6 +236,33 @@ impl super::WalletState {
         &self,
         utxos: Vec<UtxoRecoveryData>,
     ) -> anyhow::Result<(Vec<UnlockedUtxo>, MutatorSetAccumulator, BlockHeight)> {
+        let (proofs, tip_msa, tip_height) = self.restore_membership_proofs(utxos).await?;
+
+        let mut unlocked = Vec::with_capacity(proofs.len());
+        for (utxo, membership_proof) in proofs {
+            let spending_key = self
+                .find_spending_key_for_utxo(&utxo.utxo)
+                .context("No spending key found for utxo")?;
+
+            unlocked.push(UnlockedUtxo::unlock(
+                utxo.utxo,
+                spending_key.lock_script_and_witness(),
+                membership_proof,
+            ));
+        }
+
+        Ok((unlocked, tip_msa, tip_height))
+    }
+
+    /// Returns triple (list of utxos with their membership proofs, tip mutator set, tip height)
+    pub async fn restore_membership_proofs(
+        &self,
+        utxos: Vec<UtxoRecoveryData>,
+    ) -> anyhow::Result<(
+        Vec<(UtxoRecoveryData, MsMembershipProof)>,
+        MutatorSetAccumulator,
+        BlockHeight,
+    )> {
         let mut index_sets = Vec::with_capacity(utxos.len());
 
         for utxo in &utxos {
@@ -186,12 +286,8 @@ impl super::WalletState {
             msmps_recovery_data.membership_proofs.len()
         );
 
-        let mut unlocked = Vec::with_capacity(utxos.len());
+        let mut restored = Vec::with_capacity(utxos.len());
         for (recovery_data, utxo) in msmps_recovery_data.membership_proofs.into_iter().zip(utxos) {
-            let spending_key = self
-                .find_spending_key_for_utxo(&utxo.utxo)
-                .context("No spending key found for utxo")?;
-
             let membership_proof = match recovery_data.extract_ms_membership_proof(
                 utxo.aocl_index,
                 utxo.sender_randomness,
@@ -201,15 +297,11 @@ impl super::WalletState {
                 None => bail!("Server returned bad mutator set membership proof recovery data",),
             };
 
-            unlocked.push(UnlockedUtxo::unlock(
-                utxo.utxo,
-                spending_key.lock_script_and_witness(),
-                membership_proof,
-            ));
+            restored.push((utxo, membership_proof));
         }
 
         Ok((
-            unlocked,
+            restored,
             msmps_recovery_data.synced_mutator_set.into(),
             msmps_recovery_data.synced_height.into(),
         ))
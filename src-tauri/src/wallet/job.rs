@@ -0,0 +1,437 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use neptune_cash::api::export::NativeCurrencyAmount;
+use neptune_cash::api::export::ReceivingAddress;
+use neptune_cash::api::export::Timestamp;
+use neptune_cash::api::export::TxProvingCapability;
+use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMedium;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlx::Row;
+use thiserror::Error;
+use tracing::*;
+use utoipa::ToSchema;
+
+use super::input::InputSelectionRule;
+use super::spend::SendError;
+use super::sync::SyncState;
+use super::WalletState;
+use crate::prover::CancellationToken;
+use crate::prover::ProofProgress;
+
+/// A job can not be changed in its current state.
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("job {0} not found")]
+    NotFound(i64),
+    #[error("job {0} already finished")]
+    Finished(i64),
+    #[error("job {0} is already broadcasting")]
+    Broadcasting(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "status", content = "detail")]
+pub enum JobStatus {
+    /// waiting for an earlier send to finish
+    Queued,
+    Running,
+    Succeeded,
+    Failed(String),
+    Cancelled,
+    /// interrupted while broadcasting, the transaction may have reached the
+    /// node, check its txid
+    Unknown(String),
+}
+
+impl JobStatus {
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Succeeded
+                | JobStatus::Failed(_)
+                | JobStatus::Cancelled
+                | JobStatus::Unknown(_)
+        )
+    }
+}
+
+/// Progress of a running job.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStage {
+    SelectingInputs,
+    Proving,
+    /// the transaction may reach the node from here on, it can no longer be
+    /// cancelled
+    Broadcasting,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Job {
+    pub id: i64,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    pub stage: Option<JobStage>,
+    /// known once the transaction is proven
+    pub txid: Option<String>,
+    /// sub-proof being produced while proving
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<ProofProgress>,
+    /// milliseconds since the unix epoch
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Job {
+    fn from_row(row: sqlx::sqlite::SqliteRow) -> Result<Self> {
+        let stage = match row.get::<Option<String>, _>("stage") {
+            Some(stage) => Some(serde_json::from_str(&stage)?),
+            None => None,
+        };
+        Ok(Self {
+            id: row.get("id"),
+            status: serde_json::from_str(&row.get::<String, _>("status"))?,
+            stage,
+            txid: row.get("txid"),
+            progress: None,
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        })
+    }
+}
+
+/// Arguments of [WalletState::send_to_address], persisted as json so queued
+/// jobs survive a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendRequest {
+    pub outputs: Vec<(ReceivingAddress, NativeCurrencyAmount)>,
+    pub fee: NativeCurrencyAmount,
+    pub rule: InputSelectionRule,
+    pub must_include_utxos: Vec<i64>,
+    #[serde(default)]
+    pub proving: Option<TxProvingCapability>,
+}
+
+/// Handle of a job being executed in this process.
+#[derive(Clone)]
+pub struct JobHandle {
+    pub id: i64,
+    pub(super) cancel: CancellationToken,
+    /// latest progress of the proof, not persisted
+    progress: Arc<Mutex<Option<ProofProgress>>>,
+}
+
+impl JobHandle {
+    fn new(id: i64) -> Self {
+        Self {
+            id,
+            cancel: CancellationToken::new(),
+            progress: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub(super) fn set_progress(&self, progress: &ProofProgress) {
+        *self.progress.lock().unwrap() = Some(progress.clone());
+    }
+}
+
+pub(super) struct CreateJobsMigration;
+
+sqlx_migrator::sqlite_migration!(
+    CreateJobsMigration,
+    "wallet_state",
+    "create_jobs",
+    sqlx_migrator::vec_box![],
+    sqlx_migrator::vec_box![(
+        "CREATE TABLE wallet_state_jobs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        request BLOB NOT NULL,
+        status TEXT NOT NULL,
+        stage TEXT,
+        txid TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+        )", //up
+        "DROP TABLE wallet_state_jobs" //down
+    )]
+);
+
+fn now() -> i64 {
+    Timestamp::now().to_millis() as i64
+}
+
+impl WalletState {
+    async fn create_job(&self, request: &SendRequest) -> Result<i64> {
+        let now = now();
+        let id = sqlx::query(
+            "INSERT INTO wallet_state_jobs (request, status, created_at, updated_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(serde_json::to_vec(request)?)
+        .bind(serde_json::to_string(&JobStatus::Queued)?)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(id)
+    }
+
+    pub async fn get_job(&self, id: i64) -> Result<Option<Job>> {
+        let row = sqlx::query("SELECT * FROM wallet_state_jobs WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| self.job_from_row(row)).transpose()
+    }
+
+    pub async fn get_jobs(&self) -> Result<Vec<Job>> {
+        sqlx::query("SELECT * FROM wallet_state_jobs ORDER BY id DESC")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| self.job_from_row(row))
+            .collect()
+    }
+
+    fn job_from_row(&self, row: sqlx::sqlite::SqliteRow) -> Result<Job> {
+        let mut job = Job::from_row(row)?;
+        if job.stage == Some(JobStage::Proving) {
+            job.progress = self
+                .jobs
+                .get(&job.id)
+                .and_then(|handle| handle.progress.lock().unwrap().clone());
+        }
+        Ok(job)
+    }
+
+    async fn get_job_request(&self, id: i64) -> Result<SendRequest> {
+        let row = sqlx::query("SELECT request FROM wallet_state_jobs WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(serde_json::from_slice(&row.get::<Vec<u8>, _>(0))?)
+    }
+
+    async fn set_job_status(&self, id: i64, status: &JobStatus) -> Result<()> {
+        sqlx::query("UPDATE wallet_state_jobs SET status = ?, updated_at = ? WHERE id = ?")
+            .bind(serde_json::to_string(status)?)
+            .bind(now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Finishes a job unless it already is, e.g. because it was cancelled.
+    /// Jobs that reached [JobStage::Broadcasting] can only be finished with
+    /// `allow_broadcasting`.
+    ///
+    /// Returns whether the status was changed.
+    async fn finish_job(
+        &self,
+        id: i64,
+        status: &JobStatus,
+        allow_broadcasting: bool,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE wallet_state_jobs SET status = ?, updated_at = ?
+            WHERE id = ? AND status IN (?, ?) AND (? OR stage IS NOT ?)",
+        )
+        .bind(serde_json::to_string(status)?)
+        .bind(now())
+        .bind(id)
+        .bind(serde_json::to_string(&JobStatus::Queued)?)
+        .bind(serde_json::to_string(&JobStatus::Running)?)
+        .bind(allow_broadcasting)
+        .bind(serde_json::to_string(&JobStage::Broadcasting)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub(super) async fn set_job_txid(&self, job: Option<&JobHandle>, txid: &str) -> Result<()> {
+        let Some(job) = job else {
+            return Ok(());
+        };
+        sqlx::query("UPDATE wallet_state_jobs SET txid = ?, updated_at = ? WHERE id = ?")
+            .bind(txid)
+            .bind(now())
+            .bind(job.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Records the progress of `job`, fails if the job was cancelled
+    /// meanwhile.
+    ///
+    /// The stage is only written while the job is not finished, so a job
+    /// either reaches [JobStage::Broadcasting] or is cancelled, never both.
+    pub(super) async fn set_job_stage(
+        &self,
+        job: Option<&JobHandle>,
+        stage: JobStage,
+    ) -> Result<(), SendError> {
+        let Some(job) = job else {
+            return Ok(());
+        };
+        if job.cancel.is_cancelled() {
+            return Err(SendError::Cancelled);
+        }
+
+        let result = sqlx::query(
+            "UPDATE wallet_state_jobs SET status = ?, stage = ?, updated_at = ?
+            WHERE id = ? AND status IN (?, ?)",
+        )
+        .bind(serde_json::to_string(&JobStatus::Running)?)
+        .bind(serde_json::to_string(&stage)?)
+        .bind(now())
+        .bind(job.id)
+        .bind(serde_json::to_string(&JobStatus::Queued)?)
+        .bind(serde_json::to_string(&JobStatus::Running)?)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(SendError::Cancelled);
+        }
+
+        let _ = crate::service::app::emit_event_to(
+            "main",
+            "job_state",
+            serde_json::json!({ "id": job.id, "stage": stage }),
+        );
+
+        Ok(())
+    }
+
+    /// Cancels a job that has not been broadcast yet.
+    ///
+    /// The job is marked cancelled in the same statement that checks its
+    /// stage, a job that is broadcasting concurrently keeps running.
+    pub async fn cancel_job(&self, id: i64) -> Result<()> {
+        if !self.finish_job(id, &JobStatus::Cancelled, false).await? {
+            let job = self.get_job(id).await?.ok_or(JobError::NotFound(id))?;
+            if job.status.is_final() {
+                return Err(JobError::Finished(id).into());
+            }
+            return Err(JobError::Broadcasting(id).into());
+        }
+
+        // the job stops at its next stage, a queued job once it holds the
+        // spend lock
+        if let Some(handle) = self.jobs.get(&id) {
+            handle.cancel.cancel();
+        }
+
+        Ok(())
+    }
+
+    async fn run_send_job(&self, job: JobHandle) {
+        let result = async {
+            let request = self.get_job_request(job.id).await?;
+            self.send(
+                request.outputs,
+                (
+                    UtxoNotificationMedium::OnChain,
+                    UtxoNotificationMedium::OnChain,
+                ),
+                request.fee,
+                request.rule,
+                request.must_include_utxos,
+                request.proving,
+                Some(&job),
+            )
+            .await
+        }
+        .await;
+
+        let status = match result {
+            Ok(tx) => {
+                info!("job {} sent tx {}", job.id, tx.txid());
+                JobStatus::Succeeded
+            }
+            Err(SendError::Cancelled) => {
+                info!("job {} cancelled", job.id);
+                JobStatus::Cancelled
+            }
+            Err(e) => {
+                error!("job {} failed: {}", job.id, e);
+                JobStatus::Failed(e.to_string())
+            }
+        };
+
+        // a cancelled job keeps its status, even if it failed meanwhile
+        if let Err(e) = self.finish_job(job.id, &status, true).await {
+            error!("failed to update job {}: {}", job.id, e);
+        }
+        self.jobs.remove(&job.id);
+    }
+}
+
+impl SyncState {
+    /// Queues a send and returns the job id, the transaction is built, proven
+    /// and broadcast in the background.
+    pub async fn submit_send_job(self: &Arc<Self>, request: SendRequest) -> Result<i64> {
+        if self.wallet.is_watch_only() {
+            return Err(SendError::WatchOnly.into());
+        }
+
+        let id = self.wallet.create_job(&request).await?;
+        self.spawn_job(id);
+
+        Ok(id)
+    }
+
+    fn spawn_job(self: &Arc<Self>, id: i64) {
+        let handle = JobHandle::new(id);
+        self.wallet.jobs.insert(id, handle.clone());
+
+        let state = self.clone();
+        tokio::spawn(async move {
+            state.wallet.run_send_job(handle).await;
+        });
+    }
+
+    /// Restarts the jobs queued before a restart. Jobs that were already
+    /// running are failed, unless they got to broadcasting: those succeeded
+    /// if their transaction was stored as pending and are unknown otherwise,
+    /// as the node may have received it.
+    pub(super) async fn resume_jobs(self: &Arc<Self>) -> Result<()> {
+        let pending = self.wallet.get_pending_transactions().await?;
+
+        for job in self.wallet.get_jobs().await? {
+            if self.wallet.jobs.contains_key(&job.id) {
+                continue;
+            }
+
+            match job.status {
+                JobStatus::Queued => {
+                    info!("resuming job {}", job.id);
+                    self.spawn_job(job.id);
+                }
+                JobStatus::Running => {
+                    let broadcasting = job.stage == Some(JobStage::Broadcasting);
+                    let status = match &job.txid {
+                        Some(txid) if pending.contains(txid) => JobStatus::Succeeded,
+                        Some(txid) if broadcasting => JobStatus::Unknown(format!(
+                            "interrupted while broadcasting transaction {txid}"
+                        )),
+                        _ => JobStatus::Failed("interrupted by a restart".to_string()),
+                    };
+                    warn!("job {} was interrupted: {:?}", job.id, status);
+                    self.wallet.set_job_status(job.id, &status).await?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -5,14 +5,25 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use neptune_cash::api::export::SpendingKey;
+use neptune_cash::prelude::tasm_lib::prelude::Digest;
 use rayon::prelude::*;
 
+use super::watch_only::WalletKey;
+
 impl super::WalletState {
     pub async fn get_address(&self, index: u64) -> Result<String> {
-        let symmetric_key = self.key.nth_generation_spending_key(index);
-        let spending_key = SpendingKey::from(symmetric_key);
+        let address = match &self.key {
+            WalletKey::Entropy(key) => {
+                SpendingKey::from(key.nth_generation_spending_key(index)).to_address()
+            }
+            WalletKey::WatchOnly(keys) => keys.address(index)?.clone(),
+        };
+
+        address.to_bech32m(self.network)
+    }
 
-        spending_key.to_address().to_bech32m(self.network)
+    pub fn is_watch_only(&self) -> bool {
+        self.key.is_watch_only()
     }
 
     pub fn get_known_spending_keys(&self) -> Vec<SpendingKey> {
@@ -36,6 +47,28 @@ impl super::WalletState {
             .collect()
     }
 
+    /// Like [Self::get_known_spending_keys] but also searches the future key
+    /// window, for wallets that sign without being synced.
+    pub fn find_spending_key_by_lock_script_hash(
+        &self,
+        lock_script_hash: Digest,
+    ) -> Option<SpendingKey> {
+        let generation_keys = self.get_future_generation_spending_keys(Range {
+            start: 0,
+            end: self.num_generation_spending_keys() + self.num_future_keys(),
+        });
+        let symmetric_keys = self.get_future_symmetric_keys(Range {
+            start: 0,
+            end: self.num_symmetric_keys() + self.num_future_keys(),
+        });
+
+        generation_keys
+            .into_iter()
+            .chain(symmetric_keys)
+            .map(|v| *v.1)
+            .find(|k| k.lock_script_hash() == lock_script_hash)
+    }
+
     pub fn num_symmetric_keys(&self) -> u64 {
         self.num_symmetric_keys.load(Ordering::Relaxed)
     }
@@ -48,8 +81,11 @@ impl super::WalletState {
         self.num_future_keys.load(Ordering::Relaxed)
     }
 
+    /// always empty for watch-only wallets
     pub fn get_future_symmetric_keys(&self, range: Range<u64>) -> Vec<(u64, Arc<SpendingKey>)> {
-        let key = &self.key;
+        let WalletKey::Entropy(key) = &self.key else {
+            return vec![];
+        };
         (range.start..range.end)
             .into_par_iter()
             .map(|i| {
@@ -67,7 +103,9 @@ impl super::WalletState {
         &self,
         range: Range<u64>,
     ) -> Vec<(u64, Arc<SpendingKey>)> {
-        let key = &self.key;
+        let WalletKey::Entropy(key) = &self.key else {
+            return vec![];
+        };
         (range.start..range.end)
             .into_par_iter()
             .map(|i| {
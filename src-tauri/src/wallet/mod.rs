@@ -1,15 +1,18 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::ptr::null_mut;
-use std::range::Range;
 use std::sync::atomic::AtomicPtr;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 
 use anyhow::Context;
 use anyhow::Result;
+use dashmap::DashMap;
 use itertools::Itertools;
+use job::JobHandle;
+use neptune_cash::api::export::AdditionRecord;
 use neptune_cash::api::export::Network;
+use neptune_cash::api::export::ReceivingAddress;
 use neptune_cash::api::export::Tip5;
 use neptune_cash::api::export::Utxo;
 use neptune_cash::application::config::data_directory::DataDirectory;
@@ -17,11 +20,11 @@ use neptune_cash::prelude::tasm_lib::prelude::Digest;
 use neptune_cash::protocol::consensus::block::mutator_set_update::MutatorSetUpdate;
 use neptune_cash::protocol::proof_abstractions::mast_hash::MastHash;
 use neptune_cash::state::wallet::incoming_utxo::IncomingUtxo;
-use neptune_cash::state::wallet::wallet_entropy::WalletEntropy;
 use neptune_cash::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
 use neptune_cash::util_types::mutator_set::removal_record::absolute_index_set::AbsoluteIndexSet;
 use pending::TransactionUpdater;
 use rayon::prelude::*;
+use rescan::ScanKeys;
 use serde::Deserialize;
 use serde::Serialize;
 use sqlx::Pool;
@@ -30,31 +33,52 @@ use tracing::*;
 use wallet_file::wallet_dir_by_id;
 use wallet_state_table::UtxoBlockInfo;
 use wallet_state_table::UtxoDbData;
+use watch_only::WalletKey;
 
 use crate::config::wallet::ScanConfig;
 use crate::config::wallet::WalletConfig;
 use crate::config::Config;
+use crate::prover::CancellationToken;
 use crate::wallet::block::WalletBlock;
+use crate::wallet::events::EventBus;
+use crate::wallet::events::WalletEvent;
+use crate::wallet::webhook::WebhookEvent;
 
 // mod archive_state;
 pub mod balance;
+mod coin_selection;
+pub mod consolidate;
+pub mod events;
 pub mod fake_archival_state;
+pub mod fee;
 pub mod fork;
 mod input;
+pub mod job;
 pub use input::InputSelectionRule;
+pub use input::InsufficientFunds;
 pub mod block;
 pub mod block_cache;
 mod key_cache;
 mod keys;
+pub mod offline;
 mod pending;
+pub mod registry;
+pub mod rescan;
 mod spend;
+pub use spend::SendError;
+pub use spend::SendPreview;
+pub mod sweep;
 pub mod sync;
+pub mod tx_status;
+pub mod upgrader;
 pub mod wallet_file;
 mod wallet_state_table;
+pub mod watch_only;
+pub mod webhook;
 
 pub struct WalletState {
-    key: WalletEntropy,
-    scan_config: ScanConfig,
+    key: WalletKey,
+    scan_config: std::sync::Mutex<ScanConfig>,
     pub network: Network,
     num_symmetric_keys: AtomicU64,
     num_generation_spending_keys: AtomicU64,
@@ -65,11 +89,19 @@ pub struct WalletState {
     key_cache: key_cache::KeyCache,
     id: i64,
     spend_lock: tokio::sync::Mutex<()>,
+    /// jobs running in this process
+    jobs: DashMap<i64, JobHandle>,
+    /// cancels the send or re-prove holding `spend_lock`
+    active_send: std::sync::Mutex<Option<CancellationToken>>,
+    /// transactions handed to the proof upgrader
+    upgrades: upgrader::UpgradeQueue,
+    pub events: EventBus,
+    webhooks: webhook::WebhookOutbox,
 }
 
 impl WalletState {
-    pub async fn new_from_config(config: &Config) -> Result<Self> {
-        let wallet_config = config.get_current_wallet().await?;
+    pub async fn new_from_config(config: &Config, id: i64) -> Result<Self> {
+        let wallet_config = config.get_wallet(id).await?;
         let database = Self::wallet_database_path(config, wallet_config.id).await?;
         Self::new(wallet_config, &database).await
     }
@@ -104,11 +136,13 @@ impl WalletState {
 
         let num_future_keys = wallet_config.scan_config.num_keys;
 
-        let updater = TransactionUpdater::new(pool.clone()).await?;
+        let events = EventBus::default();
+        let updater = TransactionUpdater::new(pool.clone(), events.clone()).await?;
+        let webhooks = webhook::WebhookOutbox::new(pool.clone(), wallet_config.id).await?;
 
         let state = Self {
             key: wallet_config.key,
-            scan_config: wallet_config.scan_config,
+            scan_config: std::sync::Mutex::new(wallet_config.scan_config),
             network: wallet_config.network,
             num_symmetric_keys: AtomicU64::new(0),
             num_generation_spending_keys: AtomicU64::new(0),
@@ -119,6 +153,11 @@ impl WalletState {
             key_cache: key_cache::KeyCache::new(),
             id: wallet_config.id,
             spend_lock: tokio::sync::Mutex::new(()),
+            jobs: DashMap::new(),
+            active_send: std::sync::Mutex::new(None),
+            upgrades: upgrader::UpgradeQueue::default(),
+            events,
+            webhooks,
         };
 
         state.migrate_tables().await.context("migrate_tables")?;
@@ -140,15 +179,21 @@ impl WalletState {
         Ok(state)
     }
 
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn scan_config(&self) -> ScanConfig {
+        self.scan_config.lock().unwrap().clone()
+    }
+
     pub async fn start_height(&self) -> Result<u64> {
         if let Some(tip) = self.get_tip().await? {
             return Ok(tip.0 + 1);
         }
-        info!(
-            "new sync, using scan_config height: {}",
-            self.scan_config.start_height
-        );
-        Ok(self.scan_config.start_height)
+        let start_height = self.scan_config().start_height;
+        info!("new sync, using scan_config height: {}", start_height);
+        Ok(start_height)
     }
 
     pub async fn update_new_tip(
@@ -171,10 +216,18 @@ impl WalletState {
                 fork_point.0,
                 fork_point.1.to_hex()
             );
-            self.reorganize_to_height(&mut *tx, fork_point.0, fork_point.1)
+            let mut events = vec![WalletEvent::Reorg {
+                fork_height: fork_point.0,
+            }];
+            self.reorganize_to_height(&mut *tx, fork_point.0, fork_point.1, &mut events)
                 .await
                 .context("reorganize_to_height")?;
             tx.commit().await.context("commit db")?;
+            self.webhooks.wake();
+            for event in events {
+                self.events.publish(event);
+            }
+            self.publish_balance().await;
             return Ok(Some(fork_point.0));
         }
         debug!("update mutator set");
@@ -187,51 +240,25 @@ impl WalletState {
         debug!("get removal_records");
 
         debug!("scan for incoming utxo");
-        let incommings = self.par_scan_for_incoming_utxo(&block).await?;
-        let mut recovery_datas = Vec::with_capacity(incommings.len());
-
-        let incoming = incommings
-            .into_iter()
-            .map(|v| (v.addition_record(), v))
-            .collect::<std::collections::HashMap<_, _>>();
+        let incommings = self
+            .par_scan_for_incoming_utxo(&block, &self.sync_scan_keys())
+            .await?;
 
         debug!("iterate addition records");
-        let mut gusser_preimage = None;
-        for addition_record in &addition_records {
-            if let Some(incoming_utxo) = incoming.get(addition_record) {
-                let r = incoming_utxo_recovery_data_from_incomming_utxo(
-                    incoming_utxo.clone(),
-                    &msa_state,
-                );
-                recovery_datas.push(r);
-
-                if incoming_utxo.is_guesser_fee {
-                    gusser_preimage = Some(incoming_utxo.receiver_preimage);
-                }
-            }
-
-            msa_state.add(addition_record);
-        }
+        let (recovery_datas, gusser_preimage) =
+            recover_incoming_utxos(&mut msa_state, &addition_records, incommings);
 
         debug!("append utxos");
+        let mut events = vec![WalletEvent::NewBlock {
+            height,
+            digest: block.hash().to_hex(),
+        }];
         let mut db_datas = vec![];
+        let mut deposits = vec![];
         for recovery_data in recovery_datas {
-            let digest = Tip5::hash(&recovery_data.utxo);
-            let db_data = UtxoDbData {
-                id: 0,
-                hash: digest.to_hex(),
-                recovery_data,
-                spent_in_block: None,
-                confirmed_in_block: UtxoBlockInfo {
-                    block_height: height,
-                    block_digest: block.hash(),
-                    timestamp: block.kernel.header.timestamp,
-                },
-                spent_height: None,
-                confirm_height: height.try_into()?,
-                confirmed_txid: None,
-                spent_txid: None,
-            };
+            let db_data = UtxoDbData::confirmed_in(recovery_data, block)?;
+            events.push(WalletEvent::received(&db_data));
+            deposits.push(WebhookEvent::deposit(&db_data));
             db_datas.push(db_data);
         }
 
@@ -245,19 +272,21 @@ impl WalletState {
         debug!("scan for spent utxos");
         let spents = self.scan_for_spent_utxos(&block).await?;
 
-        let block_info = UtxoBlockInfo {
-            block_height: block.kernel.header.height.into(),
-            block_digest: block.hash(),
-            timestamp: block.kernel.header.timestamp,
-        };
+        let block_info = UtxoBlockInfo::of(block);
 
         let spent_updates = spents
             .iter()
             .map(|v| (v.2, block_info.clone()))
             .collect_vec();
+        events.extend(spents.iter().map(|(utxo, _, _)| WalletEvent::UtxoSpent {
+            hash: Tip5::hash(utxo).to_hex(),
+            amount: utxo.get_native_currency_amount().display_lossless(),
+            height,
+        }));
 
         debug!("update spent utxos");
-        self.update_spent_utxos(&mut *tx, spent_updates).await?;
+        self.update_spent_utxos(&mut *tx, spent_updates, &mut events)
+            .await?;
 
         debug!("scan for expected utxos");
         // update expected utxo with txid
@@ -275,6 +304,11 @@ impl WalletState {
         self.update_utxos_with_expected_utxos(&mut *tx, expected, height.try_into()?)
             .await?;
 
+        debug!("queue webhooks");
+        self.queue_block_webhooks(&mut *tx, height, deposits)
+            .await
+            .context("queue webhooks")?;
+
         debug!(
             "set tip {} {}",
             block.kernel.header.height.value(),
@@ -284,6 +318,15 @@ impl WalletState {
             .await?;
 
         tx.commit().await?;
+        self.webhooks.wake();
+
+        let balance_changed = events.len() > 1;
+        for event in events {
+            self.events.publish(event);
+        }
+        if balance_changed {
+            self.publish_balance().await;
+        }
 
         self.clean_old_expected_utxos().await?;
 
@@ -295,16 +338,51 @@ impl WalletState {
         Ok(None)
     }
 
+    /// Publishes the current balance, after utxos were received or spent.
+    pub async fn publish_balance(&self) {
+        match self.get_all_balance().await {
+            Ok((available_balance, total_balance)) => {
+                self.events.publish(WalletEvent::BalanceChanged {
+                    available_balance: available_balance.display_lossless(),
+                    total_balance: total_balance.display_lossless(),
+                })
+            }
+            Err(e) => error!("failed to get balance: {:?}", e),
+        }
+    }
+
+    /// Scans `block` for utxos sent to the keys of `scan`, the number of used
+    /// keys is raised to the highest index that received a utxo.
     async fn par_scan_for_incoming_utxo(
         &self,
         block: &WalletBlock,
+        scan: &ScanKeys,
     ) -> anyhow::Result<Vec<IncomingUtxo>> {
         let transaction = &block.kernel.body.transaction_kernel();
 
-        let spendingkeys = self.get_future_generation_spending_keys(Range {
-            start: 0,
-            end: self.num_generation_spending_keys() + self.num_future_keys(),
-        });
+        // watch-only wallets can only scan the keys that were exported to them
+        let watch_only_utxos = match &self.key {
+            WalletKey::WatchOnly(keys) if scan.watch_only => keys
+                .generation_keys
+                .par_iter()
+                .map(|key| (key, &self.num_generation_spending_keys))
+                .chain(
+                    keys.symmetric_keys
+                        .par_iter()
+                        .map(|key| (key, &self.num_symmetric_keys)),
+                )
+                .flat_map(|(key, num_keys)| {
+                    let utxo = key.scan_for_announced_utxos(&transaction);
+                    if utxo.len() > 0 {
+                        num_keys.fetch_max(key.index, Ordering::SeqCst);
+                    }
+                    utxo
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![],
+        };
+
+        let spendingkeys = self.get_future_generation_spending_keys(scan.generation);
 
         let spend_to_spendingkeys = spendingkeys.par_iter().flat_map(|spendingkey| {
             let utxo = spendingkey.1.scan_for_announced_utxos(&transaction);
@@ -318,10 +396,7 @@ impl WalletState {
         self.set_num_generation_spending_keys(self.num_generation_spending_keys())
             .await?;
 
-        let symmetric_keys = self.get_future_symmetric_keys(Range {
-            start: 0,
-            end: self.num_symmetric_keys() + self.num_future_keys(),
-        });
+        let symmetric_keys = self.get_future_symmetric_keys(scan.symmetric);
 
         let spend_to_symmetrickeys = symmetric_keys.par_iter().flat_map(|spendingkey| {
             let utxo = spendingkey.1.scan_for_announced_utxos(&transaction);
@@ -335,11 +410,21 @@ impl WalletState {
         self.set_num_symmetric_keys(self.num_symmetric_keys())
             .await?;
 
-        let own_guesser_key = self.key.guesser_fee_key();
-        let was_guessed_by_us = block
-            .kernel
-            .header
-            .was_guessed_by(&own_guesser_key.to_address().into());
+        let (guesser_address, guesser_preimage): (ReceivingAddress, Digest) = match &self.key {
+            WalletKey::Entropy(key) => {
+                let own_guesser_key = key.guesser_fee_key();
+                (
+                    own_guesser_key.to_address().into(),
+                    own_guesser_key.receiver_preimage(),
+                )
+            }
+            WalletKey::WatchOnly(keys) => (
+                keys.address(keys.guesser_key().index)?.clone(),
+                keys.guesser_key().receiver_preimage,
+            ),
+        };
+        let was_guessed_by_us =
+            scan.guesser && block.kernel.header.was_guessed_by(&guesser_address);
 
         let gusser_incoming_utxos = if was_guessed_by_us {
             let sender_randomness = block.hash();
@@ -351,7 +436,7 @@ impl WalletState {
                 .map(|utxo| IncomingUtxo {
                     utxo,
                     sender_randomness,
-                    receiver_preimage: own_guesser_key.receiver_preimage(),
+                    receiver_preimage: guesser_preimage,
                     is_guesser_fee: true,
                 })
                 .collect_vec()
@@ -362,6 +447,7 @@ impl WalletState {
         let receive = spend_to_spendingkeys
             .chain(spend_to_symmetrickeys)
             .chain(gusser_incoming_utxos)
+            .chain(watch_only_utxos)
             .collect::<Vec<_>>();
 
         Ok(receive)
@@ -446,6 +532,38 @@ impl UtxoRecoveryData {
     }
 }
 
+/// Recovery data of the `incoming` utxos among `addition_records`, and the
+/// guesser preimage if a guesser fee was received. `msa_state` is the mutator
+/// set before the additions and is advanced over all of them.
+fn recover_incoming_utxos(
+    msa_state: &mut MutatorSetAccumulator,
+    addition_records: &[AdditionRecord],
+    incoming: Vec<IncomingUtxo>,
+) -> (Vec<UtxoRecoveryData>, Option<Digest>) {
+    let incoming = incoming
+        .into_iter()
+        .map(|v| (v.addition_record(), v))
+        .collect::<HashMap<_, _>>();
+
+    let mut recovery_datas = Vec::with_capacity(incoming.len());
+    let mut gusser_preimage = None;
+    for addition_record in addition_records {
+        if let Some(incoming_utxo) = incoming.get(addition_record) {
+            let r =
+                incoming_utxo_recovery_data_from_incomming_utxo(incoming_utxo.clone(), msa_state);
+            recovery_datas.push(r);
+
+            if incoming_utxo.is_guesser_fee {
+                gusser_preimage = Some(incoming_utxo.receiver_preimage);
+            }
+        }
+
+        msa_state.add(addition_record);
+    }
+
+    (recovery_datas, gusser_preimage)
+}
+
 fn incoming_utxo_recovery_data_from_incomming_utxo(
     utxo: IncomingUtxo,
     msa_state: &MutatorSetAccumulator,
@@ -0,0 +1,267 @@
+use anyhow::ensure;
+use anyhow::Context;
+use anyhow::Result;
+use itertools::Itertools;
+use neptune_cash::api::export::BlockHeight;
+use neptune_cash::api::export::NativeCurrencyAmount;
+use neptune_cash::api::export::Network;
+use neptune_cash::api::export::ReceivingAddress;
+use neptune_cash::api::export::SpendingKey;
+use neptune_cash::api::export::Timestamp;
+use neptune_cash::api::export::TxProvingCapability;
+use neptune_cash::protocol::consensus::transaction::Transaction;
+use neptune_cash::state::wallet::unlocked_utxo::UnlockedUtxo;
+use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMedium;
+use neptune_cash::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
+use neptune_cash::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
+use num_traits::CheckedSub;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+
+use super::input::InputSelectionRule;
+use super::UtxoRecoveryData;
+use crate::prover::verify_transaction;
+use crate::prover::ProofMonitor;
+use crate::rpc_client;
+
+const UNSIGNED_TX_VERSION: u8 = 1;
+const SIGNED_TX_VERSION: u8 = 1;
+
+/// A transaction with selected inputs and outputs, waiting for lock script
+/// witnesses and a proof from the wallet holding the mnemonic.
+///
+/// All outputs, including change, are notified on chain: the machine that
+/// created the transaction has no way to claim off-chain notifications.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnsignedTransaction {
+    version: u8,
+    pub network: Network,
+    pub inputs: Vec<UnsignedInput>,
+    pub outputs: Vec<(ReceivingAddress, NativeCurrencyAmount)>,
+    pub fee: NativeCurrencyAmount,
+    pub timestamp: Timestamp,
+    pub mutator_set_accumulator: MutatorSetAccumulator,
+    pub tip_height: BlockHeight,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnsignedInput {
+    /// database id of the utxo in the creating wallet
+    pub id: i64,
+    pub recovery_data: UtxoRecoveryData,
+    pub membership_proof: MsMembershipProof,
+}
+
+/// A proven transaction, ready to be broadcast by the wallet that created it.
+///
+/// Only the transaction itself is carried back, the lock script witnesses
+/// never leave the signing machine.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    version: u8,
+    pub network: Network,
+    pub input_ids: Vec<i64>,
+    pub transaction: Transaction,
+}
+
+impl UnsignedTransaction {
+    pub fn encode(&self) -> Result<String> {
+        Ok(hex::encode(bincode::serialize(self)?))
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self> {
+        let bytes = hex::decode(encoded.trim()).context("unsigned transaction is not valid hex")?;
+        let tx: Self = bincode::deserialize(&bytes).context("invalid unsigned transaction")?;
+        ensure!(
+            tx.version == UNSIGNED_TX_VERSION,
+            "unsupported unsigned transaction version {}",
+            tx.version
+        );
+        Ok(tx)
+    }
+
+    pub fn input_amount(&self) -> NativeCurrencyAmount {
+        self.inputs
+            .iter()
+            .map(|input| input.recovery_data.utxo.get_native_currency_amount())
+            .sum()
+    }
+
+    /// Amount returned to the signing wallet, `None` if the inputs do not
+    /// cover the outputs and the fee.
+    pub fn change_amount(&self) -> Option<NativeCurrencyAmount> {
+        let spent = self
+            .outputs
+            .iter()
+            .map(|(_, amount)| *amount)
+            .sum::<NativeCurrencyAmount>()
+            + self.fee;
+        self.input_amount().checked_sub(&spent)
+    }
+}
+
+impl SignedTransaction {
+    pub fn encode(&self) -> Result<String> {
+        Ok(hex::encode(bincode::serialize(self)?))
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self> {
+        let bytes = hex::decode(encoded.trim()).context("signed transaction is not valid hex")?;
+        let tx: Self = bincode::deserialize(&bytes).context("invalid signed transaction")?;
+        ensure!(
+            tx.version == SIGNED_TX_VERSION,
+            "unsupported signed transaction version {}",
+            tx.version
+        );
+        Ok(tx)
+    }
+}
+
+impl super::WalletState {
+    /// Selects inputs and fetches their membership proofs, without touching
+    /// any spending key. Works for watch-only wallets.
+    pub async fn create_unsigned_transaction(
+        &self,
+        outputs: Vec<(ReceivingAddress, NativeCurrencyAmount)>,
+        fee: NativeCurrencyAmount,
+        rule: InputSelectionRule,
+        must_include_utxos: Vec<i64>,
+    ) -> Result<UnsignedTransaction> {
+        let _spend_guard = self.spend_lock.lock().await;
+
+        let (inputs, db_ids) = self
+            .select_inputs(&outputs, fee, rule, must_include_utxos)
+            .await?;
+
+        let (proofs, tip_msa, tip_height) = self.restore_membership_proofs(inputs).await?;
+        ensure!(
+            proofs.len() == db_ids.len(),
+            "Inputs and db_idxs must have the same length"
+        );
+
+        let inputs = proofs
+            .into_iter()
+            .zip(db_ids)
+            .map(|((recovery_data, membership_proof), id)| UnsignedInput {
+                id,
+                recovery_data,
+                membership_proof,
+            })
+            .collect_vec();
+
+        Ok(UnsignedTransaction {
+            version: UNSIGNED_TX_VERSION,
+            network: self.network,
+            inputs,
+            outputs,
+            fee,
+            timestamp: Timestamp::now(),
+            mutator_set_accumulator: tip_msa,
+            tip_height,
+        })
+    }
+
+    /// Attaches lock script witnesses and produces a `ProofCollection`.
+    ///
+    /// Does not need a node connection, so it can run on an air-gapped machine.
+    pub async fn sign_unsigned_transaction(
+        &self,
+        unsigned: UnsignedTransaction,
+    ) -> Result<SignedTransaction> {
+        ensure!(
+            unsigned.network == self.network,
+            "transaction is for network {}, wallet is on {}",
+            unsigned.network,
+            self.network
+        );
+        ensure!(
+            unsigned.change_amount().is_some(),
+            "inputs do not cover the outputs and fee"
+        );
+
+        let change_key = SpendingKey::Symmetric(self.key.entropy()?.nth_symmetric_key(0));
+
+        let mut input_ids = Vec::with_capacity(unsigned.inputs.len());
+        let mut tx_inputs = Vec::with_capacity(unsigned.inputs.len());
+        for input in unsigned.inputs {
+            let spending_key = self
+                .find_spending_key_by_lock_script_hash(input.recovery_data.utxo.lock_script_hash())
+                .context("No spending key found for utxo")?;
+
+            input_ids.push(input.id);
+            tx_inputs.push(UnlockedUtxo::unlock(
+                input.recovery_data.utxo,
+                spending_key.lock_script_and_witness(),
+                input.membership_proof,
+            ));
+        }
+
+        let tx_outputs = self
+            .generate_tx_outputs(
+                unsigned.outputs,
+                UtxoNotificationMedium::OnChain,
+                UtxoNotificationMedium::OnChain,
+                unsigned.tip_height,
+            )
+            .await?;
+
+        let (transaction, _, _) = self
+            .create_transaction_with_prover_capability(
+                tx_outputs,
+                tx_inputs,
+                change_key,
+                UtxoNotificationMedium::OnChain,
+                unsigned.fee,
+                unsigned.timestamp,
+                TxProvingCapability::ProofCollection,
+                unsigned.mutator_set_accumulator,
+                unsigned.tip_height,
+                &ProofMonitor::default(),
+            )
+            .await?;
+
+        info!("signed offline transaction {}", transaction.txid());
+
+        Ok(SignedTransaction {
+            version: SIGNED_TX_VERSION,
+            network: self.network,
+            input_ids,
+            transaction,
+        })
+    }
+
+    /// Broadcasts a transaction signed elsewhere and marks its inputs as pending.
+    ///
+    /// The transaction is not re-proven on new blocks, it has to be signed again
+    /// if the node rejects it.
+    pub async fn broadcast_signed_transaction(&self, signed: SignedTransaction) -> Result<String> {
+        ensure!(
+            signed.network == self.network,
+            "transaction is for network {}, wallet is on {}",
+            signed.network,
+            self.network
+        );
+
+        let inputs = self.get_unspent_inputs_with_ids(&signed.input_ids).await?;
+        let spent = &signed.transaction.kernel.inputs;
+        ensure!(
+            inputs.len() == spent.len()
+                && inputs.iter().all(|input| spent
+                    .iter()
+                    .any(|rr| rr.absolute_indices == input.recovery_data.abs_i())),
+            "transaction does not spend the inputs it was created with"
+        );
+        verify_transaction(&signed.transaction, self.network).await?;
+
+        let txid = rpc_client::node_rpc_client()
+            .broadcast_transaction(&signed.transaction)
+            .await?;
+
+        self.updater
+            .add_external_transaction(txid.clone(), signed.input_ids)
+            .await?;
+
+        Ok(txid)
+    }
+}
@@ -1,8 +1,14 @@
+use anyhow::anyhow;
 use anyhow::Result;
+use neptune_cash::api::export::NativeCurrencyAmount;
+use neptune_cash::api::export::SpendingKey;
 use neptune_cash::api::export::Timestamp;
 use neptune_cash::api::export::TransactionDetails;
 use neptune_cash::api::export::TxProvingCapability;
+use neptune_cash::protocol::consensus::transaction::Transaction;
 use neptune_cash::state::wallet::expected_utxo::UtxoNotifier;
+use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMedium;
+use num_traits::CheckedSub;
 use sqlx::Row;
 use sqlx::SqliteConnection;
 use sqlx::SqlitePool;
@@ -12,7 +18,16 @@ use sqlx_migrator::Migrator;
 use sqlx_migrator::Plan;
 use tracing::*;
 
+use super::events::EventBus;
+use super::events::WalletEvent;
+use super::spend::SendError;
+use super::tx_status::CreateTxStatusMigration;
+use super::tx_status::TransactionStatus;
+use super::tx_status::TxState;
+use super::wallet_state_table::ExpectedUtxoData;
 use super::WalletState;
+use crate::prover::ProofCancelled;
+use crate::prover::ProofMonitor;
 use crate::rpc_client;
 
 impl super::WalletState {
@@ -24,6 +39,131 @@ impl super::WalletState {
     pub async fn forget_tx(&self, txid: &str) -> Result<()> {
         self.updater.delete_transaction(txid).await
     }
+
+    pub async fn get_transaction_statuses(&self) -> Result<Vec<TransactionStatus>> {
+        self.updater.get_tx_statuses().await
+    }
+
+    /// Re-proves a pending transaction with a higher fee and replaces it.
+    ///
+    /// The difference is taken from the change output, the inputs and the
+    /// other outputs stay the same. Returns the replacing transaction.
+    pub async fn bump_fee(
+        &self,
+        txid: &str,
+        new_fee: NativeCurrencyAmount,
+    ) -> Result<Transaction, SendError> {
+        if self.is_watch_only() {
+            return Err(SendError::WatchOnly);
+        }
+
+        let _spend_guard = self.spend_lock.lock().await;
+
+        let (detail, input_ids, proving) = self
+            .updater
+            .get_transaction(txid)
+            .await?
+            .ok_or_else(|| SendError::UnknownTransaction(txid.to_string()))?;
+
+        if new_fee <= detail.fee {
+            return Err(SendError::FeeNotIncreased(detail.fee));
+        }
+        let increase = new_fee
+            .checked_sub(&detail.fee)
+            .ok_or_else(|| anyhow!("overflow subtracting old fee"))?;
+
+        // change always goes to the first symmetric key, see send_to_address
+        let change_key = SpendingKey::Symmetric(self.key.entropy()?.nth_symmetric_key(0));
+        let mut tx_outputs = detail.tx_outputs;
+        let change_index = tx_outputs
+            .iter()
+            .rposition(|output| output.utxo().lock_script_hash() == change_key.lock_script_hash())
+            .ok_or_else(|| anyhow!("transaction has no change output to pay the higher fee"))?;
+
+        let change = &tx_outputs[change_index];
+        let change_amount = change
+            .native_currency_amount()
+            .checked_sub(&increase)
+            .filter(|amount| *amount > NativeCurrencyAmount::coins(0))
+            .ok_or_else(|| {
+                anyhow!(
+                    "change output {} does not cover the fee increase {increase}",
+                    change.native_currency_amount()
+                )
+            })?;
+        let change_medium = if change.is_offchain() {
+            UtxoNotificationMedium::OffChain
+        } else {
+            UtxoNotificationMedium::OnChain
+        };
+
+        let mut recovery_data_list = Vec::with_capacity(detail.tx_inputs.len());
+        for tx_input in detail.tx_inputs.iter() {
+            recovery_data_list.push(self.get_recovery_data_from_utxo(&tx_input.utxo).await?);
+        }
+        let (tx_inputs, tip_msa, tip_height) = self.unlock_utxos(recovery_data_list).await?;
+
+        tx_outputs[change_index] = self
+            .create_change_output(change_amount, change_key, change_medium, tip_height)
+            .await?;
+        for tx_output in tx_outputs.iter_mut() {
+            let sender_randomness = self
+                .key
+                .entropy()?
+                .generate_sender_randomness(tip_height, tx_output.receiver_digest());
+            tx_output.set_sender_randomness(sender_randomness);
+        }
+
+        let now = Timestamp::now();
+        let expected_utxos = self.extract_expected_utxos(&tx_outputs, UtxoNotifier::Myself);
+        let transaction_details = TransactionDetails::new_without_coinbase(
+            tx_inputs,
+            tx_outputs,
+            new_fee,
+            now,
+            tip_msa,
+            self.network,
+        );
+
+        info!("bumping fee of {txid} from {} to {new_fee}", detail.fee);
+        let (monitor, _active_proof) = self.register_active_proof();
+        let transaction = match self
+            .create_raw_transaction(&transaction_details, proving, &monitor)
+            .await
+        {
+            Err(e) if e.is::<ProofCancelled>() => return Err(SendError::Cancelled),
+            result => result?,
+        };
+
+        let new_txid = rpc_client::node_rpc_client()
+            .broadcast_transaction(&transaction)
+            .await?;
+        if proving == TxProvingCapability::ProofCollection {
+            self.queue_own_upgrade(&transaction).await;
+        }
+
+        let expected_utxos = expected_utxos
+            .into_iter()
+            .map(|expected_utxo| ExpectedUtxoData {
+                id: 0,
+                txid: new_txid.clone(),
+                expected_utxo,
+                timestamp: now,
+            })
+            .collect();
+        self.updater
+            .replace_transaction(
+                txid,
+                new_txid,
+                transaction_details,
+                input_ids,
+                expected_utxos,
+                proving,
+            )
+            .await?;
+
+        Ok(transaction)
+    }
 }
 
 struct CreatePendingTxMigration;
@@ -61,14 +201,37 @@ sqlx_migrator::sqlite_migration!(
     )]
 );
 
+struct AddPendingTxProvingMigration;
+
+sqlx_migrator::sqlite_migration!(
+    AddPendingTxProvingMigration,
+    "wallet_state",
+    "add_pending_tx_proving",
+    sqlx_migrator::vec_box![],
+    sqlx_migrator::vec_box![(
+        "ALTER TABLE wallet_state_pending ADD COLUMN proving TEXT", //up
+        "ALTER TABLE wallet_state_pending DROP COLUMN proving"      //down
+    )]
+);
+
+/// Capability a pending transaction was proven with, rows stored before it
+/// was recorded were proof collections.
+fn pending_tx_proving(row: &sqlx::sqlite::SqliteRow) -> Result<TxProvingCapability> {
+    match row.get::<Option<String>, _>("proving") {
+        Some(proving) => Ok(serde_json::from_str(&proving)?),
+        None => Ok(TxProvingCapability::ProofCollection),
+    }
+}
+
 pub struct TransactionUpdater {
-    pool: SqlitePool,
+    pub(super) pool: SqlitePool,
+    pub(super) events: EventBus,
 }
 
 // upgrade transaction after new block
 impl TransactionUpdater {
-    pub async fn new(pool: SqlitePool) -> anyhow::Result<Self> {
-        let updater = Self { pool };
+    pub async fn new(pool: SqlitePool, events: EventBus) -> anyhow::Result<Self> {
+        let updater = Self { pool, events };
 
         updater.migrate_tables().await?;
         Ok(updater)
@@ -80,6 +243,8 @@ impl TransactionUpdater {
         // Adding migrations add its parents, replaces and not before as well
         migrator.add_migration(Box::new(CreatePendingTxMigration))?;
         migrator.add_migration(Box::new(CreatePendingTxDbIdsMigration))?;
+        migrator.add_migration(Box::new(CreateTxStatusMigration))?;
+        migrator.add_migration(Box::new(AddPendingTxProvingMigration))?;
 
         let mut conn = self.pool.acquire().await?;
         // use apply all to apply all pending migration
@@ -106,19 +271,45 @@ impl TransactionUpdater {
             }
         };
 
-        for (txid, transaction, _) in transactions {
+        // the caller holds the spend lock, cancelling the send stops the
+        // re-proves
+        let (monitor, _active_proof) = wallet_state.register_active_proof();
+        for (txid, transaction, _, proving) in transactions {
+            if wallet_state.upgrades.is_upgrading(&txid) {
+                info!("transaction {} is kept up to date by the upgrader", txid);
+                continue;
+            }
             info!("updating transaction {}", txid);
             match self
-                .update_transaction(txid.to_owned(), wallet_state, transaction)
+                .update_transaction(
+                    txid.to_owned(),
+                    wallet_state,
+                    transaction,
+                    proving,
+                    &monitor,
+                )
                 .await
             {
                 Ok(detail) => {
                     if let Err(e) = self.update_detail(&txid, &detail).await {
                         error!("Error updating transaction: {}", e);
                     }
+                    if let Err(e) = self.record_broadcast_attempt(&txid, None).await {
+                        error!("Error updating transaction status: {}", e);
+                    }
+                }
+                Err(err) if err.is::<ProofCancelled>() => {
+                    info!("updating transactions cancelled at {}", txid);
+                    break;
                 }
                 Err(err) => {
                     error!("error update transaction {} : {:#?}", txid, err);
+                    if let Err(e) = self
+                        .record_broadcast_attempt(&txid, Some(err.to_string()))
+                        .await
+                    {
+                        error!("Error updating transaction status: {}", e);
+                    }
                 }
             };
         }
@@ -130,6 +321,8 @@ impl TransactionUpdater {
         tx_id: String,
         wallet_state: &WalletState,
         detail: TransactionDetails,
+        proving: TxProvingCapability,
+        monitor: &ProofMonitor,
     ) -> Result<TransactionDetails> {
         info!("update transaction {}", tx_id);
         let tx_inputs = detail.tx_inputs;
@@ -151,6 +344,7 @@ impl TransactionUpdater {
         for tx_output in tx_outputs.iter_mut() {
             let new_sender_randomness = wallet_state
                 .key
+                .entropy()?
                 .generate_sender_randomness(tip_height, tx_output.receiver_digest());
             tx_output.set_sender_randomness(new_sender_randomness);
         }
@@ -171,12 +365,15 @@ impl TransactionUpdater {
         );
 
         let transaction = wallet_state
-            .create_raw_transaction(&transaction_details, TxProvingCapability::ProofCollection)
+            .create_raw_transaction(&transaction_details, proving, monitor)
             .await?;
 
         let _new_id = rpc_client::node_rpc_client()
             .broadcast_transaction(&transaction)
             .await?;
+        if proving == TxProvingCapability::ProofCollection {
+            wallet_state.queue_own_upgrade(&transaction).await;
+        }
 
         Ok(transaction_details)
     }
@@ -186,14 +383,16 @@ impl TransactionUpdater {
         tx_id: String,
         detail: TransactionDetails,
         input_ids: Vec<i64>,
+        proving: TxProvingCapability,
     ) -> Result<()> {
         let mut conn = self.pool.begin().await?;
 
         let detail = bincode::serialize(&detail)?;
 
-        sqlx::query("INSERT INTO wallet_state_pending (id, details) VALUES (?, ?)")
+        sqlx::query("INSERT INTO wallet_state_pending (id, details, proving) VALUES (?, ?, ?)")
             .bind(&tx_id)
             .bind(&detail)
+            .bind(serde_json::to_string(&proving)?)
             .execute(&mut *conn)
             .await?;
 
@@ -210,6 +409,124 @@ impl TransactionUpdater {
         Ok(())
     }
 
+    /// Marks inputs as spent by a transaction proven outside of this wallet.
+    ///
+    /// No details are stored, so such transactions are not updated on new blocks.
+    pub async fn add_external_transaction(&self, tx_id: String, input_ids: Vec<i64>) -> Result<()> {
+        let mut conn = self.pool.begin().await?;
+
+        for utxo_id in input_ids {
+            sqlx::query("INSERT INTO wallet_state_pending_ids (txid, utxo_id) VALUES (?, ?)")
+                .bind(&tx_id)
+                .bind(&utxo_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        let mut events = vec![];
+        self.create_tx_status(&mut *conn, Some(&tx_id), &TxState::InMempool, &mut events)
+            .await?;
+
+        conn.commit().await?;
+        for event in events {
+            self.events.publish(event);
+        }
+
+        Ok(())
+    }
+
+    /// Details and input ids of an unfinished pending transaction.
+    pub async fn get_transaction(
+        &self,
+        tx_id: &str,
+    ) -> Result<Option<(TransactionDetails, Vec<i64>, TxProvingCapability)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let Some(row) =
+            sqlx::query("SELECT * FROM wallet_state_pending WHERE id = ? AND finished = 0")
+                .bind(tx_id)
+                .fetch_optional(&mut *conn)
+                .await?
+        else {
+            return Ok(None);
+        };
+        let detail = bincode::deserialize::<TransactionDetails>(&row.get::<Vec<u8>, _>("details"))?;
+        let proving = pending_tx_proving(&row)?;
+
+        let input_ids = sqlx::query("SELECT utxo_id FROM wallet_state_pending_ids WHERE txid = ?")
+            .bind(tx_id)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| row.get::<i64, _>(0))
+            .collect::<Vec<_>>();
+
+        Ok(Some((detail, input_ids, proving)))
+    }
+
+    /// Replaces a pending transaction, its input ids and expected utxos in a
+    /// single database transaction.
+    pub async fn replace_transaction(
+        &self,
+        old_tx_id: &str,
+        tx_id: String,
+        detail: TransactionDetails,
+        input_ids: Vec<i64>,
+        expected_utxos: Vec<ExpectedUtxoData>,
+        proving: TxProvingCapability,
+    ) -> Result<()> {
+        let mut conn = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM wallet_state_pending WHERE id = ?")
+            .bind(old_tx_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM wallet_state_pending_ids WHERE txid = ?")
+            .bind(old_tx_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM wallet_state_expected_utxos WHERE txid = ?")
+            .bind(old_tx_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let detail = bincode::serialize(&detail)?;
+        sqlx::query("INSERT INTO wallet_state_pending (id, details, proving) VALUES (?, ?, ?)")
+            .bind(&tx_id)
+            .bind(&detail)
+            .bind(serde_json::to_string(&proving)?)
+            .execute(&mut *conn)
+            .await?;
+        for utxo_id in input_ids {
+            sqlx::query("INSERT INTO wallet_state_pending_ids (txid, utxo_id) VALUES (?, ?)")
+                .bind(&tx_id)
+                .bind(&utxo_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        for expected in expected_utxos {
+            expected.create(&mut *conn).await?;
+        }
+
+        let mut events = vec![];
+        self.set_tx_state_by_txid(
+            &mut *conn,
+            old_tx_id,
+            &TxState::Replaced(tx_id.clone()),
+            &mut events,
+        )
+        .await?;
+        self.create_tx_status(&mut *conn, Some(&tx_id), &TxState::InMempool, &mut events)
+            .await?;
+
+        conn.commit().await?;
+        for event in events {
+            self.events.publish(event);
+        }
+
+        Ok(())
+    }
+
     async fn update_detail(&self, tx_id: &str, detail: &TransactionDetails) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
 
@@ -226,7 +543,7 @@ impl TransactionUpdater {
     pub async fn get_pending_transactions<'c>(
         &self,
         tx: &'c mut SqliteConnection,
-    ) -> Result<Vec<(String, TransactionDetails, Vec<i64>)>> {
+    ) -> Result<Vec<(String, TransactionDetails, Vec<i64>, TxProvingCapability)>> {
         let rows = sqlx::query("SELECT * FROM wallet_state_pending WHERE finished = 0")
             .fetch_all(&mut *tx)
             .await?;
@@ -236,6 +553,7 @@ impl TransactionUpdater {
             let txid = row.get::<String, _>("id");
             let detail = row.get::<Vec<u8>, _>("details");
             let detail = bincode::deserialize::<TransactionDetails>(&detail)?;
+            let proving = pending_tx_proving(&row)?;
 
             let spent_utxos =
                 sqlx::query("SELECT utxo_id FROM wallet_state_pending_ids WHERE txid = ?")
@@ -246,14 +564,14 @@ impl TransactionUpdater {
                     .map(|row| row.get::<i64, _>(0))
                     .collect::<Vec<_>>();
 
-            result.push((txid, detail, spent_utxos));
+            result.push((txid, detail, spent_utxos, proving));
         }
 
         Ok(result)
     }
 
     pub async fn delete_transaction(&self, tx_id: &str) -> Result<()> {
-        let mut conn = self.pool.acquire().await?;
+        let mut conn = self.pool.begin().await?;
 
         sqlx::query("DELETE FROM wallet_state_pending WHERE id = ?")
             .bind(tx_id)
@@ -265,6 +583,15 @@ impl TransactionUpdater {
             .execute(&mut *conn)
             .await?;
 
+        let mut events = vec![];
+        self.set_tx_state_by_txid(&mut *conn, tx_id, &TxState::Forgotten, &mut events)
+            .await?;
+
+        conn.commit().await?;
+        for event in events {
+            self.events.publish(event);
+        }
+
         Ok(())
     }
 
@@ -331,6 +658,7 @@ impl TransactionUpdater {
         &self,
         tx: &'c mut SqliteConnection,
         utxoid: Vec<i64>,
+        events: &mut Vec<WalletEvent>,
     ) -> Result<()> {
         let transactions = match self.get_pending_transactions(&mut *tx).await {
             Ok(transactions) => transactions,
@@ -353,6 +681,10 @@ impl TransactionUpdater {
                     .bind(&txid)
                     .execute(&mut *tx)
                     .await?;
+
+                let reason = "inputs reverted by a reorganization".to_string();
+                self.set_tx_state_by_txid(&mut *tx, &txid, &TxState::Failed(reason), events)
+                    .await?;
             }
         }
 
@@ -0,0 +1,182 @@
+//! Wallets synced by this process.
+//!
+//! Every synced wallet runs its own [SyncState] on top of one shared
+//! [FakeArchivalState], blocks are requested once and scanned by each wallet.
+//! The wallet selected in the app is managed as `Arc<SyncState>` as well and
+//! is always synced, the others are chosen by
+//! [Config::get_synced_wallets].
+
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::Result;
+use dashmap::DashMap;
+use tokio::sync::Mutex;
+use tracing::*;
+
+use super::fake_archival_state::FakeArchivalState;
+use super::sync::SyncState;
+use crate::config::Config;
+
+tokio::task_local! {
+    static SCOPED_WALLET: Arc<SyncState>;
+}
+
+/// Sync state of the wallet the running request is scoped to, by
+/// [with_wallet], or of the current wallet.
+pub fn current_sync_state() -> Arc<SyncState> {
+    SCOPED_WALLET.try_with(Arc::clone).unwrap_or_else(|_| {
+        crate::service::get_state::<Arc<SyncState>>()
+            .inner()
+            .clone()
+    })
+}
+
+/// Runs `f` with [current_sync_state] returning `wallet`.
+pub async fn with_wallet<F: Future>(wallet: Arc<SyncState>, f: F) -> F::Output {
+    SCOPED_WALLET.scope(wallet, f).await
+}
+
+pub struct WalletRegistry {
+    fake_archival_state: FakeArchivalState,
+    wallets: DashMap<i64, Arc<SyncState>>,
+    /// serializes opening and closing wallets
+    lock: Mutex<()>,
+}
+
+impl WalletRegistry {
+    /// Syncs the current wallet and the wallets selected in the config.
+    pub async fn start(config: &Config) -> Result<Arc<Self>> {
+        let registry = Arc::new(Self {
+            fake_archival_state: FakeArchivalState::new_from_config(config).await?,
+            wallets: DashMap::new(),
+            lock: Mutex::new(()),
+        });
+
+        let current = registry.open(config, config.get_wallet_id().await?).await?;
+        crate::service::manage_or_replace(current);
+
+        for id in Self::selected_wallets(config).await? {
+            if let Err(e) = registry.open(config, id).await {
+                error!("failed to open wallet {}: {:?}", id, e);
+            }
+        }
+
+        Ok(registry)
+    }
+
+    /// Stops the running registry and starts syncing again, e.g. after the
+    /// network changed.
+    pub async fn restart(config: &Config) -> Result<Arc<Self>> {
+        if let Some(old) = crate::service::try_get_state::<Arc<Self>>() {
+            old.stop().await;
+        }
+        let registry = Self::start(config).await?;
+        crate::service::manage_or_replace(registry.clone());
+        Ok(registry)
+    }
+
+    /// Makes `id` the current wallet, the previous one keeps syncing only if
+    /// it is selected.
+    pub async fn switch_to(&self, config: &Config, id: i64) -> Result<()> {
+        let previous = crate::service::try_get_state::<Arc<SyncState>>().map(|s| s.wallet.id());
+
+        let current = self.open(config, id).await?;
+        crate::service::manage_or_replace(current);
+
+        if let Some(previous) = previous.filter(|previous| *previous != id) {
+            if !Self::is_selected(config, previous).await? {
+                self.close(previous).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies a changed [Config::get_synced_wallets], opens newly selected
+    /// wallets and closes those no longer selected, except the current one.
+    pub async fn update_selection(&self, config: &Config) -> Result<()> {
+        let current = config.get_wallet_id().await?;
+        let selected = Self::selected_wallets(config).await?;
+
+        for state in self.wallets() {
+            let id = state.wallet.id();
+            if id != current && !selected.contains(&id) {
+                self.close(id).await;
+            }
+        }
+        for id in selected {
+            self.open(config, id).await?;
+        }
+        Ok(())
+    }
+
+    async fn selected_wallets(config: &Config) -> Result<Vec<i64>> {
+        let ids = config.get_wallet_ids().await?;
+        Ok(match config.get_synced_wallets().await? {
+            Some(selected) => ids.into_iter().filter(|id| selected.contains(id)).collect(),
+            None => ids,
+        })
+    }
+
+    /// Whether the wallet is synced when it is not the current one.
+    pub async fn is_selected(config: &Config, id: i64) -> Result<bool> {
+        Ok(match config.get_synced_wallets().await? {
+            Some(selected) => selected.contains(&id),
+            None => true,
+        })
+    }
+
+    /// Returns the sync state of the wallet, starting its sync if it is not
+    /// running yet.
+    pub async fn open(&self, config: &Config, id: i64) -> Result<Arc<SyncState>> {
+        let _guard = self.lock.lock().await;
+        if let Some(state) = self.get(id) {
+            return Ok(state);
+        }
+
+        info!("start syncing wallet {}", id);
+        let state = Arc::new(SyncState::new(config, id, self.fake_archival_state.clone()).await?);
+        self.wallets.insert(id, state.clone());
+        state.clone().sync().await;
+        Ok(state)
+    }
+
+    /// Stops syncing the wallet.
+    pub async fn close(&self, id: i64) {
+        let _guard = self.lock.lock().await;
+        if let Some((_, state)) = self.wallets.remove(&id) {
+            info!("stop syncing wallet {}", id);
+            state.cancel_sync().await;
+        }
+    }
+
+    pub fn get(&self, id: i64) -> Option<Arc<SyncState>> {
+        self.wallets.get(&id).map(|state| state.clone())
+    }
+
+    /// Synced wallets ordered by id.
+    pub fn wallets(&self) -> Vec<Arc<SyncState>> {
+        let mut wallets = self
+            .wallets
+            .iter()
+            .map(|state| state.clone())
+            .collect::<Vec<_>>();
+        wallets.sort_by_key(|state| state.wallet.id());
+        wallets
+    }
+
+    /// Stops syncing every wallet.
+    pub async fn stop(&self) {
+        let _guard = self.lock.lock().await;
+        let ids = self
+            .wallets
+            .iter()
+            .map(|state| *state.key())
+            .collect::<Vec<_>>();
+        for id in ids {
+            if let Some((_, state)) = self.wallets.remove(&id) {
+                state.cancel_sync().await;
+            }
+        }
+    }
+}
@@ -0,0 +1,623 @@
+//! Rescans of synced blocks for keys that were not scanned at the time.
+//!
+//! Blocks are read through the [FakeArchivalState] of the sync, so cached
+//! blocks are reused. Only utxos missing from `wallet_state_utxos` are added,
+//! the rows of the sync are kept, and the added utxos are checked against the
+//! inputs of the later blocks up to the wallet tip to fix up their spent
+//! status.
+//!
+//! [FakeArchivalState]: super::fake_archival_state::FakeArchivalState
+
+use std::range::Range;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use itertools::Itertools;
+use neptune_cash::protocol::consensus::block::mutator_set_update::MutatorSetUpdate;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlx::SqliteConnection;
+use thiserror::Error;
+use tracing::*;
+use utoipa::ToSchema;
+
+use super::events::WalletEvent;
+use super::recover_incoming_utxos;
+use super::sync::SyncState;
+use super::sync::SYNC_BLOCK_BATCH_SIZE;
+use super::wallet_state_table::UtxoBlockInfo;
+use super::wallet_state_table::UtxoDbData;
+use super::WalletState;
+use crate::config::wallet::ScanConfig;
+use crate::config::Config;
+
+/// Parameters of a rescan that can not be applied.
+#[derive(Debug, Error)]
+pub enum RescanError {
+    #[error("the key window can only be widened and the start height only lowered")]
+    ScanConfigNarrowed,
+    #[error("the height range is empty")]
+    EmptyHeightRange,
+    #[error("the key index range is empty")]
+    EmptyKeyRange,
+}
+
+/// Keys scanned for incoming utxos.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanKeys {
+    /// derivation indexes of the generation keys
+    pub generation: Range<u64>,
+    /// derivation indexes of the symmetric keys
+    pub symmetric: Range<u64>,
+    /// guesser fees of the blocks guessed by the wallet
+    pub guesser: bool,
+    /// keys exported to a watch-only wallet
+    pub watch_only: bool,
+}
+
+/// Keys looked for by [SyncState::rescan], the keys of the sync if empty.
+///
+/// Watch-only wallets always scan all exported keys.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct KeyFilter {
+    /// first derivation index of the keys to scan
+    pub index_start: Option<u64>,
+    /// derivation index after the last key to scan, may be beyond the window
+    /// of the sync, e.g. for imported keys. Defaults to the end of the window,
+    /// or `index_start` plus the number of future keys if that is further.
+    pub index_end: Option<u64>,
+    /// skip generation keys
+    #[serde(default)]
+    pub skip_generation: bool,
+    /// skip symmetric keys
+    #[serde(default)]
+    pub skip_symmetric: bool,
+    /// skip guesser fees
+    #[serde(default)]
+    pub skip_guesser: bool,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct RescanResult {
+    /// blocks read by the rescan
+    pub blocks: u64,
+    /// hashes of the missing utxos that were added
+    pub utxos: Vec<String>,
+}
+
+impl WalletState {
+    /// Keys scanned while syncing new blocks.
+    pub(super) fn sync_scan_keys(&self) -> ScanKeys {
+        self.window_scan_keys(self.num_future_keys())
+    }
+
+    /// Keys of the sync with a window of `num_future_keys` keys.
+    fn window_scan_keys(&self, num_future_keys: u64) -> ScanKeys {
+        ScanKeys {
+            generation: Range {
+                start: 0,
+                end: self.num_generation_spending_keys() + num_future_keys,
+            },
+            symmetric: Range {
+                start: 0,
+                end: self.num_symmetric_keys() + num_future_keys,
+            },
+            guesser: true,
+            watch_only: true,
+        }
+    }
+
+    /// Keys of `filter`, the index range applies to generation and symmetric
+    /// keys alike.
+    fn filter_scan_keys(&self, filter: &KeyFilter) -> Result<ScanKeys, RescanError> {
+        if let (Some(start), Some(end)) = (filter.index_start, filter.index_end) {
+            if start >= end {
+                return Err(RescanError::EmptyKeyRange);
+            }
+        }
+
+        let sync = self.sync_scan_keys();
+        let range = |window: Range<u64>, skip: bool| {
+            let start = filter.index_start.unwrap_or(window.start);
+            let end = filter
+                .index_end
+                .unwrap_or(window.end.max(start + self.num_future_keys()));
+            match skip {
+                true => Range { start, end: start },
+                false => Range { start, end },
+            }
+        };
+
+        Ok(ScanKeys {
+            generation: range(sync.generation, filter.skip_generation),
+            symmetric: range(sync.symmetric, filter.skip_symmetric),
+            guesser: !filter.skip_guesser,
+            watch_only: true,
+        })
+    }
+
+    async fn has_utxo(&self, tx: &mut SqliteConnection, utxo: &UtxoDbData) -> Result<bool> {
+        let row =
+            sqlx::query("SELECT id FROM wallet_state_utxos WHERE hash = ? AND confirm_height = ?")
+                .bind(&utxo.hash)
+                .bind(utxo.confirm_height)
+                .fetch_optional(&mut *tx)
+                .await?;
+        Ok(row.is_some())
+    }
+}
+
+impl SyncState {
+    /// Changes the scan config of the wallet and rescans what it adds: the
+    /// blocks below the old start height with all keys and the synced blocks
+    /// with the keys added to the window.
+    ///
+    /// The key window can only be widened and the start height only lowered.
+    /// The config is stored once the rescan succeeded, utxos found by a failed
+    /// one are kept and skipped when it is retried.
+    ///
+    /// Sends, fee bumps and new blocks wait for the rescan, see
+    /// [Self::rescan_locked].
+    pub async fn update_scan_config(&self, scan_config: ScanConfig) -> Result<RescanResult> {
+        let old = self.wallet.scan_config();
+        if scan_config.num_keys < old.num_keys || scan_config.start_height > old.start_height {
+            return Err(RescanError::ScanConfigNarrowed.into());
+        }
+
+        // new blocks are applied holding the spend lock, the tip stays put
+        // while it is held
+        let _spend_guard = self.wallet.spend_lock.lock().await;
+
+        let mut result = RescanResult::default();
+        if scan_config.start_height < old.start_height {
+            let range = Range {
+                start: scan_config.start_height,
+                end: old.start_height,
+            };
+            let keys = self.wallet.window_scan_keys(scan_config.num_keys);
+            self.rescan_locked(range, &keys, &mut result).await?;
+        }
+
+        if scan_config.num_keys > old.num_keys {
+            // the first `old.num_keys` keys were in the window of every block
+            let mut keys = self.wallet.window_scan_keys(scan_config.num_keys);
+            keys.generation.start = old.num_keys.min(keys.generation.end);
+            keys.symmetric.start = old.num_keys.min(keys.symmetric.end);
+            keys.guesser = false;
+            keys.watch_only = false;
+
+            let range = Range {
+                start: old.start_height,
+                end: u64::MAX,
+            };
+            self.rescan_locked(range, &keys, &mut result).await?;
+        }
+
+        let config = crate::service::get_state::<Arc<Config>>();
+        config
+            .update_scan_config(self.wallet.id, &scan_config)
+            .await?;
+        self.wallet
+            .num_future_keys
+            .store(scan_config.num_keys, Ordering::Relaxed);
+        *self.wallet.scan_config.lock().unwrap() = scan_config;
+
+        info!(
+            "scan config of wallet {} updated, {} utxos found in {} blocks",
+            self.wallet.id,
+            result.utxos.len(),
+            result.blocks
+        );
+        Ok(result)
+    }
+
+    /// Scans the synced blocks of `range` for the keys of `filter` and adds
+    /// the utxos the wallet is missing, e.g. after importing keys or a fix of
+    /// the scanning. Later state is kept, unlike [Self::reset_to_height].
+    ///
+    /// Sends, fee bumps and new blocks wait for the rescan, see
+    /// [Self::rescan_locked].
+    pub async fn rescan(&self, range: Range<u64>, filter: &KeyFilter) -> Result<RescanResult> {
+        if range.start >= range.end {
+            return Err(RescanError::EmptyHeightRange.into());
+        }
+        let keys = self.wallet.filter_scan_keys(filter)?;
+
+        let _spend_guard = self.wallet.spend_lock.lock().await;
+
+        let mut result = RescanResult::default();
+        self.rescan_locked(range, &keys, &mut result).await?;
+
+        info!(
+            "rescan found {} utxos in {} blocks",
+            result.utxos.len(),
+            result.blocks
+        );
+        Ok(result)
+    }
+
+    /// Scans the synced blocks of `range` for utxos of `keys`, then the
+    /// following blocks up to the tip for inputs spending the utxos found.
+    ///
+    /// Must be called holding the spend lock of the wallet. The lock keeps the
+    /// tip in place and is held for the whole rescan, including the requests
+    /// of uncached blocks from the node, so a long rescan delays sends, fee
+    /// bumps and the sync of new blocks until it is done.
+    ///
+    /// Every block is committed on its own, with the webhooks of the utxos it
+    /// added, and its events are published after the commit. Blocks done
+    /// before a failure are kept.
+    async fn rescan_locked(
+        &self,
+        range: Range<u64>,
+        keys: &ScanKeys,
+        result: &mut RescanResult,
+    ) -> Result<()> {
+        let Some((tip, tip_digest)) = self.wallet.get_tip().await? else {
+            // nothing synced yet, the sync starts from the scan config
+            return Ok(());
+        };
+        let end = range.end.min(tip + 1);
+        if range.start >= end {
+            return Ok(());
+        }
+
+        let tip_block = self
+            .fake_archival_state
+            .get_block_by_height(tip)
+            .await?
+            .with_context(|| format!("block {tip} not found"))?;
+        if tip_block.hash() != tip_digest {
+            bail!("block {tip} does not match the wallet tip, retry after the sync caught up");
+        }
+
+        info!("rescan blocks {}..{}", range.start, end);
+
+        let mut msa_state = self.mutator_set_before(range.start).await?;
+        let mut found: Vec<UtxoDbData> = vec![];
+
+        for height in range.start..=tip {
+            if height >= end && found.iter().all(|utxo| utxo.spent_in_block.is_some()) {
+                break;
+            }
+
+            if (height - range.start) % SYNC_BLOCK_BATCH_SIZE == 0 {
+                self.fake_archival_state
+                    .prepare(height, SYNC_BLOCK_BATCH_SIZE)
+                    .await
+                    .context("prepare blocks error")?;
+            }
+            let block = self
+                .fake_archival_state
+                .get_block_by_height(height)
+                .await?
+                .with_context(|| format!("block {height} not found"))?;
+            result.blocks += 1;
+
+            let mut tx = self.wallet.pool.begin().await?;
+            let mut events = vec![];
+            let mut added = vec![];
+
+            let block_info = UtxoBlockInfo::of(&block);
+            let inputs = block
+                .kernel
+                .body
+                .transaction_kernel()
+                .inputs
+                .iter()
+                .map(|rr| rr.absolute_indices)
+                .collect_vec();
+            let mut spent_updates = vec![];
+            for utxo in found.iter_mut() {
+                if utxo.spent_in_block.is_none() && inputs.contains(&utxo.recovery_data.abs_i()) {
+                    utxo.spent_in_block = Some(block_info.clone());
+                    spent_updates.push((utxo.id, block_info.clone()));
+                    events.push(WalletEvent::UtxoSpent {
+                        hash: utxo.hash.clone(),
+                        amount: utxo
+                            .recovery_data
+                            .utxo
+                            .get_native_currency_amount()
+                            .display_lossless(),
+                        height,
+                    });
+                }
+            }
+            self.wallet
+                .update_spent_utxos(&mut *tx, spent_updates, &mut events)
+                .await?;
+
+            if height < end {
+                let MutatorSetUpdate {
+                    additions: addition_records,
+                    removals: _,
+                } = block.mutator_set_update();
+                let incoming = self.wallet.par_scan_for_incoming_utxo(&block, keys).await?;
+                let (recovery_datas, guesser_preimage) =
+                    recover_incoming_utxos(&mut msa_state, &addition_records, incoming);
+
+                for recovery_data in recovery_datas {
+                    let mut utxo = UtxoDbData::confirmed_in(recovery_data, &block)?;
+                    if self.wallet.has_utxo(&mut *tx, &utxo).await? {
+                        continue;
+                    }
+                    info!("rescan found utxo {} at {}", utxo.hash, height);
+                    utxo.id = utxo.create(&mut *tx).await?;
+                    events.push(WalletEvent::received(&utxo));
+                    added.push(utxo);
+                }
+
+                if let Some(key) = guesser_preimage {
+                    self.wallet.add_raw_hash_key(&mut *tx, key).await?;
+                }
+            }
+
+            if !added.is_empty() {
+                self.wallet
+                    .queue_rescan_webhooks(&mut *tx, tip, &added)
+                    .await
+                    .context("queue webhooks")?;
+            }
+
+            tx.commit().await?;
+
+            if !added.is_empty() {
+                self.wallet.webhooks.wake();
+            }
+            if !events.is_empty() {
+                for event in events {
+                    self.wallet.events.publish(event);
+                }
+                self.wallet.publish_balance().await;
+            }
+            found.extend(added);
+        }
+
+        result.utxos.extend(found.into_iter().map(|utxo| utxo.hash));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use neptune_cash::api::export::BlockHeight;
+    use neptune_cash::api::export::NativeCurrencyAmount;
+    use neptune_cash::api::export::Network;
+    use neptune_cash::api::export::SpendingKey;
+    use neptune_cash::api::export::Timestamp;
+    use neptune_cash::api::export::Tip5;
+    use neptune_cash::api::export::Utxo;
+    use neptune_cash::application::json_rpc::core::model::block::body::RpcMutatorSetAccumulator;
+    use neptune_cash::application::json_rpc::core::model::block::transaction_kernel::RpcTransactionKernel;
+    use neptune_cash::application::json_rpc::core::model::wallet::block::RpcWalletBlock;
+    use neptune_cash::prelude::tasm_lib::prelude::Digest;
+    use neptune_cash::protocol::consensus::block::Block;
+    use neptune_cash::protocol::consensus::transaction::transaction_kernel::TransactionKernelProxy;
+    use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMedium;
+    use neptune_cash::state::wallet::wallet_entropy::WalletEntropy;
+    use num_traits::Zero;
+    use sqlx::Row;
+
+    use super::*;
+    use crate::config::wallet::WalletConfig;
+    use crate::wallet::block::WalletBlock;
+    use crate::wallet::block_cache::BlockCache;
+    use crate::wallet::block_cache::BlockCacheImpl;
+    use crate::wallet::fake_archival_state::FakeArchivalState;
+    use crate::wallet::watch_only::WalletKey;
+    use crate::wallet::UtxoRecoveryData;
+
+    fn utxo(height: i64) -> UtxoDbData {
+        let utxo = Utxo::new_native_currency(Tip5::hash(&1u64), NativeCurrencyAmount::coins(1));
+        UtxoDbData {
+            id: 0,
+            hash: Tip5::hash(&utxo).to_hex(),
+            recovery_data: UtxoRecoveryData {
+                utxo,
+                sender_randomness: Digest::default(),
+                receiver_preimage: Digest::default(),
+                aocl_index: height as u64,
+            },
+            spent_in_block: None,
+            confirmed_in_block: UtxoBlockInfo {
+                block_height: height as u64,
+                block_digest: Digest::default(),
+                timestamp: Timestamp::now(),
+            },
+            confirm_height: height,
+            spent_height: None,
+            confirmed_txid: None,
+            spent_txid: None,
+        }
+    }
+
+    async fn wallet() -> WalletState {
+        wallet_from(0).await
+    }
+
+    async fn wallet_from(start_height: u64) -> WalletState {
+        let config = WalletConfig {
+            id: 0,
+            key: WalletKey::Entropy(WalletEntropy::devnet_wallet()),
+            scan_config: ScanConfig {
+                num_keys: 25,
+                start_height,
+            },
+            network: Network::Main,
+        };
+        WalletState::new(config, &PathBuf::new()).await.unwrap()
+    }
+
+    /// Genesis and a block 1 on top of it, which sends 1 coin to the first
+    /// generation address of `wallet` with an on-chain notification.
+    async fn blocks_paying(wallet: &WalletState) -> (WalletBlock, WalletBlock) {
+        let genesis = Block::genesis(wallet.network);
+        let address =
+            SpendingKey::from(wallet.key.entropy().unwrap().nth_generation_spending_key(0))
+                .to_address();
+        let outputs = wallet
+            .generate_tx_outputs(
+                [(address, NativeCurrencyAmount::coins(1))],
+                UtxoNotificationMedium::OnChain,
+                UtxoNotificationMedium::OnChain,
+                BlockHeight::from(1u64),
+            )
+            .await
+            .unwrap();
+
+        let timestamp = genesis.header().timestamp + Timestamp::minutes(10);
+        let kernel = TransactionKernelProxy {
+            inputs: vec![],
+            outputs: outputs.addition_records(),
+            announcements: outputs.iter().filter_map(|o| o.announcement()).collect(),
+            fee: NativeCurrencyAmount::zero(),
+            coinbase: None,
+            timestamp,
+            mutator_set_hash: Digest::default(),
+            merge_bit: false,
+        }
+        .into_kernel();
+        let mut msa = genesis.mutator_set_accumulator_after().unwrap();
+        for addition_record in &kernel.outputs {
+            msa.add(addition_record);
+        }
+
+        let mut block = RpcWalletBlock::from(&genesis);
+        block.kernel.header.height = BlockHeight::from(1u64);
+        block.kernel.header.prev_block_digest = genesis.hash();
+        block.kernel.header.timestamp = timestamp;
+        block.kernel.body.transaction_kernel = RpcTransactionKernel::from(&kernel);
+        block.kernel.body.mutator_set_accumulator = RpcMutatorSetAccumulator::from(&msa);
+
+        (
+            WalletBlock::from(&RpcWalletBlock::from(&genesis)),
+            WalletBlock::from(&block),
+        )
+    }
+
+    /// Synced up to block 1, which pays the wallet, with both blocks cached.
+    async fn synced_state(start_height: u64) -> SyncState {
+        let dir = std::env::temp_dir().join(format!("rescan-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = Config::new(&dir).await.unwrap();
+        crate::service::manage(Arc::new(config));
+
+        let wallet = wallet_from(start_height).await;
+        let (genesis, block) = blocks_paying(&wallet).await;
+
+        let mut tx = wallet.pool.begin().await.unwrap();
+        wallet.set_tip(&mut *tx, (1, block.hash())).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let cache = BlockCacheImpl::new_persist(&dir, wallet.network, 10)
+            .await
+            .unwrap();
+        cache
+            .add_blocks([genesis, block].into_iter())
+            .await
+            .unwrap();
+        let fake_archival_state = FakeArchivalState::new(cache, wallet.network, None);
+
+        SyncState::from_wallet(wallet, fake_archival_state)
+    }
+
+    async fn utxo_count(wallet: &WalletState) -> i64 {
+        sqlx::query("SELECT COUNT(*) FROM wallet_state_utxos")
+            .fetch_one(&wallet.pool)
+            .await
+            .unwrap()
+            .get(0)
+    }
+
+    #[tokio::test]
+    async fn filters_scan_keys() {
+        let wallet = wallet().await;
+        wallet
+            .num_generation_spending_keys
+            .store(3, Ordering::Relaxed);
+
+        let keys = wallet.sync_scan_keys();
+        assert_eq!((keys.generation.start, keys.generation.end), (0, 28));
+        assert_eq!((keys.symmetric.start, keys.symmetric.end), (0, 25));
+
+        // a start beyond the window scans the number of future keys
+        let keys = wallet
+            .filter_scan_keys(&KeyFilter {
+                index_start: Some(40),
+                skip_symmetric: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!((keys.generation.start, keys.generation.end), (40, 65));
+        assert_eq!(keys.symmetric.start, keys.symmetric.end);
+
+        let keys = wallet
+            .filter_scan_keys(&KeyFilter {
+                index_start: Some(0),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!((keys.generation.start, keys.generation.end), (0, 28));
+
+        let keys = wallet
+            .filter_scan_keys(&KeyFilter {
+                index_start: Some(20),
+                index_end: Some(100),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!((keys.generation.start, keys.generation.end), (20, 100));
+        assert!(keys.guesser);
+
+        let empty = wallet.filter_scan_keys(&KeyFilter {
+            index_start: Some(40),
+            index_end: Some(40),
+            ..Default::default()
+        });
+        assert!(matches!(empty, Err(RescanError::EmptyKeyRange)));
+    }
+
+    #[tokio::test]
+    async fn has_utxo_matches_hash_and_height() {
+        let wallet = wallet().await;
+
+        let mut tx = wallet.pool.begin().await.unwrap();
+        let id = utxo(10).create(&mut *tx).await.unwrap();
+        assert!(id > 0);
+
+        assert!(wallet.has_utxo(&mut *tx, &utxo(10)).await.unwrap());
+        // the same utxo received again in another block is a new row
+        assert!(!wallet.has_utxo(&mut *tx, &utxo(11)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn lowering_start_height_rescans_cached_blocks() {
+        let state = synced_state(2).await;
+
+        let result = state
+            .update_scan_config(ScanConfig {
+                num_keys: 25,
+                start_height: 0,
+            })
+            .await
+            .unwrap();
+        assert_eq!(result.blocks, 2);
+        assert_eq!(result.utxos.len(), 1);
+        assert_eq!(utxo_count(&state.wallet).await, 1);
+        assert_eq!(state.wallet.scan_config().start_height, 0);
+
+        // the utxo is known by hash and height, a second rescan adds nothing
+        let result = state
+            .rescan(Range { start: 1, end: 2 }, &KeyFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(result.blocks, 1);
+        assert!(result.utxos.is_empty());
+        assert_eq!(utxo_count(&state.wallet).await, 1);
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use itertools::Itertools;
 use neptune_cash::api::export::Timestamp;
 use neptune_cash::api::export::TransactionDetails;
@@ -20,11 +22,24 @@ use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMedium;
 use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMethod;
 use neptune_cash::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
 use num_traits::CheckedSub;
+use serde::Deserialize;
+use serde::Serialize;
 use thiserror::Error;
 use tracing::*;
+use utoipa::ToSchema;
 
 use super::input::InputSelectionRule;
+use super::input::InsufficientFunds;
+use super::job::JobHandle;
+use super::job::JobStage;
+use super::tx_status::TxState;
+use crate::config::Config;
+use crate::prover::verify_transaction;
+use crate::prover::CancellationToken;
+use crate::prover::InvalidProof;
 use crate::prover::ProofBuilder;
+use crate::prover::ProofCancelled;
+use crate::prover::ProofMonitor;
 use crate::rpc_client;
 use crate::rpc_client::BroadcastError;
 use crate::wallet::wallet_state_table::ExpectedUtxoData;
@@ -37,10 +52,125 @@ impl super::WalletState {
         fee: NativeCurrencyAmount,
         rule: InputSelectionRule,
         must_include_utxos: Vec<i64>,
+        proving: Option<TxProvingCapability>,
+    ) -> anyhow::Result<Transaction, SendError> {
+        self.send(
+            outputs,
+            utxo_notification_media,
+            fee,
+            rule,
+            must_include_utxos,
+            proving,
+            None,
+        )
+        .await
+    }
+
+    /// [Self::send_to_address], reporting progress to `job` if the send runs
+    /// as a job. `proving` defaults to the configured capability.
+    #[expect(clippy::too_many_arguments)]
+    pub(super) async fn send(
+        &self,
+        outputs: Vec<(ReceivingAddress, NativeCurrencyAmount)>,
+        utxo_notification_media: (UtxoNotificationMedium, UtxoNotificationMedium),
+        fee: NativeCurrencyAmount,
+        rule: InputSelectionRule,
+        must_include_utxos: Vec<i64>,
+        proving: Option<TxProvingCapability>,
+        job: Option<&JobHandle>,
     ) -> anyhow::Result<Transaction, SendError> {
+        if self.is_watch_only() {
+            return Err(SendError::WatchOnly);
+        }
+
+        let proving = match proving {
+            Some(proving) => proving,
+            None => {
+                crate::service::get_state::<Arc<Config>>()
+                    .get_tx_proving_capability()
+                    .await?
+            }
+        };
+
         let _spend_guard = self.spend_lock.lock().await;
+        self.set_job_stage(job, JobStage::SelectingInputs).await?;
+
+        let cancel = job.map(|job| job.cancel.clone()).unwrap_or_default();
+        *self.active_send.lock().unwrap() = Some(cancel.clone());
+
+        let mut events = vec![];
+        let status_id = self
+            .updater
+            .create_tx_status(&self.pool, None, &TxState::Building, &mut events)
+            .await?;
+        for event in events {
+            self.events.publish(event);
+        }
+
+        let result = self
+            .send_to_address_inner(
+                status_id,
+                outputs,
+                utxo_notification_media,
+                fee,
+                rule,
+                must_include_utxos,
+                proving,
+                job,
+                cancel,
+            )
+            .await;
+        *self.active_send.lock().unwrap() = None;
+
+        if let Err(e) = &result {
+            let state = TxState::Failed(e.to_string());
+            if let Err(e) = self.updater.set_tx_state(status_id, &state).await {
+                error!("failed to update transaction status: {}", e);
+            }
+        }
+
+        result
+    }
+
+    /// Stops the send currently building or proving a transaction, at the
+    /// latest before it is broadcast, or the running re-prove of pending
+    /// transactions. Returns false if there is none.
+    pub fn cancel_send(&self) -> bool {
+        match self.active_send.lock().unwrap().as_ref() {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Monitor for proofs made holding `spend_lock` outside of a send, e.g.
+    /// re-proves of pending transactions, so [Self::cancel_send] stops them
+    /// as well. The token is unregistered when the guard is dropped.
+    pub(super) fn register_active_proof(&self) -> (ProofMonitor, ActiveProofGuard<'_>) {
+        let cancel = CancellationToken::new();
+        *self.active_send.lock().unwrap() = Some(cancel.clone());
+        (
+            ProofMonitor::new(cancel),
+            ActiveProofGuard(&self.active_send),
+        )
+    }
+
+    #[expect(clippy::too_many_arguments)]
+    async fn send_to_address_inner(
+        &self,
+        status_id: i64,
+        outputs: Vec<(ReceivingAddress, NativeCurrencyAmount)>,
+        utxo_notification_media: (UtxoNotificationMedium, UtxoNotificationMedium),
+        fee: NativeCurrencyAmount,
+        rule: InputSelectionRule,
+        must_include_utxos: Vec<i64>,
+        tx_proving_capability: TxProvingCapability,
+        job: Option<&JobHandle>,
+        cancel: CancellationToken,
+    ) -> anyhow::Result<Transaction, SendError> {
         let now = Timestamp::now();
-        let tx_proving_capability = TxProvingCapability::ProofCollection;
 
         let (owned_utxo_notification_medium, unowned_utxo_notification_medium) =
             utxo_notification_media;
@@ -53,7 +183,7 @@ impl super::WalletState {
 
         // obtain next unused symmetric key for change utxo
         let change_key = {
-            let symmetric_key = self.key.nth_symmetric_key(0);
+            let symmetric_key = self.key.entropy()?.nth_symmetric_key(0);
             let spending_key = SpendingKey::Symmetric(symmetric_key);
             // self.set_num_symmetric_keys(self.num_symmetric_keys() + 1)
             //     .await?;
@@ -77,10 +207,22 @@ impl super::WalletState {
                 unowned_utxo_notification_medium,
                 tip_height,
             )
-            .await;
+            .await?;
 
         let _ =
             crate::service::app::emit_event_to("main", "send_state", "stmi: step 3. create tx.");
+        self.set_job_stage(job, JobStage::Proving).await?;
+        self.updater
+            .set_tx_state(status_id, &TxState::Proving)
+            .await?;
+
+        let job_progress = job.cloned();
+        let monitor = ProofMonitor::new(cancel.clone()).with_progress(move |progress| {
+            if let Some(job) = &job_progress {
+                job.set_progress(progress);
+            }
+            let _ = crate::service::app::emit_event_to("main", "proof_progress", progress.clone());
+        });
 
         // NOTE: A change output will be added to tx_outputs if needed.
         let (transaction, transaction_details, maybe_change_output) = match self
@@ -94,10 +236,12 @@ impl super::WalletState {
                 tx_proving_capability,
                 tip_msa,
                 tip_height,
+                &monitor,
             )
             .await
         {
             Ok(tx) => tx,
+            Err(e) if e.is::<ProofCancelled>() => return Err(SendError::Cancelled),
             Err(e) => {
                 tracing::error!("Could not create transaction: {}", e);
                 return Err(e.into());
@@ -123,9 +267,25 @@ impl super::WalletState {
             "stmi: step 5. broadcast transaction.",
         );
 
-        let txid = rpc_client::node_rpc_client()
+        if cancel.is_cancelled() {
+            return Err(SendError::Cancelled);
+        }
+        let txid = transaction.txid().to_string();
+        self.set_job_stage(job, JobStage::Broadcasting).await?;
+        self.set_job_txid(job, &txid).await?;
+        self.updater.set_tx_broadcast(status_id, &txid).await?;
+
+        let broadcast = rpc_client::node_rpc_client()
             .broadcast_transaction(&transaction)
+            .await;
+        self.updater
+            .record_broadcast_attempt(&txid, broadcast.as_ref().err().map(|e| e.to_string()))
             .await?;
+        broadcast?;
+
+        if tx_proving_capability == TxProvingCapability::ProofCollection {
+            self.queue_own_upgrade(&transaction).await;
+        }
 
         let _ = crate::service::app::emit_event_to(
             "main",
@@ -145,26 +305,91 @@ impl super::WalletState {
         self.add_expected_utxo(expected_utxo_data).await?;
 
         self.updater
-            .add_transaction(txid.clone(), transaction_details, db_ids)
+            .add_transaction(
+                txid.clone(),
+                transaction_details,
+                db_ids,
+                tx_proving_capability,
+            )
             .await?;
 
         Ok(transaction)
     }
 
+    /// Runs input selection and the change calculation of [Self::send_to_address]
+    /// without unlocking, proving or broadcasting anything.
+    pub async fn preview_send_to_address(
+        &self,
+        outputs: Vec<(ReceivingAddress, NativeCurrencyAmount)>,
+        utxo_notification_media: (UtxoNotificationMedium, UtxoNotificationMedium),
+        fee: NativeCurrencyAmount,
+        rule: InputSelectionRule,
+        must_include_utxos: Vec<i64>,
+    ) -> anyhow::Result<SendPreview> {
+        let (owned_utxo_notification_medium, unowned_utxo_notification_medium) =
+            utxo_notification_media;
+
+        let (inputs, db_ids) = self
+            .select_inputs(&outputs, fee, rule, must_include_utxos)
+            .await?;
+
+        let input_amount: NativeCurrencyAmount = inputs
+            .iter()
+            .map(|input| input.utxo.get_native_currency_amount())
+            .sum();
+        let output_amount: NativeCurrencyAmount = outputs.iter().map(|(_, amount)| *amount).sum();
+        let change_amount =
+            input_amount
+                .checked_sub(&(output_amount + fee))
+                .ok_or(InsufficientFunds {
+                    available: input_amount,
+                    required: output_amount + fee,
+                })?;
+
+        let outputs = outputs
+            .into_iter()
+            .map(|(address, amount)| {
+                let utxo = Utxo::new_native_currency(address.lock_script_hash(), amount);
+                let owned = self.can_unlock(&utxo);
+                let medium = if owned {
+                    owned_utxo_notification_medium
+                } else {
+                    unowned_utxo_notification_medium
+                };
+                Ok(PreviewOutput {
+                    address: address.to_bech32m(self.network)?,
+                    amount: amount.display_lossless(),
+                    owned,
+                    onchain: medium == UtxoNotificationMedium::OnChain,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(SendPreview {
+            inputs: db_ids,
+            input_amount: input_amount.display_lossless(),
+            change_amount: change_amount.display_lossless(),
+            change_onchain: owned_utxo_notification_medium == UtxoNotificationMedium::OnChain,
+            fee: fee.display_lossless(),
+            outputs,
+        })
+    }
+
     pub async fn generate_tx_outputs(
         &self,
         outputs: impl IntoIterator<Item = (ReceivingAddress, NativeCurrencyAmount)>,
         owned_utxo_notify_medium: UtxoNotificationMedium,
         unowned_utxo_notify_medium: UtxoNotificationMedium,
         block_height: BlockHeight,
-    ) -> TxOutputList {
+    ) -> anyhow::Result<TxOutputList> {
+        let key = self.key.entropy()?;
+
         // Convert outputs.  [address:amount] --> TxOutputList
         let tx_outputs: Vec<_> = outputs
             .into_iter()
             .map(|(address, amount)| {
-                let sender_randomness = self
-                    .key
-                    .generate_sender_randomness(block_height, address.privacy_digest());
+                let sender_randomness =
+                    key.generate_sender_randomness(block_height, address.privacy_digest());
 
                 // The UtxoNotifyMethod (Onchain or Offchain) is auto-detected
                 // based on whether the address belongs to our wallet or not
@@ -178,7 +403,7 @@ impl super::WalletState {
             })
             .collect();
 
-        tx_outputs.into()
+        Ok(tx_outputs.into())
     }
 
     pub fn can_unlock(&self, utxo: &Utxo) -> bool {
@@ -238,6 +463,7 @@ impl super::WalletState {
         prover_capability: TxProvingCapability,
         tip_msa: MutatorSetAccumulator,
         tip_height: BlockHeight,
+        monitor: &ProofMonitor,
     ) -> anyhow::Result<(Transaction, TransactionDetails, Option<TxOutput>)> {
         // 1. create/add change output if necessary.
         let total_spend = tx_outputs.total_native_coins() + fee;
@@ -270,13 +496,9 @@ impl super::WalletState {
             self.network,
         );
 
-        // note: if this task is cancelled, the proving job will continue
-        // because TritonVmJobOptions::cancel_job_rx is None.
-        // see how compose_task handles cancellation in mine_loop.
-
         // 2. Create the transaction
         let transaction = self
-            .create_raw_transaction(&transaction_details, prover_capability)
+            .create_raw_transaction(&transaction_details, prover_capability, monitor)
             .await?;
 
         Ok((transaction, transaction_details, maybe_change_output))
@@ -299,6 +521,7 @@ impl super::WalletState {
         let receiver_digest = own_receiving_address.privacy_digest();
         let change_sender_randomness = {
             self.key
+                .entropy()?
                 .generate_sender_randomness(tip_height, receiver_digest)
         };
 
@@ -352,11 +575,19 @@ impl super::WalletState {
         &self,
         transaction_details: &TransactionDetails,
         proving_power: TxProvingCapability,
+        monitor: &ProofMonitor,
     ) -> anyhow::Result<Transaction> {
         // note: this executes the prover which can take a very
         //       long time, perhaps minutes.  The `await` here, should avoid
         //       block the tokio executor and other async tasks.
-        Self::create_transaction_from_data_worker(transaction_details, proving_power).await
+        let transaction =
+            Self::create_transaction_from_data_worker(transaction_details, proving_power, monitor)
+                .await?;
+
+        // catch a bad proof here rather than as a rejected broadcast
+        verify_transaction(&transaction, transaction_details.network).await?;
+
+        Ok(transaction)
     }
 
     // note: this executes the prover which can take a very
@@ -367,6 +598,7 @@ impl super::WalletState {
     async fn create_transaction_from_data_worker(
         transaction_details: &TransactionDetails,
         proving_power: TxProvingCapability,
+        monitor: &ProofMonitor,
     ) -> anyhow::Result<Transaction> {
         let primitive_witness = PrimitiveWitness::from_transaction_details(transaction_details);
 
@@ -380,16 +612,48 @@ impl super::WalletState {
         let kernel = primitive_witness.kernel.clone();
         let proof = match proving_power {
             TxProvingCapability::PrimitiveWitness => TransactionProof::Witness(primitive_witness),
-            TxProvingCapability::LockScript => todo!(),
+            TxProvingCapability::LockScript => {
+                anyhow::bail!("a lock script capability cannot prove a transaction")
+            }
             TxProvingCapability::ProofCollection => {
+                let config = crate::service::get_state::<Arc<Config>>();
+                if let Some(remote) = config.get_remote_prover().await? {
+                    let collection = remote
+                        .prove(
+                            &config.get_secret_key().await?,
+                            &primitive_witness,
+                            transaction_details.network,
+                            monitor,
+                        )
+                        .await?;
+                    return Ok(Transaction {
+                        kernel,
+                        proof: TransactionProof::ProofCollection(collection),
+                    });
+                }
+
+                // stops the prover if this future is dropped
+                let guard = monitor.cancel_on_drop();
+                let monitor = monitor.clone();
                 let collection = tokio::task::spawn_blocking(move || {
-                    ProofBuilder::produce_proof_collection(&primitive_witness)
+                    ProofBuilder::produce_proof_collection(&primitive_witness, &monitor)
+                })
+                .await;
+                guard.disarm();
+
+                TransactionProof::ProofCollection(collection??)
+            }
+            TxProvingCapability::SingleProof => {
+                let guard = monitor.cancel_on_drop();
+                let monitor = monitor.clone();
+                let proof = tokio::task::spawn_blocking(move || {
+                    ProofBuilder::produce_single_proof(&primitive_witness, &monitor)
                 })
-                .await??;
+                .await;
+                guard.disarm();
 
-                TransactionProof::ProofCollection(collection)
+                TransactionProof::SingleProof(proof??)
             }
-            TxProvingCapability::SingleProof => todo!(),
         };
 
         Ok(Transaction { kernel, proof })
@@ -421,10 +685,57 @@ impl super::WalletState {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SendPreview {
+    /// database ids of the selected utxos
+    pub inputs: Vec<i64>,
+    pub input_amount: String,
+    pub change_amount: String,
+    pub change_onchain: bool,
+    pub fee: String,
+    pub outputs: Vec<PreviewOutput>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PreviewOutput {
+    pub address: String,
+    pub amount: String,
+    pub owned: bool,
+    pub onchain: bool,
+}
+
+/// Unregisters the token of [WalletState::register_active_proof].
+pub(super) struct ActiveProofGuard<'a>(&'a std::sync::Mutex<Option<CancellationToken>>);
+
+impl Drop for ActiveProofGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum SendError {
     #[error(transparent)]
-    Proof(#[from] anyhow::Error),
+    Proof(anyhow::Error),
+    #[error(transparent)]
+    InvalidProof(#[from] InvalidProof),
     #[error(transparent)]
     Broadcast(#[from] BroadcastError),
+    #[error("watch-only wallet cannot spend")]
+    WatchOnly,
+    #[error("cancelled")]
+    Cancelled,
+    #[error("pending transaction {0} not found")]
+    UnknownTransaction(String),
+    #[error("new fee must be higher than the current fee {0}")]
+    FeeNotIncreased(NativeCurrencyAmount),
+}
+
+impl From<anyhow::Error> for SendError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<InvalidProof>() {
+            Ok(e) => SendError::InvalidProof(e),
+            Err(e) => SendError::Proof(e),
+        }
+    }
 }
@@ -0,0 +1,125 @@
+use std::collections::HashSet;
+use std::range::Range;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use neptune_cash::api::export::NativeCurrencyAmount;
+use neptune_cash::protocol::consensus::transaction::Transaction;
+use neptune_cash::state::wallet::address::ReceivingAddress;
+use neptune_cash::state::wallet::utxo_notification::UtxoNotificationMedium;
+use num_traits::CheckedSub;
+use num_traits::Zero;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+use utoipa::ToSchema;
+
+use super::input::InputSelectionRule;
+use super::spend::SendError;
+use super::wallet_state_table::UtxoDbData;
+
+/// Restricts which utxos are swept, all spendable utxos if empty.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SweepFilter {
+    /// first derivation index of the keys to sweep
+    pub index_start: Option<u64>,
+    /// derivation index after the last key to sweep
+    pub index_end: Option<u64>,
+    /// skip utxos with fewer confirmations
+    pub min_confirmations: Option<u64>,
+}
+
+impl super::WalletState {
+    /// Spendable utxos matching `filter`.
+    ///
+    /// The index range applies to generation and symmetric keys alike.
+    pub async fn get_sweep_utxos(&self, filter: &SweepFilter) -> Result<Vec<UtxoDbData>> {
+        let mut utxos = self.get_spendable_utxos().await?;
+
+        if filter.index_start.is_some() || filter.index_end.is_some() {
+            let start = filter.index_start.unwrap_or(0);
+            let end = filter.index_end.unwrap_or(u64::MAX);
+
+            // keys beyond the scanned window cannot own any utxo
+            let generation_end =
+                end.min(self.num_generation_spending_keys() + self.num_future_keys());
+            let symmetric_end = end.min(self.num_symmetric_keys() + self.num_future_keys());
+
+            let lock_script_hashes = self
+                .get_future_generation_spending_keys(Range {
+                    start,
+                    end: generation_end.max(start),
+                })
+                .into_iter()
+                .chain(self.get_future_symmetric_keys(Range {
+                    start,
+                    end: symmetric_end.max(start),
+                }))
+                .map(|(_, key)| key.lock_script_hash())
+                .collect::<HashSet<_>>();
+
+            utxos.retain(|utxo| {
+                lock_script_hashes.contains(&utxo.recovery_data.utxo.lock_script_hash())
+            });
+        }
+
+        if let Some(min_confirmations) = filter.min_confirmations {
+            let tip = self.get_tip().await?.map(|tip| tip.0).unwrap_or(0) as i64;
+            utxos.retain(|utxo| tip - utxo.confirm_height + 1 >= min_confirmations as i64);
+        }
+
+        Ok(utxos)
+    }
+
+    /// Sends every utxo matching `filter` to `address`, the fee is subtracted
+    /// from the swept amount so no change output is created.
+    ///
+    /// Returns the transaction and the amount received by `address`.
+    pub async fn sweep(
+        &self,
+        address: ReceivingAddress,
+        fee: NativeCurrencyAmount,
+        filter: &SweepFilter,
+    ) -> Result<(Transaction, NativeCurrencyAmount), SendError> {
+        if self.is_watch_only() {
+            return Err(SendError::WatchOnly);
+        }
+
+        let utxos = self.get_sweep_utxos(filter).await?;
+        if utxos.is_empty() {
+            return Err(anyhow!("no spendable utxos to sweep").into());
+        }
+
+        let total: NativeCurrencyAmount = utxos
+            .iter()
+            .map(|utxo| utxo.recovery_data.utxo.get_native_currency_amount())
+            .sum();
+        let amount = total
+            .checked_sub(&fee)
+            .filter(|amount| *amount > NativeCurrencyAmount::zero())
+            .ok_or_else(|| anyhow!("fee {fee} exceeds the swept amount {total}"))?;
+
+        info!(
+            "sweeping {} utxos, {} to destination after fee",
+            utxos.len(),
+            amount
+        );
+
+        let ids = utxos.iter().map(|utxo| utxo.id).collect();
+        let transaction = self
+            .send_to_address(
+                vec![(address, amount)],
+                (
+                    UtxoNotificationMedium::OnChain,
+                    UtxoNotificationMedium::OnChain,
+                ),
+                fee,
+                InputSelectionRule::default(),
+                ids,
+                None,
+            )
+            .await?;
+
+        Ok((transaction, amount))
+    }
+}
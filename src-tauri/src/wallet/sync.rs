@@ -4,8 +4,10 @@ use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
+use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Result;
+use neptune_cash::api::export::NativeCurrencyAmount;
 use neptune_cash::api::export::Timestamp;
 use neptune_cash::protocol::consensus::block::Block;
 use neptune_cash::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
@@ -15,12 +17,15 @@ use tokio::sync::Mutex;
 use tokio::sync::Notify;
 use tokio::task::JoinHandle;
 use tracing::*;
+use utoipa::ToSchema;
 
+use super::events::WalletEvent;
 use super::fake_archival_state::FakeArchivalState;
-use super::fake_archival_state::SnapshotReader;
+use super::fee::estimate_fee_from_blocks;
+use super::fee::fee_estimate_window;
+use super::fee::FeeEstimate;
 use super::WalletState;
 use crate::config::Config;
-use crate::wallet::block_cache::BlockCacheImpl;
 
 const SYNC_STOPPED: i8 = 0;
 const SYNC_SYNCING: i8 = 1;
@@ -28,64 +33,60 @@ const SYNC_PAUSED: i8 = 2;
 const SYNC_WAIT_PAUSE: i8 = 3;
 
 pub const SYNC_BLOCK_BATCH_SIZE: u64 = 50;
+const AUTO_CONSOLIDATE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
 pub struct SyncState {
     height: AtomicU64,
     updated_to_tip: AtomicI8,
     syncing: AtomicI8,
-    fake_archival_state: FakeArchivalState,
+    pub(super) fake_archival_state: FakeArchivalState,
     pub wallet: super::WalletState,
     cancel: AtomicI8,
     /// Used to notify the sync task to wake up and check for new blocks.
     waker: Notify,
     handler: Mutex<Option<JoinHandle<()>>>,
+    consolidate_handler: Mutex<Option<JoinHandle<()>>>,
+    upgrader_handler: Mutex<Option<JoinHandle<()>>>,
+    webhook_handler: Mutex<Option<JoinHandle<()>>>,
+    /// when the last `sync_height` event was sent, in milliseconds
+    last_sync_event_time: AtomicU64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SyncStatus {
     pub height: u64,
     pub syncing: bool,
     pub updated_to_tip: bool,
 }
 
-static LAST_SYNC_EVENT_TIME: AtomicU64 = AtomicU64::new(0);
-
 impl SyncState {
-    pub async fn new(config: &Config) -> Result<Self> {
-        let wallet = WalletState::new_from_config(&config).await?;
-        let data_dir = config.get_data_dir().await?;
-        let snapshot_reader = match SnapshotReader::new(&data_dir).await {
-            Ok(v) => {
-                debug!("snapshot reader created : {:?}", v);
-                Some(v)
-            }
-            Err(e) => {
-                error!("failed to create snapshot reader: {:#?}", e);
-                None
-            }
-        };
-
-        let block_cache = if config.get_disk_cache().await? {
-            info!("disk cache enabled");
-            BlockCacheImpl::new_persist(&data_dir, config.get_network().await?, 200).await?
-        } else {
-            warn!("disk cache is disabled, this will cause performance issues");
-            BlockCacheImpl::new_memory(200)
-        };
+    /// Opens the wallet, blocks are read from the archival state shared with
+    /// the other synced wallets.
+    pub async fn new(
+        config: &Config,
+        id: i64,
+        fake_archival_state: FakeArchivalState,
+    ) -> Result<Self> {
+        let wallet = WalletState::new_from_config(config, id).await?;
+        Ok(Self::from_wallet(wallet, fake_archival_state))
+    }
 
-        Ok(Self {
+    /// Syncs an opened wallet with blocks read from `fake_archival_state`.
+    pub(super) fn from_wallet(wallet: WalletState, fake_archival_state: FakeArchivalState) -> Self {
+        Self {
             height: AtomicU64::new(0),
             updated_to_tip: AtomicI8::new(0),
             syncing: AtomicI8::new(0),
-            fake_archival_state: FakeArchivalState::new(
-                block_cache,
-                wallet.network,
-                snapshot_reader,
-            ),
+            fake_archival_state,
             wallet,
             cancel: AtomicI8::new(0),
             waker: Notify::new(),
             handler: Mutex::new(None),
-        })
+            consolidate_handler: Mutex::new(None),
+            upgrader_handler: Mutex::new(None),
+            webhook_handler: Mutex::new(None),
+            last_sync_event_time: AtomicU64::new(0),
+        }
     }
 
     pub async fn status(&self) -> SyncStatus {
@@ -96,6 +97,22 @@ impl SyncState {
         };
     }
 
+    /// Fee suggestion from the recently synced blocks, only blocks already in
+    /// the block cache are considered.
+    pub async fn estimate_fee(&self, num_inputs: u64) -> Result<FeeEstimate> {
+        let next_height = self.height.load(Ordering::Relaxed);
+
+        let mut blocks = vec![];
+        for height in fee_estimate_window(next_height) {
+            if let Some(block) = self.fake_archival_state.get_cached_block(height).await? {
+                blocks.push(block);
+            }
+        }
+        debug!("estimating fee from {} cached blocks", blocks.len());
+
+        estimate_fee_from_blocks(&blocks, num_inputs)
+    }
+
     pub async fn reset_to_height(&self, height: u64) -> Result<()> {
         if self.syncing.load(Ordering::Relaxed) != SYNC_PAUSED {
             self.syncing.store(SYNC_WAIT_PAUSE, Ordering::Relaxed);
@@ -109,6 +126,9 @@ impl SyncState {
 
         let task = async {
             let mut tx = self.wallet.pool.begin().await?;
+            let mut events = vec![WalletEvent::Reorg {
+                fork_height: height,
+            }];
             let block = self
                 .fake_archival_state
                 .get_block_by_height(height)
@@ -116,15 +136,25 @@ impl SyncState {
                 .context("failed to get block by height")?
                 .context("block not found")?;
             self.wallet
-                .reorganize_to_height(&mut tx, height, block.hash())
+                .reorganize_to_height(&mut tx, height, block.hash(), &mut events)
                 .await?;
             tx.commit().await?;
-            self.fake_archival_state.reset_to_height(height).await?;
+            self.wallet.webhooks.wake();
+            for event in events {
+                self.wallet.events.publish(event);
+            }
             self.height.store(height + 1, Ordering::Relaxed);
+            // orphaned blocks left in the cache show up as a fork when synced
+            if let Err(e) = self.fake_archival_state.evict_orphaned_blocks(height).await {
+                warn!("failed to evict orphaned blocks: {:?}", e);
+            }
             Ok::<(), anyhow::Error>(())
         };
 
         let result = task.await;
+        if result.is_ok() {
+            self.wallet.publish_balance().await;
+        }
         self.syncing.store(SYNC_SYNCING, Ordering::Relaxed);
         self.waker.notify_one();
 
@@ -148,28 +178,71 @@ impl SyncState {
         });
 
         self.handler.lock().await.replace(task);
+
+        let consolidate = tokio::spawn(self.clone().auto_consolidate());
+        self.consolidate_handler.lock().await.replace(consolidate);
+
+        let upgrader = tokio::spawn(self.clone().run_upgrader());
+        self.upgrader_handler.lock().await.replace(upgrader);
+
+        let webhooks = tokio::spawn(self.clone().run_webhooks());
+        self.webhook_handler.lock().await.replace(webhooks);
+
+        if let Err(e) = self.resume_jobs().await {
+            error!("failed to resume jobs: {:?}", e);
+        }
+    }
+
+    async fn auto_consolidate(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(AUTO_CONSOLIDATE_INTERVAL).await;
+            if let Err(e) = self.try_consolidate().await {
+                error!("auto consolidate error: {:?}", e);
+            }
+        }
+    }
+
+    /// Runs a consolidation if it is enabled and the wallet is idle, i.e.
+    /// synced to tip and without pending transactions.
+    async fn try_consolidate(&self) -> Result<()> {
+        let config = crate::service::get_state::<Arc<Config>>();
+        let Some(consolidate) = config.get_auto_consolidate().await? else {
+            return Ok(());
+        };
+
+        if self.updated_to_tip.load(Ordering::Relaxed) == 0
+            || !self.wallet.get_pending_transactions().await?.is_empty()
+        {
+            return Ok(());
+        }
+
+        let spendable = self.wallet.get_spendable_utxos().await?.len();
+        if spendable < consolidate.min_utxos.max(2) {
+            return Ok(());
+        }
+
+        let fee = NativeCurrencyAmount::coins_from_str(&consolidate.fee)?;
+        if let Some(tx) = self
+            .wallet
+            .consolidate(consolidate.max_inputs, fee)
+            .await
+            .map_err(|e| anyhow!("{}", e))?
+        {
+            info!(
+                "auto consolidation of {} utxos: {}",
+                tx.kernel.inputs.len(),
+                tx.txid()
+            );
+        }
+
+        Ok(())
     }
 
     async fn sync_inner(&self) -> Result<()> {
         let start = self.wallet.start_height().await?;
         debug!("start set to: {start}");
 
-        let mut previous_mutator_set_accumulator = match start {
-            0 => MutatorSetAccumulator::default(),
-            1 => Block::genesis(self.wallet.network).mutator_set_accumulator_after()?,
-            _ => {
-                let context = format!(
-                    "Prev block does not exist. Could not get block with height {}",
-                    start - 1
-                );
-                let block = self
-                    .fake_archival_state
-                    .get_block_by_height(start - 1)
-                    .await?
-                    .context(context)?;
-                block.mutator_set_accumulator_after()
-            }
-        };
+        let mut previous_mutator_set_accumulator = self.mutator_set_before(start).await?;
 
         self.update(if start > 1 { start - 1 } else { start });
         self.height.store(start, Ordering::Relaxed);
@@ -233,6 +306,26 @@ impl SyncState {
             }
         }
     }
+    /// Mutator set accumulator before the block at `height` is applied.
+    pub(super) async fn mutator_set_before(&self, height: u64) -> Result<MutatorSetAccumulator> {
+        Ok(match height {
+            0 => MutatorSetAccumulator::default(),
+            1 => Block::genesis(self.wallet.network).mutator_set_accumulator_after()?,
+            _ => {
+                let context = format!(
+                    "Prev block does not exist. Could not get block with height {}",
+                    height - 1
+                );
+                let block = self
+                    .fake_archival_state
+                    .get_block_by_height(height - 1)
+                    .await?
+                    .context(context)?;
+                block.mutator_set_accumulator_after()
+            }
+        })
+    }
+
     async fn sync_height(
         &self,
         previous_mutator_set_accumulator: &mut MutatorSetAccumulator,
@@ -321,9 +414,9 @@ impl SyncState {
 
             self.update(fork);
             self.fake_archival_state
-                .reset_to_height(fork)
+                .evict_orphaned_blocks(fork)
                 .await
-                .context("reset to height error")?;
+                .context("evict orphaned blocks")?;
             self.height.store(fork + 1, Ordering::Relaxed);
             return Ok(None);
         }
@@ -333,33 +426,59 @@ impl SyncState {
             current_height
         );
 
+        self.wallet
+            .upgrades
+            .apply_block(previous_mutator_set_accumulator, &current_block);
         *previous_mutator_set_accumulator = current_mutator_set_accumulator;
 
         let now = Timestamp::now().to_millis();
-        if now - LAST_SYNC_EVENT_TIME.load(Ordering::Relaxed) > 100 {
+        if now - self.last_sync_event_time.load(Ordering::Relaxed) > 100 {
             self.update(current_height);
-            LAST_SYNC_EVENT_TIME.store(now, Ordering::Relaxed);
+            self.last_sync_event_time.store(now, Ordering::Relaxed);
         }
         self.height.store(current_height + 1, Ordering::Relaxed);
 
         Ok(None)
     }
 
+    /// Whether this is the wallet selected in the app, only its progress is
+    /// sent to the app.
+    fn is_current(&self) -> bool {
+        crate::service::try_get_state::<Arc<SyncState>>()
+            .is_some_and(|current| std::ptr::eq(Arc::as_ptr(&*current), self))
+    }
+
     fn update(&self, height: u64) {
         self.updated_to_tip.store(0, Ordering::Relaxed);
-        let _ = crate::service::app::emit_event_to("main", "sync_height", height);
+        if self.is_current() {
+            let _ = crate::service::app::emit_event_to("main", "sync_height", height);
+        }
     }
 
     fn updated_to_tip(&self, height: u64) {
         self.updated_to_tip.store(1, Ordering::Relaxed);
-        let _ = crate::service::app::emit_event_to("main", "sync_finish", height);
+        if self.is_current() {
+            let _ = crate::service::app::emit_event_to("main", "sync_finish", height);
+        }
     }
 
     fn syncing_new_tip(&self, height: u64) {
-        let _ = crate::service::app::emit_event_to("main", "syncing_new_block", height);
+        if self.is_current() {
+            let _ = crate::service::app::emit_event_to("main", "syncing_new_block", height);
+        }
     }
 
     pub async fn cancel_sync(&self) {
+        if let Some(handler) = self.consolidate_handler.lock().await.take() {
+            handler.abort();
+        }
+        if let Some(handler) = self.upgrader_handler.lock().await.take() {
+            handler.abort();
+        }
+        if let Some(handler) = self.webhook_handler.lock().await.take() {
+            handler.abort();
+        }
+
         self.cancel.store(1, Ordering::Relaxed);
         self.waker.notify_waiters();
 
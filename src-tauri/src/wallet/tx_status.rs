@@ -0,0 +1,238 @@
+use anyhow::Result;
+use neptune_cash::api::export::Timestamp;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlx::Row;
+use sqlx::Sqlite;
+use utoipa::ToSchema;
+
+use super::events::WalletEvent;
+use super::pending::TransactionUpdater;
+
+/// failed transactions are listed for this long, in milliseconds
+const FAILED_RETENTION_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Lifecycle of a transaction sent by this wallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "status", content = "detail")]
+pub enum TxState {
+    /// selecting inputs and creating outputs
+    Building,
+    Proving,
+    /// proven, being submitted to the node
+    Broadcast,
+    /// accepted by the node
+    InMempool,
+    /// inputs spent in the block at this height
+    Confirmed(u64),
+    /// never reached the node, with the reason
+    Failed(String),
+    /// replaced by the transaction with this id, e.g. by a fee bump
+    Replaced(String),
+    Forgotten,
+}
+
+impl TxState {
+    fn is_final(&self) -> bool {
+        matches!(
+            self,
+            TxState::Confirmed(_) | TxState::Replaced(_) | TxState::Forgotten
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TransactionStatus {
+    pub id: i64,
+    /// known once the transaction is proven
+    pub tx_id: Option<String>,
+    #[serde(flatten)]
+    pub state: TxState,
+    /// milliseconds since the unix epoch
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// number of times the transaction was submitted to the node
+    pub attempts: i64,
+    pub last_error: Option<String>,
+}
+
+impl TransactionStatus {
+    fn from_row(row: sqlx::sqlite::SqliteRow) -> Result<Self> {
+        Ok(Self {
+            id: row.get("id"),
+            tx_id: row.get("txid"),
+            state: serde_json::from_str(&row.get::<String, _>("state"))?,
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            attempts: row.get("attempts"),
+            last_error: row.get("last_error"),
+        })
+    }
+}
+
+pub(super) struct CreateTxStatusMigration;
+
+sqlx_migrator::sqlite_migration!(
+    CreateTxStatusMigration,
+    "wallet_state",
+    "create_tx_status",
+    sqlx_migrator::vec_box![],
+    sqlx_migrator::vec_box![(
+        "CREATE TABLE wallet_state_tx_status (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        txid TEXT,
+        state TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT
+        )", //up
+        "DROP TABLE wallet_state_tx_status" //down
+    )]
+);
+
+fn now() -> i64 {
+    Timestamp::now().to_millis() as i64
+}
+
+fn state_event(id: Option<i64>, txid: Option<String>, state: &TxState) -> WalletEvent {
+    WalletEvent::TxStateChanged {
+        id,
+        txid,
+        state: state.clone(),
+    }
+}
+
+impl TransactionUpdater {
+    /// Inserts a status, the event is pushed to `events` to be published once
+    /// the executor's transaction is committed.
+    pub async fn create_tx_status<'c, E>(
+        &self,
+        executor: E,
+        txid: Option<&str>,
+        state: &TxState,
+        events: &mut Vec<WalletEvent>,
+    ) -> Result<i64>
+    where
+        E: sqlx::Executor<'c, Database = Sqlite>,
+    {
+        let now = now();
+        let id = sqlx::query(
+            "INSERT INTO wallet_state_tx_status (txid, state, created_at, updated_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(txid)
+        .bind(serde_json::to_string(state)?)
+        .bind(now)
+        .bind(now)
+        .execute(executor)
+        .await?
+        .last_insert_rowid();
+
+        events.push(state_event(Some(id), txid.map(str::to_string), state));
+        Ok(id)
+    }
+
+    pub async fn set_tx_state(&self, id: i64, state: &TxState) -> Result<()> {
+        sqlx::query("UPDATE wallet_state_tx_status SET state = ?, updated_at = ? WHERE id = ?")
+            .bind(serde_json::to_string(state)?)
+            .bind(now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        self.publish_state(Some(id), None, state);
+        Ok(())
+    }
+
+    /// Sets the txid of a proven transaction that is about to be broadcast.
+    pub async fn set_tx_broadcast(&self, id: i64, txid: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE wallet_state_tx_status SET txid = ?, state = ?, updated_at = ? WHERE id = ?",
+        )
+        .bind(txid)
+        .bind(serde_json::to_string(&TxState::Broadcast)?)
+        .bind(now())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        self.publish_state(Some(id), Some(txid.to_string()), &TxState::Broadcast);
+        Ok(())
+    }
+
+    /// Like [Self::create_tx_status], the event is pushed to `events`.
+    pub async fn set_tx_state_by_txid<'c, E>(
+        &self,
+        executor: E,
+        txid: &str,
+        state: &TxState,
+        events: &mut Vec<WalletEvent>,
+    ) -> Result<()>
+    where
+        E: sqlx::Executor<'c, Database = Sqlite>,
+    {
+        sqlx::query("UPDATE wallet_state_tx_status SET state = ?, updated_at = ? WHERE txid = ?")
+            .bind(serde_json::to_string(state)?)
+            .bind(now())
+            .bind(txid)
+            .execute(executor)
+            .await?;
+
+        events.push(state_event(None, Some(txid.to_string()), state));
+        Ok(())
+    }
+
+    /// Counts a submission to the node, the transaction is in the mempool if
+    /// it succeeded.
+    pub async fn record_broadcast_attempt(&self, txid: &str, error: Option<String>) -> Result<()> {
+        // rebroadcasts after every block would repeat the event otherwise
+        let entered_mempool = error.is_none()
+            && sqlx::query("SELECT 1 FROM wallet_state_tx_status WHERE txid = ? AND state != ?")
+                .bind(txid)
+                .bind(serde_json::to_string(&TxState::InMempool)?)
+                .fetch_optional(&self.pool)
+                .await?
+                .is_some();
+
+        let query = match error {
+            None => sqlx::query(
+                "UPDATE wallet_state_tx_status SET attempts = attempts + 1, state = ?, updated_at = ? WHERE txid = ?",
+            )
+            .bind(serde_json::to_string(&TxState::InMempool)?),
+            Some(error) => sqlx::query(
+                "UPDATE wallet_state_tx_status SET attempts = attempts + 1, last_error = ?, updated_at = ? WHERE txid = ?",
+            )
+            .bind(error),
+        };
+
+        query.bind(now()).bind(txid).execute(&self.pool).await?;
+
+        if entered_mempool {
+            self.publish_state(None, Some(txid.to_string()), &TxState::InMempool);
+        }
+        Ok(())
+    }
+
+    fn publish_state(&self, id: Option<i64>, txid: Option<String>, state: &TxState) {
+        self.events.publish(state_event(id, txid, state));
+    }
+
+    /// Transactions that are still in progress, and failures of the last day.
+    pub async fn get_tx_statuses(&self) -> Result<Vec<TransactionStatus>> {
+        let rows = sqlx::query("SELECT * FROM wallet_state_tx_status ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let cutoff = now() - FAILED_RETENTION_MS;
+        let mut statuses = vec![];
+        for row in rows {
+            let status = TransactionStatus::from_row(row)?;
+            let expired = matches!(status.state, TxState::Failed(_)) && status.updated_at < cutoff;
+            if !status.state.is_final() && !expired {
+                statuses.push(status);
+            }
+        }
+
+        Ok(statuses)
+    }
+}
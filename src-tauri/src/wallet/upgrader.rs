@@ -0,0 +1,438 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use anyhow::Result;
+use dashmap::DashMap;
+use neptune_cash::api::export::NativeCurrencyAmount;
+use neptune_cash::api::export::TransactionProof;
+use neptune_cash::protocol::consensus::block::block_height::BlockHeight;
+use neptune_cash::protocol::consensus::block::mutator_set_update::MutatorSetUpdate;
+use neptune_cash::protocol::consensus::transaction::Transaction;
+use neptune_cash::state::wallet::wallet_entropy::WalletEntropy;
+use neptune_cash::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::select;
+use tokio::sync::Notify;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::*;
+use utoipa::ToSchema;
+
+use super::block::WalletBlock;
+use super::sync::SyncState;
+use super::WalletState;
+use crate::config::Config;
+use crate::prover::verify_transaction;
+use crate::prover::ProofBuilder;
+use crate::prover::ProofMonitor;
+use crate::rpc_client;
+
+/// default number of transactions held for upgrading
+pub const DEFAULT_UPGRADER_MAX_QUEUE: usize = 20;
+
+const UPGRADER_IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Settings of the proof upgrader, disabled when not stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpgraderConfig {
+    /// fee collected from every submitted transaction with a gobbler
+    /// transaction paying this wallet, own transactions are not charged
+    pub gobble_fee: Option<String>,
+    /// transactions proven at the same time
+    #[serde(default = "default_max_parallel")]
+    pub max_parallel: usize,
+    /// transactions held for upgrading, further submissions are rejected
+    #[serde(default = "default_max_queue")]
+    pub max_queue: usize,
+}
+
+fn default_max_parallel() -> usize {
+    1
+}
+
+fn default_max_queue() -> usize {
+    DEFAULT_UPGRADER_MAX_QUEUE
+}
+
+impl UpgraderConfig {
+    fn parse_gobble_fee(&self) -> Result<Option<NativeCurrencyAmount>> {
+        self.gobble_fee
+            .as_deref()
+            .map(NativeCurrencyAmount::coins_from_str)
+            .transpose()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "status", content = "detail")]
+pub enum UpgradeStatus {
+    /// waiting for a proving slot
+    Queued,
+    Proving,
+    /// upgraded to a single proof, synced to the tip and broadcast
+    Broadcast,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UpgradeItem {
+    /// id of the submitted transaction
+    pub txid: String,
+    /// id of the last broadcast version, changes with every block
+    pub current_txid: String,
+    pub fee: String,
+    /// created by this wallet
+    pub own: bool,
+    #[serde(flatten)]
+    pub status: UpgradeStatus,
+}
+
+struct UpgradeEntry {
+    transaction: Transaction,
+    own: bool,
+    status: UpgradeStatus,
+    /// blocks to apply to the proof, with the mutator set before each block
+    updates: Vec<(MutatorSetAccumulator, MutatorSetUpdate)>,
+}
+
+/// Work taken from the queue by [SyncState::run_upgrader].
+struct UpgradeWork {
+    txid: String,
+    transaction: Transaction,
+    own: bool,
+    updates: Vec<(MutatorSetAccumulator, MutatorSetUpdate)>,
+}
+
+/// Transactions upgraded to single proofs and kept valid until they are
+/// mined. Held in memory only.
+#[derive(Default)]
+pub struct UpgradeQueue {
+    entries: DashMap<String, UpgradeEntry>,
+    /// height and mutator set of the last synced block
+    tip: std::sync::Mutex<Option<(BlockHeight, MutatorSetAccumulator)>>,
+    waker: Notify,
+}
+
+impl UpgradeQueue {
+    fn submit(
+        &self,
+        transaction: Transaction,
+        own: bool,
+        config: &UpgraderConfig,
+    ) -> Result<String> {
+        match &transaction.proof {
+            TransactionProof::ProofCollection(_) | TransactionProof::SingleProof(_) => {}
+            TransactionProof::Witness(_) => {
+                bail!("primitive witness transactions are not accepted")
+            }
+        }
+        if !own {
+            if let Some(gobble_fee) = config.parse_gobble_fee()? {
+                ensure!(
+                    transaction.kernel.fee >= gobble_fee,
+                    "transaction fee {} does not cover the gobble fee {gobble_fee}",
+                    transaction.kernel.fee
+                );
+            }
+        }
+
+        // a transaction spending the same inputs replaces the old one, e.g. a
+        // pending transaction that was proven again for a new block
+        self.entries
+            .retain(|_, entry| !shares_inputs(&entry.transaction, &transaction));
+
+        let active = self
+            .entries
+            .iter()
+            .filter(|entry| !matches!(entry.status, UpgradeStatus::Failed(_)))
+            .count();
+        ensure!(
+            active < config.max_queue,
+            "upgrade queue is full ({} transactions)",
+            config.max_queue
+        );
+
+        let txid = transaction.txid().to_string();
+        self.entries.insert(
+            txid.clone(),
+            UpgradeEntry {
+                transaction,
+                own,
+                status: UpgradeStatus::Queued,
+                updates: vec![],
+            },
+        );
+        self.waker.notify_one();
+
+        Ok(txid)
+    }
+
+    /// Whether `txid` is queued or being upgraded, the upgrader keeps it
+    /// synced to the tip so it must not be proven again meanwhile.
+    pub(super) fn is_upgrading(&self, txid: &str) -> bool {
+        self.entries
+            .get(txid)
+            .is_some_and(|entry| !matches!(entry.status, UpgradeStatus::Failed(_)))
+    }
+
+    fn list(&self) -> Vec<UpgradeItem> {
+        self.entries
+            .iter()
+            .map(|entry| UpgradeItem {
+                txid: entry.key().clone(),
+                current_txid: entry.transaction.txid().to_string(),
+                fee: entry.transaction.kernel.fee.display_lossless(),
+                own: entry.own,
+                status: entry.status.clone(),
+            })
+            .collect()
+    }
+
+    /// Drops the transactions whose inputs were spent by `block`, and queues
+    /// the others to be updated to the new mutator set.
+    pub(super) fn apply_block(&self, previous: &MutatorSetAccumulator, block: &WalletBlock) {
+        *self.tip.lock().unwrap() = Some((
+            block.kernel.header.height,
+            block.mutator_set_accumulator_after(),
+        ));
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let update = block.mutator_set_update();
+        self.entries.retain(|txid, entry| {
+            let spent = entry.transaction.kernel.inputs.iter().any(|input| {
+                update
+                    .removals
+                    .iter()
+                    .any(|removal| removal.absolute_indices == input.absolute_indices)
+            });
+            if spent {
+                info!("upgraded transaction {} left the mempool", txid);
+            }
+            // failures are listed until the next block
+            !spent && !matches!(entry.status, UpgradeStatus::Failed(_))
+        });
+
+        for mut entry in self.entries.iter_mut() {
+            entry.updates.push((previous.clone(), update.clone()));
+            if entry.status == UpgradeStatus::Broadcast {
+                entry.status = UpgradeStatus::Queued;
+            }
+        }
+        self.waker.notify_one();
+    }
+
+    fn next(&self) -> Option<UpgradeWork> {
+        // the mutator set is needed to upgrade
+        if self.tip.lock().unwrap().is_none() {
+            return None;
+        }
+        let mut entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.status == UpgradeStatus::Queued)?;
+        entry.status = UpgradeStatus::Proving;
+
+        Some(UpgradeWork {
+            txid: entry.key().clone(),
+            transaction: entry.transaction.clone(),
+            own: entry.own,
+            updates: std::mem::take(&mut entry.updates),
+        })
+    }
+
+    fn finish(&self, txid: &str, result: Result<Transaction>) {
+        // removed meanwhile if it was mined
+        let Some(mut entry) = self.entries.get_mut(txid) else {
+            return;
+        };
+
+        match result {
+            Ok(transaction) => {
+                entry.transaction = transaction;
+                // blocks that arrived while proving
+                entry.status = match entry.updates.is_empty() {
+                    true => UpgradeStatus::Broadcast,
+                    false => UpgradeStatus::Queued,
+                };
+            }
+            Err(e) => {
+                error!("failed to upgrade transaction {}: {:?}", txid, e);
+                entry.status = UpgradeStatus::Failed(e.to_string());
+            }
+        }
+    }
+}
+
+fn shares_inputs(a: &Transaction, b: &Transaction) -> bool {
+    a.kernel.inputs.iter().any(|a| {
+        b.kernel
+            .inputs
+            .iter()
+            .any(|b| a.absolute_indices == b.absolute_indices)
+    })
+}
+
+impl WalletState {
+    /// Queues a transaction proven elsewhere to be upgraded to a single proof.
+    pub async fn submit_upgrade(&self, transaction: Transaction) -> Result<String> {
+        let config = crate::service::get_state::<Arc<Config>>()
+            .get_proof_upgrader()
+            .await?
+            .context("proof upgrader is disabled")?;
+        self.upgrades.submit(transaction, false, &config)
+    }
+
+    pub fn get_upgrades(&self) -> Vec<UpgradeItem> {
+        self.upgrades.list()
+    }
+
+    /// Hands a proof collection transaction of this wallet to the upgrader,
+    /// if it is enabled.
+    pub(super) async fn queue_own_upgrade(&self, transaction: &Transaction) {
+        let config = crate::service::get_state::<Arc<Config>>();
+        match config.get_proof_upgrader().await {
+            Ok(Some(config)) => {
+                if let Err(e) = self.upgrades.submit(transaction.clone(), true, &config) {
+                    warn!("failed to queue upgrade of {}: {}", transaction.txid(), e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("failed to read proof upgrader config: {:?}", e),
+        }
+    }
+
+    async fn upgrade(&self, work: UpgradeWork, config: &UpgraderConfig) -> Result<Transaction> {
+        let gobble_fee = match work.own {
+            true => None,
+            false => config.parse_gobble_fee()?,
+        };
+        let entropy = match gobble_fee {
+            Some(_) => Some(self.key.entropy()?.clone()),
+            None => None,
+        };
+        let tip = self
+            .upgrades
+            .tip
+            .lock()
+            .unwrap()
+            .clone()
+            .context("tip unknown")?;
+        let builder = ProofBuilder::new(gobble_fee, self.network);
+
+        info!("upgrading transaction {}", work.txid);
+        let monitor = ProofMonitor::default();
+        // stops the prover if the upgrader is stopped
+        let guard = monitor.cancel_on_drop();
+        let worker_monitor = monitor.clone();
+        let transaction = tokio::task::spawn_blocking(move || {
+            prove_upgrade(
+                &builder,
+                work.transaction,
+                work.updates,
+                tip,
+                entropy,
+                &worker_monitor,
+            )
+        })
+        .await;
+        guard.disarm();
+        let transaction = transaction??;
+        verify_transaction(&transaction, self.network).await?;
+
+        rpc_client::node_rpc_client()
+            .broadcast_transaction(&transaction)
+            .await?;
+        info!("broadcast upgraded transaction {}", transaction.txid());
+
+        Ok(transaction)
+    }
+}
+
+/// Upgrades to a single proof if needed, then applies the blocks mined since
+/// the transaction was proven.
+fn prove_upgrade(
+    builder: &ProofBuilder,
+    mut transaction: Transaction,
+    updates: Vec<(MutatorSetAccumulator, MutatorSetUpdate)>,
+    tip: (BlockHeight, MutatorSetAccumulator),
+    entropy: Option<WalletEntropy>,
+    monitor: &ProofMonitor,
+) -> Result<Transaction> {
+    if !matches!(transaction.proof, TransactionProof::SingleProof(_)) {
+        let (height, tip_mutator_set) = tip;
+        // the mutator set the transaction was proven against
+        let mutator_set = match updates.first() {
+            Some((previous, _)) => previous.clone(),
+            None => tip_mutator_set,
+        };
+        ensure!(
+            transaction.kernel.mutator_set_hash == mutator_set.hash(),
+            "transaction is not synced to the tip"
+        );
+        transaction = builder.upgrade_proof(transaction, &mutator_set, height, entropy, monitor)?;
+    }
+
+    for (previous, update) in updates {
+        transaction = builder.update_single_proof(transaction, &previous, update, monitor)?;
+    }
+
+    Ok(transaction)
+}
+
+impl SyncState {
+    /// Upgrades the queued transactions while the proof upgrader is enabled.
+    pub(super) async fn run_upgrader(self: Arc<Self>) {
+        // dropped with this task, which aborts the running upgrades
+        let mut tasks = JoinSet::new();
+        let mut slots = Arc::new(Semaphore::new(0));
+        let mut max_parallel = 0;
+
+        loop {
+            while tasks.try_join_next().is_some() {}
+
+            let config = crate::service::get_state::<Arc<Config>>();
+            let config = match config.get_proof_upgrader().await {
+                Ok(Some(config)) => config,
+                Ok(None) => {
+                    tokio::time::sleep(UPGRADER_IDLE_INTERVAL).await;
+                    continue;
+                }
+                Err(e) => {
+                    error!("failed to read proof upgrader config: {:?}", e);
+                    tokio::time::sleep(UPGRADER_IDLE_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            if config.max_parallel.max(1) != max_parallel {
+                max_parallel = config.max_parallel.max(1);
+                slots = Arc::new(Semaphore::new(max_parallel));
+            }
+            let permit = slots.clone().acquire_owned().await.unwrap();
+
+            let Some(work) = self.wallet.upgrades.next() else {
+                drop(permit);
+                select! {
+                    _ = tokio::time::sleep(UPGRADER_IDLE_INTERVAL) => {},
+                    _ = self.wallet.upgrades.waker.notified() => {},
+                }
+                continue;
+            };
+
+            let state = self.clone();
+            tasks.spawn(async move {
+                let txid = work.txid.clone();
+                let result = state.wallet.upgrade(work, &config).await;
+                state.wallet.upgrades.finish(&txid, result);
+                drop(permit);
+                state.wallet.upgrades.waker.notify_one();
+            });
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::sync::atomic::Ordering;
 
 use anyhow::Result;
 use neptune_cash::api::export::Timestamp;
+use neptune_cash::api::export::Tip5;
 use neptune_cash::prelude::tasm_lib::prelude::Digest;
 use neptune_cash::state::wallet::expected_utxo::ExpectedUtxo;
 use serde::Deserialize;
@@ -15,6 +16,10 @@ use sqlx_migrator::Migrator;
 use sqlx_migrator::Plan;
 use tracing::info;
 
+use super::block::WalletBlock;
+use super::events::WalletEvent;
+use super::job::CreateJobsMigration;
+use super::tx_status::TxState;
 use super::UtxoRecoveryData;
 use super::WalletState;
 
@@ -112,6 +117,16 @@ pub struct UtxoBlockInfo {
     pub timestamp: Timestamp,
 }
 
+impl UtxoBlockInfo {
+    pub(super) fn of(block: &WalletBlock) -> Self {
+        Self {
+            block_height: block.kernel.header.height.into(),
+            block_digest: block.hash(),
+            timestamp: block.kernel.header.timestamp,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Tip {
     pub height: u64,
@@ -119,7 +134,26 @@ struct Tip {
 }
 
 impl UtxoDbData {
-    fn from_row(row: sqlx::sqlite::SqliteRow) -> anyhow::Result<Self> {
+    /// Unspent utxo received in `block`.
+    pub(super) fn confirmed_in(
+        recovery_data: UtxoRecoveryData,
+        block: &WalletBlock,
+    ) -> anyhow::Result<Self> {
+        let confirmed_in_block = UtxoBlockInfo::of(block);
+        Ok(Self {
+            id: 0,
+            hash: Tip5::hash(&recovery_data.utxo).to_hex(),
+            recovery_data,
+            spent_in_block: None,
+            confirm_height: confirmed_in_block.block_height.try_into()?,
+            confirmed_in_block,
+            spent_height: None,
+            confirmed_txid: None,
+            spent_txid: None,
+        })
+    }
+
+    pub(super) fn from_row(row: sqlx::sqlite::SqliteRow) -> anyhow::Result<Self> {
         let recovery_data = row.get::<Vec<u8>, _>("recovery_data");
         let recovery_data = bincode::deserialize(&recovery_data)?;
 
@@ -145,7 +179,8 @@ impl UtxoDbData {
         })
     }
 
-    pub async fn create<'c, E>(&self, executor: E) -> anyhow::Result<()>
+    /// Inserts the utxo, returns its id.
+    pub async fn create<'c, E>(&self, executor: E) -> anyhow::Result<i64>
     where
         E: sqlx::Executor<'c, Database = Sqlite>,
     {
@@ -155,14 +190,14 @@ impl UtxoDbData {
 
         let confirmed_in_block = serde_json::to_string(&self.confirmed_in_block)?;
 
-        sqlx::query(query)
+        let result = sqlx::query(query)
             .bind(&self.hash)
             .bind(&data)
             .bind(&confirmed_in_block)
             .bind(&self.confirm_height)
             .execute(executor)
             .await?;
-        Ok(())
+        Ok(result.last_insert_rowid())
     }
 
     pub fn display_pretty(&self) -> String {
@@ -210,7 +245,8 @@ impl ExpectedUtxoData {
         })
     }
 
-    pub async fn create<'c, E>(&self, executor: E) -> anyhow::Result<()>
+    /// Inserts the utxo, returns its id.
+    pub async fn create<'c, E>(&self, executor: E) -> anyhow::Result<i64>
     where
         E: sqlx::Executor<'c, Database = Sqlite>,
     {
@@ -240,6 +276,7 @@ impl WalletState {
         migrator.add_migration(Box::new(CreateWalletStateUtxosMigration))?;
         migrator.add_migration(Box::new(CreateWalletStateExpectedUtxoMigration))?;
         migrator.add_migration(Box::new(CreateWalletStateKnownRawHashKeysMigration))?;
+        migrator.add_migration(Box::new(CreateJobsMigration))?;
 
         let mut conn = self.pool.acquire().await?;
         // use apply all to apply all pending migration
@@ -348,6 +385,7 @@ impl WalletState {
         &self,
         tx: &'c mut SqliteConnection,
         utxos: Vec<(i64, UtxoBlockInfo)>,
+        events: &mut Vec<WalletEvent>,
     ) -> Result<()> {
         for utxo in &utxos {
             let info = serde_json::to_string(&utxo.1)?;
@@ -360,10 +398,18 @@ impl WalletState {
         }
 
         // remove from pending so it will not be updated again
-        for (id, _) in utxos {
+        for (id, block) in utxos {
             info!("checking utxo {} for pending", id);
             if let Some(txid) = self.updater.try_remove_pending_by_utxo_id(tx, id).await? {
                 info!("removing pending tx {}", txid);
+                self.updater
+                    .set_tx_state_by_txid(
+                        &mut *tx,
+                        &txid,
+                        &TxState::Confirmed(block.block_height),
+                        events,
+                    )
+                    .await?;
                 sqlx::query::<Sqlite>("UPDATE wallet_state_utxos SET spent_txid = ? WHERE id = ?")
                     .bind(&txid)
                     .bind(&id)
@@ -570,15 +616,19 @@ impl WalletState {
         Ok(())
     }
 
-    // reorganize to the fork point
+    // reorganize to the fork point, events are published by the caller after
+    // the commit
     pub async fn reorganize_to_height<'c>(
         &self,
         tx: &'c mut SqliteConnection,
         height: u64,
         digest: Digest,
+        events: &mut Vec<WalletEvent>,
     ) -> Result<()> {
         let height_i64 = height as i64;
 
+        self.queue_revert_webhooks(&mut *tx, height).await?;
+
         let ids = sqlx::query("SELECT id FROM wallet_state_utxos WHERE confirm_height > ?")
             .bind(&height_i64)
             .fetch_all(&mut *tx)
@@ -588,7 +638,7 @@ impl WalletState {
             .collect::<Vec<_>>();
 
         self.updater
-            .try_clean_pending_by_utxo(&mut *tx, ids)
+            .try_clean_pending_by_utxo(&mut *tx, ids, events)
             .await?;
 
         sqlx::query("DELETE FROM wallet_state_utxos WHERE confirm_height > ?")
@@ -617,11 +667,12 @@ mod tests {
     use super::*;
     use crate::config::wallet::ScanConfig;
     use crate::config::wallet::WalletConfig;
+    use crate::wallet::watch_only::WalletKey;
     #[tokio::test]
     async fn test_migrate_tables() {
         let config = WalletConfig {
             id: 0,
-            key: WalletEntropy::devnet_wallet(),
+            key: WalletKey::Entropy(WalletEntropy::devnet_wallet()),
             scan_config: ScanConfig {
                 num_keys: 1,
                 start_height: 0,
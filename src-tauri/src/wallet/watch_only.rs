@@ -0,0 +1,303 @@
+use aes_gcm::aead::Aead;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::KeyInit;
+use aes_gcm::Nonce;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use anyhow::Result;
+use neptune_cash::api::export::GenerationSpendingKey;
+use neptune_cash::api::export::Network;
+use neptune_cash::api::export::ReceivingAddress;
+use neptune_cash::api::export::SpendingKey;
+use neptune_cash::api::export::Utxo;
+use neptune_cash::prelude::tasm_lib::prelude::Digest;
+use neptune_cash::prelude::triton_vm::prelude::BFieldElement;
+use neptune_cash::prelude::twenty_first::math::lattice;
+use neptune_cash::prelude::twenty_first::math::lattice::kem::CIPHERTEXT_SIZE_IN_BFES;
+use neptune_cash::protocol::consensus::transaction::announcement::Announcement;
+use neptune_cash::protocol::consensus::transaction::transaction_kernel::TransactionKernel;
+use neptune_cash::state::wallet::address::generation_address::GENERATION_FLAG;
+use neptune_cash::state::wallet::address::symmetric_key::SYMMETRIC_KEY_FLAG;
+use neptune_cash::state::wallet::incoming_utxo::IncomingUtxo;
+use neptune_cash::state::wallet::wallet_entropy::WalletEntropy;
+use serde::Deserialize;
+use serde::Serialize;
+use sha3::digest::ExtendableOutput;
+use sha3::digest::Update;
+use sha3::Shake256;
+use tracing::warn;
+
+const WATCH_ONLY_VERSION: u8 = 2;
+
+/// The key material a wallet is built from.
+///
+/// A watch-only wallet only holds the material needed to recognize incoming
+/// utxos, it can never produce lock script witnesses.
+pub enum WalletKey {
+    Entropy(WalletEntropy),
+    WatchOnly(WatchOnlyKeys),
+}
+
+impl WalletKey {
+    pub fn entropy(&self) -> Result<&WalletEntropy> {
+        match self {
+            WalletKey::Entropy(entropy) => Ok(entropy),
+            WalletKey::WatchOnly(_) => bail!("watch-only wallet does not hold spending keys"),
+        }
+    }
+
+    pub fn is_watch_only(&self) -> bool {
+        matches!(self, WalletKey::WatchOnly(_))
+    }
+}
+
+/// Exported viewing material of a wallet.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WatchOnlyKeys {
+    version: u8,
+    pub network: Network,
+    pub generation_keys: Vec<ViewingKey>,
+    pub symmetric_keys: Vec<ViewingKey>,
+}
+
+impl WatchOnlyKeys {
+    pub fn from_entropy(entropy: &WalletEntropy, num_keys: u64, network: Network) -> Result<Self> {
+        let generation_keys = (0..num_keys)
+            .map(|i| {
+                ViewingKey::from_spending_key(
+                    i,
+                    &SpendingKey::from(entropy.nth_generation_spending_key(i)),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let symmetric_keys = (0..num_keys)
+            .map(|i| {
+                ViewingKey::from_spending_key(i, &SpendingKey::from(entropy.nth_symmetric_key(i)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            version: WATCH_ONLY_VERSION,
+            network,
+            generation_keys,
+            symmetric_keys,
+        })
+    }
+
+    /// hex encoded representation used to move viewing material between machines
+    pub fn encode(&self) -> Result<String> {
+        Ok(hex::encode(bincode::serialize(self)?))
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self> {
+        let bytes = hex::decode(encoded.trim()).context("viewing key is not valid hex")?;
+        let keys: Self = bincode::deserialize(&bytes).context("invalid viewing key")?;
+        ensure!(
+            keys.version == WATCH_ONLY_VERSION,
+            "unsupported viewing key version {}",
+            keys.version
+        );
+        ensure!(
+            !keys.generation_keys.is_empty(),
+            "viewing key has no addresses"
+        );
+        Ok(keys)
+    }
+
+    /// the guesser fee key is the first generation key
+    pub fn guesser_key(&self) -> &ViewingKey {
+        &self.generation_keys[0]
+    }
+
+    pub fn address(&self, index: u64) -> Result<&ReceivingAddress> {
+        self.generation_keys
+            .iter()
+            .find(|k| k.index == index)
+            .and_then(|k| k.address.as_ref())
+            .context(format!("address {index} is not part of the viewing key"))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViewingSecret {
+    Generation(lattice::kem::SecretKey),
+    Symmetric([u8; 32]),
+}
+
+/// Everything needed to detect and claim-track utxos sent to a single key,
+/// without the unlock key of that key.
+///
+/// Symmetric keys have no address: a `ReceivingAddress::Symmetric` is the key
+/// itself and would carry the unlock key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ViewingKey {
+    pub index: u64,
+    pub receiver_identifier: BFieldElement,
+    pub receiver_preimage: Digest,
+    lock_script_hash: Digest,
+    pub address: Option<ReceivingAddress>,
+    secret: ViewingSecret,
+}
+
+impl ViewingKey {
+    pub fn from_spending_key(index: u64, key: &SpendingKey) -> Result<Self> {
+        let (lock_script_hash, address, secret) = match key {
+            SpendingKey::Generation(generation_key) => {
+                let address = generation_key.to_address();
+                (
+                    address.lock_script().hash(),
+                    Some(address.into()),
+                    ViewingSecret::Generation(generation_decryption_key(generation_key)?),
+                )
+            }
+            SpendingKey::Symmetric(symmetric_key) => (
+                symmetric_key.lock_script().hash(),
+                None,
+                ViewingSecret::Symmetric(symmetric_key.secret_key().into()),
+            ),
+        };
+
+        Ok(Self {
+            index,
+            receiver_identifier: key.receiver_identifier(),
+            receiver_preimage: key.privacy_preimage(),
+            lock_script_hash,
+            address,
+            secret,
+        })
+    }
+
+    pub fn lock_script_hash(&self) -> Digest {
+        self.lock_script_hash
+    }
+
+    fn key_type_flag(&self) -> BFieldElement {
+        match self.secret {
+            ViewingSecret::Generation(_) => GENERATION_FLAG,
+            ViewingSecret::Symmetric(_) => SYMMETRIC_KEY_FLAG,
+        }
+    }
+
+    /// Same as `SpendingKey::scan_for_announced_utxos`, using viewing material only.
+    pub fn scan_for_announced_utxos(&self, tx_kernel: &TransactionKernel) -> Vec<IncomingUtxo> {
+        tx_kernel
+            .announcements
+            .iter()
+            .filter(|a| self.matches_announcement(a))
+            .filter_map(|a| match self.decrypt(&a.message[2..]) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    warn!(
+                        "possible loss of funds! skipping announcement for viewing key {}: {}",
+                        self.receiver_identifier, e
+                    );
+                    None
+                }
+            })
+            .map(|(utxo, sender_randomness)| IncomingUtxo {
+                utxo,
+                sender_randomness,
+                receiver_preimage: self.receiver_preimage,
+                is_guesser_fee: false,
+            })
+            .collect()
+    }
+
+    fn matches_announcement(&self, announcement: &Announcement) -> bool {
+        announcement.message.len() > 2
+            && announcement.message[0] == self.key_type_flag()
+            && announcement.message[1] == self.receiver_identifier
+    }
+
+    fn decrypt(&self, ciphertext: &[BFieldElement]) -> Result<(Utxo, Digest)> {
+        let (shared_key, remainder) = match &self.secret {
+            ViewingSecret::Generation(decryption_key) => {
+                ensure!(
+                    ciphertext.len() > CIPHERTEXT_SIZE_IN_BFES,
+                    "Ciphertext does not have nonce."
+                );
+                let (kem_ctxt, remainder) = ciphertext.split_at(CIPHERTEXT_SIZE_IN_BFES);
+                let kem_ctxt: [BFieldElement; CIPHERTEXT_SIZE_IN_BFES] =
+                    kem_ctxt.try_into().unwrap();
+                let shared_key = lattice::kem::dec(*decryption_key, kem_ctxt.into())
+                    .context("Could not establish shared secret key.")?;
+                (shared_key, remainder)
+            }
+            ViewingSecret::Symmetric(secret_key) => (*secret_key, ciphertext),
+        };
+
+        ensure!(remainder.len() > 1, "Ciphertext does not have payload.");
+        let (nonce_ctxt, dem_ctxt) = remainder.split_at(1);
+
+        let cipher = Aes256Gcm::new(&shared_key.into());
+        let nonce_as_bytes = [nonce_ctxt[0].value().to_be_bytes().to_vec(), vec![0u8; 4]].concat();
+        #[allow(deprecated)]
+        let nonce = Nonce::from_slice(&nonce_as_bytes);
+        let ciphertext_bytes = bfes_to_bytes(dem_ctxt)?;
+        let plaintext = cipher
+            .decrypt(nonce, ciphertext_bytes.as_ref())
+            .map_err(|_| anyhow!("Failed to decrypt symmetric payload."))?;
+
+        Ok(bincode::deserialize(&plaintext)?)
+    }
+}
+
+/// The lattice decryption key of a generation key.
+///
+/// `GenerationSpendingKey` has no accessor for its decryption key, its serde
+/// representation (`{ seed }`) is the only public view of the seed it is
+/// derived from. The seed is checked against the public address before use.
+fn generation_decryption_key(key: &GenerationSpendingKey) -> Result<lattice::kem::SecretKey> {
+    let mut value = serde_json::to_value(key)?;
+    let seed: Digest = serde_json::from_value(value["seed"].take())
+        .context("generation key does not expose its seed")?;
+    ensure!(
+        GenerationSpendingKey::derive_from_seed(seed).to_address() == key.to_address(),
+        "generation key seed does not match its address"
+    );
+
+    let randomness = shake256(&bincode::serialize(&seed)?);
+    let (decryption_key, _) = lattice::kem::keygen(randomness);
+    Ok(decryption_key)
+}
+
+fn shake256(input: &[u8]) -> [u8; 32] {
+    let mut hasher = Shake256::default();
+    hasher.update(input);
+    let mut result = [0u8; 32];
+    hasher.finalize_xof_into(&mut result);
+    result
+}
+
+// inverse of the byte encoding used by utxo notifications
+fn bfes_to_bytes(bfes: &[BFieldElement]) -> Result<Vec<u8>> {
+    ensure!(!bfes.is_empty(), "Cannot decode empty byte stream");
+
+    let length = bfes[0].value() as usize;
+    ensure!(
+        length <= size_of_val(bfes),
+        "Cannot decode byte stream shorter than length indicated."
+    );
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(length);
+    let mut skip_top = false;
+    for bfe in bfes.iter().skip(1) {
+        let bfe_bytes = bfe.value().to_be_bytes();
+        if skip_top {
+            bytes.extend_from_slice(&bfe_bytes[4..8]);
+            skip_top = false;
+        } else {
+            bytes.extend_from_slice(&bfe_bytes[0..4]);
+            if bfe_bytes[0..4] == [0xff, 0xff, 0xff, 0xff] {
+                skip_top = true;
+            } else {
+                bytes.extend_from_slice(&bfe_bytes[4..8]);
+            }
+        }
+    }
+
+    Ok(bytes[0..length].to_vec())
+}
@@ -0,0 +1,771 @@
+//! Outgoing webhooks for deposits.
+//!
+//! Events are written to the `wallet_state_webhook_outbox` table in the same
+//! database transaction as the block that caused them, one row per configured
+//! endpoint, and delivered by a background task as `POST` requests with a JSON
+//! body:
+//!
+//! - `deposit`: a utxo was received in a synced block
+//! - `deposit_confirmed`: the block of a received utxo reached the configured
+//!   number of confirmations, the block itself counts as one
+//! - `deposit_reverted`: the block of a received utxo was rolled back by a
+//!   reorganization, the utxo may be received again in another block
+//!
+//! Every request carries the headers
+//!
+//! - `X-Webhook-Id`: id of the event, the `id` field of the body
+//! - `X-Webhook-Timestamp`: unix time of the request in seconds
+//! - `X-Webhook-Signature`: `sha256=` followed by the hex encoded
+//!   HMAC-SHA256 of `{timestamp}.{body}` keyed with the endpoint secret
+//!
+//! Any 2xx response acknowledges the event. Failed deliveries are retried
+//! with exponential backoff and given up after `max_attempts`.
+//!
+//! Ordering: events of an endpoint are delivered one at a time in the order
+//! they happened, an event is not sent before the previous one was
+//! acknowledged or given up. Events of different endpoints are independent.
+//! An endpoint is identified by its `id`, or by its url and secret if it has
+//! none, so endpoints sharing a url have separate queues. Changing the url or
+//! secret of an endpoint without an `id` starts a new queue.
+//!
+//! Delivered events are removed after seven days.
+//!
+//! Idempotency: delivery is at least once, an event is sent again if the
+//! acknowledgement is lost. Its id stays the same across retries, receivers
+//! should ignore ids they already processed. A utxo received, reverted and
+//! received again produces three events with different ids.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Result;
+use hmac::Hmac;
+use hmac::Mac;
+use neptune_cash::api::export::Timestamp;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest as _;
+use sha2::Sha256;
+use sqlx::Row;
+use sqlx::SqliteConnection;
+use sqlx::SqlitePool;
+use sqlx_migrator::Info;
+use sqlx_migrator::Migrate;
+use sqlx_migrator::Migrator;
+use sqlx_migrator::Plan;
+use tokio::sync::Notify;
+use tracing::*;
+
+use super::sync::SyncState;
+use super::wallet_state_table::UtxoDbData;
+use super::WalletState;
+use crate::config::Config;
+
+pub const DEFAULT_WEBHOOK_CONFIRMATIONS: u64 = 6;
+pub const DEFAULT_WEBHOOK_MAX_ATTEMPTS: u32 = 20;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// queued events are checked at least this often, new ones are sent at once
+const WEBHOOK_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// delay before the first retry, doubled with every further attempt
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// delivered events are kept this long, in milliseconds
+const DELIVERED_RETENTION_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// Webhooks of the wallet, disabled when not stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub endpoints: Vec<WebhookEndpoint>,
+    /// confirmations after which `deposit_confirmed` is sent
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u64,
+    /// deliveries of an event before it is given up
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_confirmations() -> u64 {
+    DEFAULT_WEBHOOK_CONFIRMATIONS
+}
+
+fn default_max_attempts() -> u32 {
+    DEFAULT_WEBHOOK_MAX_ATTEMPTS
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEndpoint {
+    /// keeps the queued events of the endpoint when its url or secret changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub url: String,
+    /// key of the HMAC signature
+    pub secret: String,
+}
+
+impl WebhookEndpoint {
+    /// Key of the endpoint's rows in the outbox.
+    fn key(&self) -> String {
+        if let Some(id) = &self.id {
+            return format!("id:{id}");
+        }
+        let mut hasher = Sha256::new();
+        hasher.update((self.url.len() as u64).to_le_bytes());
+        hasher.update(self.url.as_bytes());
+        hasher.update(self.secret.as_bytes());
+        format!("sha256:{}", hex::encode(hasher.finalize()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Deposit {
+        utxo_hash: String,
+        amount: String,
+        height: u64,
+        block_digest: String,
+    },
+    DepositConfirmed {
+        utxo_hash: String,
+        amount: String,
+        height: u64,
+        block_digest: String,
+        confirmations: u64,
+    },
+    DepositReverted {
+        utxo_hash: String,
+        amount: String,
+        height: u64,
+        block_digest: String,
+    },
+}
+
+impl WebhookEvent {
+    pub(super) fn deposit(utxo: &UtxoDbData) -> Self {
+        WebhookEvent::Deposit {
+            utxo_hash: utxo.hash.clone(),
+            amount: utxo_amount(utxo),
+            height: utxo.confirmed_in_block.block_height,
+            block_digest: utxo.confirmed_in_block.block_digest.to_hex(),
+        }
+    }
+
+    pub(super) fn deposit_confirmed(utxo: &UtxoDbData, confirmations: u64) -> Self {
+        WebhookEvent::DepositConfirmed {
+            utxo_hash: utxo.hash.clone(),
+            amount: utxo_amount(utxo),
+            height: utxo.confirmed_in_block.block_height,
+            block_digest: utxo.confirmed_in_block.block_digest.to_hex(),
+            confirmations,
+        }
+    }
+
+    pub(super) fn deposit_reverted(utxo: &UtxoDbData) -> Self {
+        WebhookEvent::DepositReverted {
+            utxo_hash: utxo.hash.clone(),
+            amount: utxo_amount(utxo),
+            height: utxo.confirmed_in_block.block_height,
+            block_digest: utxo.confirmed_in_block.block_digest.to_hex(),
+        }
+    }
+}
+
+fn utxo_amount(utxo: &UtxoDbData) -> String {
+    utxo.recovery_data
+        .utxo
+        .get_native_currency_amount()
+        .display_lossless()
+}
+
+/// Body of a webhook request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub id: i64,
+    pub wallet_id: i64,
+    /// milliseconds since the unix epoch
+    pub created_at: i64,
+    #[serde(flatten)]
+    pub event: WebhookEvent,
+}
+
+/// Hex encoded HMAC-SHA256 of `{timestamp}.{body}`.
+pub fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Delay before the next delivery after `attempts` failed ones.
+fn retry_delay(attempts: u32) -> Duration {
+    MIN_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_RETRY_DELAY)
+}
+
+fn now() -> i64 {
+    Timestamp::now().to_millis() as i64
+}
+
+struct CreateWebhookOutboxMigration;
+
+sqlx_migrator::sqlite_migration!(
+    CreateWebhookOutboxMigration,
+    "wallet_state",
+    "create_webhook_outbox",
+    sqlx_migrator::vec_box![],
+    sqlx_migrator::vec_box![(
+        "CREATE TABLE wallet_state_webhook_outbox (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        url TEXT NOT NULL,
+        endpoint TEXT NOT NULL,
+        event TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        next_attempt_at INTEGER NOT NULL,
+        delivered_at INTEGER DEFAULT NULL,
+        failed INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT DEFAULT NULL
+        )", //up
+        "DROP TABLE wallet_state_webhook_outbox" //down
+    )]
+);
+
+struct OutboxEntry {
+    id: i64,
+    event: WebhookEvent,
+    created_at: i64,
+    attempts: u32,
+    next_attempt_at: i64,
+}
+
+impl OutboxEntry {
+    fn from_row(row: sqlx::sqlite::SqliteRow) -> Result<Self> {
+        Ok(Self {
+            id: row.get("id"),
+            event: serde_json::from_str(&row.get::<String, _>("event"))?,
+            created_at: row.get("created_at"),
+            attempts: row.get("attempts"),
+            next_attempt_at: row.get("next_attempt_at"),
+        })
+    }
+}
+
+/// Persistent queue of webhook deliveries.
+pub struct WebhookOutbox {
+    pool: SqlitePool,
+    wallet_id: i64,
+    client: reqwest::Client,
+    waker: Notify,
+}
+
+impl WebhookOutbox {
+    pub async fn new(pool: SqlitePool, wallet_id: i64) -> Result<Self> {
+        let outbox = Self {
+            pool,
+            wallet_id,
+            client: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()?,
+            waker: Notify::new(),
+        };
+
+        outbox.migrate_tables().await?;
+        Ok(outbox)
+    }
+
+    async fn migrate_tables(&self) -> Result<()> {
+        let mut migrator = Migrator::default();
+        migrator.add_migration(Box::new(CreateWebhookOutboxMigration))?;
+
+        let mut conn = self.pool.acquire().await?;
+        migrator.run(&mut *conn, &Plan::apply_all()).await?;
+
+        Ok(())
+    }
+
+    /// Queues the events for every endpoint, within the database transaction
+    /// of the block that caused them. Call [Self::wake] after the commit.
+    pub async fn enqueue(
+        &self,
+        tx: &mut SqliteConnection,
+        config: &WebhookConfig,
+        events: &[WebhookEvent],
+    ) -> Result<()> {
+        let now = now();
+        for event in events {
+            let event = serde_json::to_string(event)?;
+            for endpoint in &config.endpoints {
+                sqlx::query(
+                    "INSERT INTO wallet_state_webhook_outbox (url, endpoint, event, created_at, next_attempt_at) VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind(&endpoint.url)
+                .bind(endpoint.key())
+                .bind(&event)
+                .bind(now)
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn wake(&self) {
+        self.waker.notify_one();
+    }
+
+    pub async fn wait(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.waker.notified()).await;
+    }
+
+    /// Delivers the due events of every endpoint, endpoints are served
+    /// concurrently.
+    pub async fn deliver_due(&self, config: &WebhookConfig) {
+        if let Err(e) = self.prune_delivered().await {
+            error!("failed to prune delivered webhooks: {:?}", e);
+        }
+
+        let deliveries = config
+            .endpoints
+            .iter()
+            .map(|endpoint| self.deliver_endpoint(endpoint, config.max_attempts));
+        for (endpoint, result) in config
+            .endpoints
+            .iter()
+            .zip(futures::future::join_all(deliveries).await)
+        {
+            if let Err(e) = result {
+                error!("webhook delivery to {} failed: {:?}", endpoint.url, e);
+            }
+        }
+    }
+
+    /// Removes the events delivered before the retention period.
+    async fn prune_delivered(&self) -> Result<()> {
+        sqlx::query("DELETE FROM wallet_state_webhook_outbox WHERE delivered_at < ?")
+            .bind(now() - DELIVERED_RETENTION_MS)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delivers the events of an endpoint in order, until one fails or is
+    /// not yet due.
+    async fn deliver_endpoint(&self, endpoint: &WebhookEndpoint, max_attempts: u32) -> Result<()> {
+        let key = endpoint.key();
+
+        loop {
+            let row = sqlx::query(
+                "SELECT * FROM wallet_state_webhook_outbox WHERE endpoint = ? AND delivered_at IS NULL AND failed = 0 ORDER BY id LIMIT 1",
+            )
+            .bind(&key)
+            .fetch_optional(&self.pool)
+            .await?;
+            let Some(row) = row else {
+                return Ok(());
+            };
+            let entry = OutboxEntry::from_row(row)?;
+            if entry.next_attempt_at > now() {
+                return Ok(());
+            }
+
+            match self.post(endpoint, &entry).await {
+                Ok(()) => {
+                    debug!("webhook {} delivered to {}", entry.id, endpoint.url);
+                    sqlx::query(
+                        "UPDATE wallet_state_webhook_outbox SET attempts = ?, delivered_at = ?, last_error = NULL WHERE id = ?",
+                    )
+                    .bind(entry.attempts + 1)
+                    .bind(now())
+                    .bind(entry.id)
+                    .execute(&self.pool)
+                    .await?;
+                }
+                Err(e) => {
+                    let attempts = entry.attempts + 1;
+                    let failed = attempts >= max_attempts;
+                    let next_attempt_at = now() + retry_delay(attempts).as_millis() as i64;
+                    if failed {
+                        error!(
+                            "giving up webhook {} to {} after {} attempts: {:?}",
+                            entry.id, endpoint.url, attempts, e
+                        );
+                    } else {
+                        warn!(
+                            "webhook {} to {} failed, attempt {}: {:?}",
+                            entry.id, endpoint.url, attempts, e
+                        );
+                    }
+                    sqlx::query(
+                        "UPDATE wallet_state_webhook_outbox SET attempts = ?, next_attempt_at = ?, failed = ?, last_error = ? WHERE id = ?",
+                    )
+                    .bind(attempts)
+                    .bind(next_attempt_at)
+                    .bind(failed)
+                    .bind(format!("{:#}", e))
+                    .bind(entry.id)
+                    .execute(&self.pool)
+                    .await?;
+
+                    // later events wait for this one, unless it was given up
+                    if !failed {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    async fn post(&self, endpoint: &WebhookEndpoint, entry: &OutboxEntry) -> Result<()> {
+        let body = serde_json::to_string(&WebhookPayload {
+            id: entry.id,
+            wallet_id: self.wallet_id,
+            created_at: entry.created_at,
+            event: entry.event.clone(),
+        })?;
+        let timestamp = now() as u64 / 1000;
+        let signature = sign(&endpoint.secret, timestamp, &body);
+
+        self.client
+            .post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Webhook-Id", entry.id)
+            .header("X-Webhook-Timestamp", timestamp)
+            .header("X-Webhook-Signature", format!("sha256={signature}"))
+            .body(body)
+            .send()
+            .await
+            .context("request failed")?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+impl WalletState {
+    async fn webhook_config(&self) -> Result<Option<WebhookConfig>> {
+        Ok(crate::service::get_state::<Arc<Config>>()
+            .get_webhooks()
+            .await?
+            .filter(|config| !config.endpoints.is_empty()))
+    }
+
+    /// Queues the deposits of a synced block and the deposits that reached
+    /// the confirmation depth with it.
+    pub(super) async fn queue_block_webhooks(
+        &self,
+        tx: &mut SqliteConnection,
+        height: u64,
+        deposits: Vec<WebhookEvent>,
+    ) -> Result<()> {
+        let Some(config) = self.webhook_config().await? else {
+            return Ok(());
+        };
+
+        let mut events = deposits;
+        let confirmations = config.confirmations.max(1);
+        if let Some(confirm_height) = (height + 1).checked_sub(confirmations) {
+            let rows = sqlx::query("SELECT * FROM wallet_state_utxos WHERE confirm_height = ?")
+                .bind(confirm_height as i64)
+                .fetch_all(&mut *tx)
+                .await?;
+            for row in rows {
+                let utxo = UtxoDbData::from_row(row)?;
+                events.push(WebhookEvent::deposit_confirmed(&utxo, confirmations));
+            }
+        }
+
+        self.webhooks.enqueue(tx, &config, &events).await
+    }
+
+    /// Queues the deposits added by a rescan up to `tip`, with their
+    /// confirmation if they are deep enough already.
+    pub(super) async fn queue_rescan_webhooks(
+        &self,
+        tx: &mut SqliteConnection,
+        tip: u64,
+        utxos: &[UtxoDbData],
+    ) -> Result<()> {
+        let Some(config) = self.webhook_config().await? else {
+            return Ok(());
+        };
+
+        let confirmations = config.confirmations.max(1);
+        let mut events = Vec::with_capacity(utxos.len() * 2);
+        for utxo in utxos {
+            events.push(WebhookEvent::deposit(utxo));
+            if tip + 1 >= utxo.confirm_height as u64 + confirmations {
+                events.push(WebhookEvent::deposit_confirmed(utxo, confirmations));
+            }
+        }
+
+        self.webhooks.enqueue(tx, &config, &events).await
+    }
+
+    /// Queues the deposits rolled back by a reorganization to `height`.
+    pub(super) async fn queue_revert_webhooks(
+        &self,
+        tx: &mut SqliteConnection,
+        height: u64,
+    ) -> Result<()> {
+        let Some(config) = self.webhook_config().await? else {
+            return Ok(());
+        };
+
+        let rows = sqlx::query(
+            "SELECT * FROM wallet_state_utxos WHERE confirm_height > ? ORDER BY confirm_height, id",
+        )
+        .bind(height as i64)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut events = Vec::with_capacity(rows.len());
+        for row in rows {
+            events.push(WebhookEvent::deposit_reverted(&UtxoDbData::from_row(row)?));
+        }
+
+        self.webhooks.enqueue(tx, &config, &events).await
+    }
+}
+
+impl SyncState {
+    /// Delivers queued webhooks while the wallet is open.
+    pub(super) async fn run_webhooks(self: Arc<Self>) {
+        loop {
+            match self.wallet.webhook_config().await {
+                Ok(Some(config)) => self.wallet.webhooks.deliver_due(&config).await,
+                Ok(None) => {}
+                Err(e) => error!("failed to read webhook config: {:?}", e),
+            }
+            self.wallet.webhooks.wait(WEBHOOK_POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::Router;
+
+    use super::*;
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    /// Receiver answering the first request with an error.
+    async fn receiver() -> (String, Received) {
+        let received = Received::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State(received): State<Received>, headers: HeaderMap, body: String| async move {
+                        let mut received = received.lock().unwrap();
+                        received.push((headers, body));
+                        match received.len() {
+                            1 => StatusCode::INTERNAL_SERVER_ERROR,
+                            _ => StatusCode::OK,
+                        }
+                    },
+                ),
+            )
+            .with_state(received.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    fn deposit(height: u64) -> WebhookEvent {
+        WebhookEvent::Deposit {
+            utxo_hash: format!("utxo{height}"),
+            amount: "1".to_string(),
+            height,
+            block_digest: format!("block{height}"),
+        }
+    }
+
+    async fn outbox() -> WebhookOutbox {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        WebhookOutbox::new(pool, 7).await.unwrap()
+    }
+
+    async fn count(outbox: &WebhookOutbox, filter: &str) -> i64 {
+        sqlx::query(&format!(
+            "SELECT COUNT(*) FROM wallet_state_webhook_outbox WHERE {filter}"
+        ))
+        .fetch_one(&outbox.pool)
+        .await
+        .unwrap()
+        .get(0)
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        assert_eq!(retry_delay(1), MIN_RETRY_DELAY);
+        assert_eq!(retry_delay(3), MIN_RETRY_DELAY * 4);
+        assert_eq!(retry_delay(100), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn delivers_in_order_after_retry() {
+        let (url, received) = receiver().await;
+        let outbox = outbox().await;
+        let config = WebhookConfig {
+            endpoints: vec![WebhookEndpoint {
+                id: None,
+                url,
+                secret: "secret".to_string(),
+            }],
+            confirmations: DEFAULT_WEBHOOK_CONFIRMATIONS,
+            max_attempts: DEFAULT_WEBHOOK_MAX_ATTEMPTS,
+        };
+
+        let mut tx = outbox.pool.begin().await.unwrap();
+        outbox
+            .enqueue(&mut *tx, &config, &[deposit(1), deposit(2)])
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        // the first delivery fails and holds back the second event
+        outbox.deliver_due(&config).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+        outbox.deliver_due(&config).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+
+        sqlx::query("UPDATE wallet_state_webhook_outbox SET next_attempt_at = 0")
+            .execute(&outbox.pool)
+            .await
+            .unwrap();
+        outbox.deliver_due(&config).await;
+
+        let received = received.lock().unwrap();
+        let payloads = received
+            .iter()
+            .map(|(headers, body)| {
+                let timestamp = headers["x-webhook-timestamp"].to_str().unwrap();
+                assert_eq!(
+                    headers["x-webhook-signature"].to_str().unwrap(),
+                    format!(
+                        "sha256={}",
+                        sign("secret", timestamp.parse().unwrap(), body)
+                    )
+                );
+                let payload: WebhookPayload = serde_json::from_str(body).unwrap();
+                assert_eq!(
+                    headers["x-webhook-id"].to_str().unwrap(),
+                    payload.id.to_string()
+                );
+                assert_eq!(payload.wallet_id, 7);
+                (payload.id, payload.event)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            payloads,
+            vec![(1, deposit(1)), (1, deposit(1)), (2, deposit(2))]
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoints_sharing_a_url_are_separate() {
+        let (url, received) = receiver().await;
+        let outbox = outbox().await;
+        let endpoint = |secret: &str| WebhookEndpoint {
+            id: None,
+            url: url.clone(),
+            secret: secret.to_string(),
+        };
+        let config = WebhookConfig {
+            endpoints: vec![endpoint("a"), endpoint("b")],
+            confirmations: DEFAULT_WEBHOOK_CONFIRMATIONS,
+            max_attempts: DEFAULT_WEBHOOK_MAX_ATTEMPTS,
+        };
+
+        let mut tx = outbox.pool.begin().await.unwrap();
+        outbox
+            .enqueue(&mut *tx, &config, &[deposit(1)])
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        for secret in ["a", "b"] {
+            let filter = format!("endpoint = '{}'", endpoint(secret).key());
+            assert_eq!(count(&outbox, &filter).await, 1);
+        }
+
+        // one endpoint gets the failed first response and retries alone
+        outbox.deliver_due(&config).await;
+        assert_eq!(count(&outbox, "delivered_at IS NOT NULL").await, 1);
+        sqlx::query("UPDATE wallet_state_webhook_outbox SET next_attempt_at = 0")
+            .execute(&outbox.pool)
+            .await
+            .unwrap();
+        outbox.deliver_due(&config).await;
+        assert_eq!(count(&outbox, "delivered_at IS NOT NULL").await, 2);
+
+        let signed_with = |secret: &str| {
+            received.lock().unwrap().iter().any(|(headers, body)| {
+                let timestamp = headers["x-webhook-timestamp"].to_str().unwrap();
+                headers["x-webhook-signature"].to_str().unwrap()
+                    == format!("sha256={}", sign(secret, timestamp.parse().unwrap(), body))
+            })
+        };
+        assert!(signed_with("a"));
+        assert!(signed_with("b"));
+    }
+
+    #[tokio::test]
+    async fn prunes_delivered_after_retention() {
+        let outbox = outbox().await;
+        let config = WebhookConfig {
+            endpoints: vec![WebhookEndpoint {
+                id: Some("hook".to_string()),
+                url: "http://127.0.0.1:1/hook".to_string(),
+                secret: "secret".to_string(),
+            }],
+            confirmations: DEFAULT_WEBHOOK_CONFIRMATIONS,
+            max_attempts: DEFAULT_WEBHOOK_MAX_ATTEMPTS,
+        };
+
+        let mut tx = outbox.pool.begin().await.unwrap();
+        outbox
+            .enqueue(&mut *tx, &config, &[deposit(1), deposit(2), deposit(3)])
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        let expired = now() - DELIVERED_RETENTION_MS - 1;
+        sqlx::query("UPDATE wallet_state_webhook_outbox SET delivered_at = ? WHERE id = 1")
+            .bind(expired)
+            .execute(&outbox.pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE wallet_state_webhook_outbox SET delivered_at = ? WHERE id = 2")
+            .bind(now())
+            .execute(&outbox.pool)
+            .await
+            .unwrap();
+
+        outbox.prune_delivered().await.unwrap();
+        let ids = sqlx::query("SELECT id FROM wallet_state_webhook_outbox ORDER BY id")
+            .fetch_all(&outbox.pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| row.get::<i64, _>(0))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![2, 3]);
+    }
+}
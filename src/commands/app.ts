@@ -1,4 +1,4 @@
-import { invoke } from "@tauri-apps/api/core";
+import { invoke } from "./invoke";
 
 export async function run_rpc_server() {
     await invoke('run_rpc_server', {})
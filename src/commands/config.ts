@@ -1,5 +1,5 @@
 import { BlockCacheFile } from "@/store/types";
-import { invoke } from "@tauri-apps/api/core"; 
+import { invoke } from "./invoke"; 
 export async function get_network(): Promise<string> {
     return await invoke('get_network', {})
 }
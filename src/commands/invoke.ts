@@ -0,0 +1,20 @@
+import { invoke as tauriInvoke, InvokeArgs } from "@tauri-apps/api/core";
+
+// error returned by the wallet commands, `code` is stable across versions
+export interface CommandError {
+    code: string,
+    message: string,
+}
+
+export function isCommandError(error: unknown): error is CommandError {
+    return typeof error === "object" && error !== null && "code" in error && "message" in error;
+}
+
+// rejects with the message of a CommandError so callers can show it as is
+export async function invoke<T>(cmd: string, args?: InvokeArgs): Promise<T> {
+    try {
+        return await tauriInvoke<T>(cmd, args);
+    } catch (error) {
+        throw isCommandError(error) ? error.message : error;
+    }
+}
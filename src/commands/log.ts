@@ -1,4 +1,4 @@
-import { invoke } from "@tauri-apps/api/core";
+import { invoke } from "./invoke";
 
 export async function get_logs(): Promise<string[]> {
     return await invoke('get_logs', {})
@@ -3,7 +3,6 @@ export const LATEST_BLOCKHEIGHT = '/rpc/block/tip_height'
 
 export const SCAN_BLOCK_STATE = '/rpc/scan/state'
 
-export const START_SCAN_BLOCK = '/rpc/scan/0/0'
 // History records
 export const WALLET_ACTIVITY_HISTORY = '/rpc/wallet/history'
 
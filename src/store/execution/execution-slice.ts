@@ -185,7 +185,8 @@ export const requestSedExecutionTransaction = createAsyncThunk<
         } catch (error: any) {
             let errorMessage = "Send transaction failed!"
             if (error.response && error.response.data) {
-                errorMessage = error.response.data
+                // errors are {code, message} objects
+                errorMessage = error.response.data.message || error.response.data
             }
             message = errorMessage
         }
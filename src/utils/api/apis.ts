@@ -84,7 +84,7 @@ export const forgetPendingTransaction = ({
     let {rpc} = handleServiceUrl(serverUrl)
     return service({
         url: url(`${rpc}${WALLET_FORGET_TX}${txid}`),
-        method: "GET",
+        method: "DELETE",
     });
 }
 
@@ -21,8 +21,14 @@ export interface WalletBalanceData {
 
 
 export interface PendingTransaction {
-    tx_id: string;
+    id: number;
+    tx_id: string | null;
     status: string;
+    detail?: string | number;
+    created_at: number;
+    updated_at: number;
+    attempts: number;
+    last_error: string | null;
 }
 
 export interface HistoryData {